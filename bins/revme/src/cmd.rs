@@ -3,6 +3,7 @@ pub mod blockchaintest;
 pub mod bytecode;
 pub mod evmrunner;
 pub mod statetest;
+pub mod witness;
 
 use clap::Parser;
 
@@ -24,6 +25,8 @@ pub enum MainCmd {
     Blockchaintest(blockchaintest::Cmd),
     /// Execute Ethereum blockchain tests.
     Btest(blockchaintest::Cmd),
+    /// Re-execute a block using only the witness data.
+    Witness(witness::Cmd),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -33,6 +36,8 @@ pub enum Error {
     #[error(transparent)]
     Blockchaintest(#[from] blockchaintest::Error),
     #[error(transparent)]
+    Witness(#[from] witness::Error),
+    #[error(transparent)]
     EvmRunnerErrors(#[from] evmrunner::Errors),
     #[error("Custom error: {0}")]
     Custom(&'static str),
@@ -50,6 +55,7 @@ impl MainCmd {
                 cmd.run();
             }
             Self::Blockchaintest(cmd) | Self::Btest(cmd) => cmd.run()?,
+            Self::Witness(cmd) => cmd.run()?,
         }
         Ok(())
     }
@@ -754,7 +754,7 @@ fn execute_blockchain_test(
         let mut evm = evm_context.build_mainnet_with_inspector(TracerEip3155::new_stdout());
 
         // Pre block system calls
-        pre_block::pre_block_transition(&mut evm, spec_id, parent_block_hash, beacon_root);
+        pre_block::pre_block_transition(&mut evm, parent_block_hash, beacon_root);
 
         // Execute each transaction in the block
         for (tx_idx, tx) in transactions.iter().enumerate() {
@@ -945,12 +945,25 @@ fn execute_blockchain_test(
         evm.db_mut().bump_bal_index();
 
         // uncle rewards are not implemented yet
-        post_block::post_block_transition(
+        if let Err(e) = post_block::post_block_transition(
             &mut evm,
-            &block_env,
             block.withdrawals.as_deref().unwrap_or_default(),
-            spec_id,
-        );
+        ) {
+            if !should_fail {
+                return Err(TestExecutionError::PostBlock {
+                    block_idx,
+                    error: e.to_string(),
+                });
+            }
+            if json_output {
+                let output = json!({
+                    "block": block_idx,
+                    "error": e.to_string(),
+                    "status": "expected_failure"
+                });
+                print_json(&output);
+            }
+        }
 
         // insert present block hash.
         state
@@ -1002,7 +1015,7 @@ fn execute_blockchain_test(
 }
 
 /// Convert ForkSpec to SpecId
-fn fork_to_spec_id(fork: ForkSpec) -> SpecId {
+pub(crate) fn fork_to_spec_id(fork: ForkSpec) -> SpecId {
     match fork {
         ForkSpec::Frontier => SpecId::FRONTIER,
         ForkSpec::Homestead | ForkSpec::FrontierToHomesteadAt5 => SpecId::HOMESTEAD,
@@ -1132,6 +1145,9 @@ pub enum TestExecutionError {
     #[error("BAL error")]
     BalMismatchError,
 
+    #[error("Post-block transition failed at block {block_idx}: {error}")]
+    PostBlock { block_idx: usize, error: String },
+
     #[error(
         "Post-state validation failed for {address:?}.{field}: expected {expected}, got {actual}"
     )]
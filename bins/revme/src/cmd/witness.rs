@@ -0,0 +1,511 @@
+use crate::cmd::blockchaintest::fork_to_spec_id;
+use clap::Parser;
+use revm::{
+    block::{self, BlockExecutionOutput, BlockExecutor, BlockInput},
+    context::{cfg::CfgEnv, BlockEnv, TxEnv},
+    context_interface::{block::BlobExcessGasAndPrice, result::ExecutionResult},
+    database::{
+        witness::{RecordingDb, Witness, WitnessDb, WitnessError},
+        Database, InMemoryDB, PlainAccount, State,
+    },
+    primitives::B256,
+    state::{AccountInfo, Bytecode},
+    statetest_types::blockchain::{BlockHeader, BlockchainTest, BlockchainTestCase},
+    trie::{receipts_root, witness_state_root, StateTrie},
+    Context, MainBuilder, MainContext,
+};
+use serde::de::DeserializeOwned;
+use std::{fs, path::PathBuf};
+use thiserror::Error;
+
+/// `witness` subcommand
+///
+/// Checks the witness against the state root of the parent block, re-executes the block using
+/// only the witness and checks the gas used, the receipts root and the state root against the
+/// block header.
+///
+/// With `--generate` the witness of the block is recorded from the pre-state of the test and
+/// written to the witness path instead.
+#[derive(Parser, Debug)]
+pub struct Cmd {
+    /// Path to the blockchain test JSON containing the block
+    #[arg(required = true)]
+    block: PathBuf,
+    /// Path to the witness JSON with the pre-state of the block
+    #[arg(required = true)]
+    witness: PathBuf,
+    /// Name of the test case, first test case is used if not set
+    #[arg(long)]
+    test: Option<String>,
+    /// Index of the block in the test case
+    #[arg(long, default_value_t = 0)]
+    block_index: usize,
+    /// Record the witness of the block and write it to the witness path
+    #[arg(long)]
+    generate: bool,
+}
+
+impl Cmd {
+    /// Runs `witness` command.
+    pub fn run(&self) -> Result<(), Error> {
+        let test: BlockchainTest = read_json(&self.block)?;
+        let (name, test_case) = match &self.test {
+            Some(name) => test
+                .0
+                .get_key_value(name)
+                .ok_or_else(|| Error::TestNotFound(name.clone()))?,
+            None => test
+                .0
+                .iter()
+                .next()
+                .ok_or_else(|| Error::NoTestCases(self.block.clone()))?,
+        };
+
+        if self.generate {
+            println!(
+                "Recording witness of block {} of {name}...",
+                self.block_index
+            );
+            let witness = generate_witness(test_case, self.block_index)?;
+            let json = serde_json::to_string_pretty(&witness).map_err(Error::JsonEncode)?;
+            fs::write(&self.witness, json)
+                .map_err(|e| Error::FileWrite(self.witness.clone(), e))?;
+            println!("Witness written to {}", self.witness.display());
+            return Ok(());
+        }
+
+        let witness: Witness = read_json(&self.witness)?;
+        println!("Re-executing block {} of {name}...", self.block_index);
+        verify_block(test_case, self.block_index, witness)?;
+        println!("Witness and block results match the block headers");
+        Ok(())
+    }
+}
+
+/// Records the witness of the block on top of the pre-state of the test case.
+///
+/// Blocks before it are executed to get its pre-state. Witness contains the trie nodes that
+/// prove it against the state root of the parent block.
+fn generate_witness(test_case: &BlockchainTestCase, block_index: usize) -> Result<Witness, Error> {
+    let mut db = InMemoryDB::default();
+    let mut accounts = Vec::new();
+    for (address, account) in test_case.pre.clone().into_genesis_state() {
+        let info = AccountInfo::default()
+            .with_balance(account.balance)
+            .with_nonce(account.nonce)
+            .with_code(Bytecode::new_raw(account.code));
+        db.insert_account_info(address, info.clone());
+        db.replace_account_storage(address, account.storage.clone())
+            .map_err(|e| Error::Execution(e.to_string()))?;
+        accounts.push((
+            address,
+            PlainAccount {
+                info,
+                storage: account.storage,
+            },
+        ));
+    }
+    let mut trie = StateTrie::from_plain_accounts(
+        accounts
+            .iter()
+            .map(|(address, account)| (*address, account)),
+    );
+
+    let mut state = State::builder()
+        .with_database(db)
+        .with_bundle_update()
+        .build();
+    state
+        .block_hashes
+        .insert(0, test_case.genesis_block_header.hash);
+    for index in 0..block_index {
+        let output = execute_block(test_case, index, &mut state)?;
+        trie.apply_bundle(&output.bundle);
+        let header = block_header(test_case, index)?;
+        state
+            .block_hashes
+            .insert(header.number.try_into().map_err(overflow)?, header.hash);
+    }
+
+    let mut recording = State::builder()
+        .with_database(RecordingDb::new(&mut state))
+        .with_bundle_update()
+        .build();
+    execute_block(test_case, block_index, &mut recording)?;
+    let mut witness = recording.database.take_witness();
+    trie.prove_witness(&mut witness);
+    Ok(witness)
+}
+
+/// Verifies the witness against the parent state root and re-executes the block on top of it.
+///
+/// The gas used, the receipts root and the post-state root computed from the witness nodes are
+/// checked against the header. Block hashes in the witness are not verified.
+fn verify_block(
+    test_case: &BlockchainTestCase,
+    block_index: usize,
+    witness: Witness,
+) -> Result<(), Error> {
+    let header = block_header(test_case, block_index)?;
+    let parent = parent_header(test_case, block_index)?;
+    witness.verify(parent.state_root)?;
+
+    let mut state = State::builder()
+        .with_database(WitnessDb::new(witness))
+        .with_bundle_update()
+        .build();
+    let output = execute_block(test_case, block_index, &mut state)?;
+    let witness = &state.database.witness;
+    println!(
+        "Executed {} transactions, gas used {}",
+        output.results.len(),
+        output.gas_used
+    );
+
+    let gas_used: u64 = header.gas_used.try_into().map_err(overflow)?;
+    if output.gas_used != gas_used {
+        return Err(Error::GasUsedMismatch {
+            expected: gas_used,
+            got: output.gas_used,
+        });
+    }
+    let receipts_root = receipts_root(&output.receipts);
+    if receipts_root != header.receipt_trie {
+        return Err(Error::ReceiptsRootMismatch {
+            expected: header.receipt_trie,
+            got: receipts_root,
+        });
+    }
+    let state_root =
+        witness_state_root(witness, parent.state_root, &output.bundle).map_err(Error::PostState)?;
+    if state_root != header.state_root {
+        return Err(Error::StateRootMismatch {
+            expected: header.state_root,
+            got: state_root,
+        });
+    }
+    Ok(())
+}
+
+/// Executes the block of the test case on top of the state.
+fn execute_block<DB: Database>(
+    test_case: &BlockchainTestCase,
+    block_index: usize,
+    state: &mut State<DB>,
+) -> Result<BlockExecutionOutput<ExecutionResult>, Error> {
+    let (cfg, block_env, input, transactions) = block_inputs(test_case, block_index)?;
+    let evm = Context::mainnet()
+        .with_cfg(cfg)
+        .with_db(state)
+        .build_mainnet();
+    BlockExecutor::mainnet(evm)
+        .execute_block(block_env, &input, transactions)
+        .map_err(|error| Error::Execution(error.to_string()))
+}
+
+/// Returns the configuration, block environment, input and transactions of the block.
+fn block_inputs(
+    test_case: &BlockchainTestCase,
+    block_index: usize,
+) -> Result<(CfgEnv, BlockEnv, BlockInput, Vec<TxEnv>), Error> {
+    let header = block_header(test_case, block_index)?;
+    let block = &test_case.blocks[block_index];
+
+    let spec_id = fork_to_spec_id(test_case.network);
+    let mut cfg = CfgEnv::default();
+    cfg.set_spec_and_mainnet_gas_params(spec_id);
+
+    // Excess blob gas of the block env is derived from the parent header.
+    let parent = parent_header(test_case, block_index)?;
+    let parent_excess_blob_gas = parent
+        .excess_blob_gas
+        .unwrap_or_default()
+        .try_into()
+        .map_err(overflow)?;
+    let block_env = header.to_block_env(Some(BlobExcessGasAndPrice::new_with_spec(
+        parent_excess_blob_gas,
+        spec_id,
+    )));
+
+    let transactions = block
+        .transactions
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            tx.to_tx_env()
+                .map_err(|error| Error::InvalidTransaction { index, error })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let withdrawals = block
+        .withdrawals
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .map(|withdrawal| {
+            Ok(block::Withdrawal {
+                index: withdrawal.index.try_into().map_err(overflow)?,
+                validator_index: withdrawal.validator_index.try_into().map_err(overflow)?,
+                address: withdrawal.address,
+                amount: withdrawal.amount.try_into().map_err(overflow)?,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    let input = BlockInput {
+        parent_hash: Some(header.parent_hash),
+        parent_beacon_block_root: header.parent_beacon_block_root,
+        withdrawals,
+    };
+    Ok((cfg, block_env, input, transactions))
+}
+
+fn block_header(test_case: &BlockchainTestCase, block_index: usize) -> Result<&BlockHeader, Error> {
+    test_case
+        .blocks
+        .get(block_index)
+        .and_then(|block| block.block_header.as_ref())
+        .ok_or(Error::BlockNotFound(block_index))
+}
+
+fn parent_header(
+    test_case: &BlockchainTestCase,
+    block_index: usize,
+) -> Result<&BlockHeader, Error> {
+    match block_index {
+        0 => Ok(&test_case.genesis_block_header),
+        index => block_header(test_case, index - 1),
+    }
+}
+
+fn overflow<T: std::fmt::Display>(error: T) -> Error {
+    Error::InvalidNumber(error.to_string())
+}
+
+/// Reads and decodes the JSON file.
+fn read_json<T: DeserializeOwned>(path: &PathBuf) -> Result<T, Error> {
+    let content = fs::read_to_string(path).map_err(|e| Error::FileRead(path.clone(), e))?;
+    serde_json::from_str(&content).map_err(|e| Error::JsonDecode(path.clone(), e))
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read file {0}: {1}")]
+    FileRead(PathBuf, std::io::Error),
+
+    #[error("Failed to write file {0}: {1}")]
+    FileWrite(PathBuf, std::io::Error),
+
+    #[error("Failed to decode JSON from {0}: {1}")]
+    JsonDecode(PathBuf, serde_json::Error),
+
+    #[error("Failed to encode JSON: {0}")]
+    JsonEncode(serde_json::Error),
+
+    #[error("No test cases found in: {0}")]
+    NoTestCases(PathBuf),
+
+    #[error("Test case not found: {0}")]
+    TestNotFound(String),
+
+    #[error("Block {0} with header not found in the test case")]
+    BlockNotFound(usize),
+
+    #[error("Invalid transaction {index}: {error}")]
+    InvalidTransaction { index: usize, error: String },
+
+    #[error("Number out of range: {0}")]
+    InvalidNumber(String),
+
+    #[error("Witness does not match the parent state root: {0}")]
+    Witness(#[from] WitnessError),
+
+    #[error("Witness does not prove the changed state: {0}")]
+    PostState(WitnessError),
+
+    #[error("Block execution failed: {0}")]
+    Execution(String),
+
+    #[error("Gas used mismatch: expected {expected}, got {got}")]
+    GasUsedMismatch { expected: u64, got: u64 },
+
+    #[error("Receipts root mismatch: expected {expected}, got {got}")]
+    ReceiptsRootMismatch { expected: B256, got: B256 },
+
+    #[error("State root mismatch: expected {expected}, got {got}")]
+    StateRootMismatch { expected: B256, got: B256 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::{
+        primitives::{address, bytes, Address, U256},
+        statetest_types::blockchain::{
+            Account, Block, ForkSpec, SealEngine, State as TestState, Transaction, Withdrawal,
+        },
+        trie::state_root,
+    };
+    use std::collections::BTreeMap;
+
+    const SENDER: Address = address!("0x1000000000000000000000000000000000000001");
+    const COUNTER: Address = address!("0x2000000000000000000000000000000000000002");
+
+    /// Returns the accounts of the pre-state.
+    fn plain_accounts(pre: &TestState) -> Vec<(Address, PlainAccount)> {
+        pre.clone()
+            .into_genesis_state()
+            .into_iter()
+            .map(|(address, account)| {
+                let info = AccountInfo::default()
+                    .with_balance(account.balance)
+                    .with_nonce(account.nonce)
+                    .with_code(Bytecode::new_raw(account.code));
+                let storage = account.storage;
+                (address, PlainAccount { info, storage })
+            })
+            .collect()
+    }
+
+    /// Block with a call to the counter and a withdrawal on top of a few accounts.
+    fn test_case() -> BlockchainTestCase {
+        let mut pre = BTreeMap::new();
+        for i in 3..20u8 {
+            pre.insert(
+                Address::with_last_byte(i),
+                Account {
+                    balance: U256::from(i),
+                    ..Default::default()
+                },
+            );
+        }
+        pre.insert(
+            SENDER,
+            Account {
+                balance: U256::from(10).pow(U256::from(18)),
+                ..Default::default()
+            },
+        );
+        pre.insert(
+            COUNTER,
+            Account {
+                // `PUSH0 SLOAD PUSH1 1 ADD PUSH0 SSTORE STOP`
+                code: bytes!("5f546001015f5500"),
+                nonce: U256::from(1),
+                storage: BTreeMap::from([(U256::ZERO, U256::from(5))]),
+                ..Default::default()
+            },
+        );
+        let pre = TestState(pre);
+        let plain = plain_accounts(&pre);
+
+        let transaction: Transaction = serde_json::from_value(serde_json::json!({
+            "sender": SENDER,
+            "to": COUNTER,
+            "data": "0x",
+            "gasLimit": "0x186a0",
+            "gasPrice": "0x1",
+            "nonce": "0x0",
+            "value": "0x0",
+            "r": "0x0",
+            "s": "0x0",
+            "v": "0x0",
+        }))
+        .unwrap();
+        let block = Block {
+            block_header: Some(BlockHeader {
+                number: U256::from(1),
+                gas_limit: U256::from(30_000_000),
+                timestamp: U256::from(12),
+                base_fee_per_gas: Some(U256::ZERO),
+                ..Default::default()
+            }),
+            transactions: Some(vec![transaction]),
+            withdrawals: Some(vec![Withdrawal {
+                index: U256::ZERO,
+                validator_index: U256::ZERO,
+                address: Address::with_last_byte(3),
+                amount: U256::from(1),
+            }]),
+            ..Default::default()
+        };
+        BlockchainTestCase {
+            genesis_block_header: BlockHeader {
+                state_root: state_root(plain.iter().map(|(address, account)| (*address, account))),
+                ..Default::default()
+            },
+            genesis_rlp: None,
+            blocks: vec![block],
+            post_state: None,
+            pre,
+            lastblockhash: B256::ZERO,
+            network: ForkSpec::Shanghai,
+            seal_engine: SealEngine::NoProof,
+        }
+    }
+
+    #[test]
+    fn generate_and_verify() {
+        let mut test_case = test_case();
+        let witness = generate_witness(&test_case, 0).unwrap();
+        assert!(witness.accounts.contains_key(&COUNTER));
+        assert!(!witness.nodes.is_empty());
+
+        // expected results of the block.
+        let mut state = State::builder()
+            .with_database(WitnessDb::new(witness.clone()))
+            .with_bundle_update()
+            .build();
+        let output = execute_block(&test_case, 0, &mut state).unwrap();
+        assert!(output.results.iter().all(|result| result.is_success()));
+        let plain = plain_accounts(&test_case.pre);
+        let mut trie =
+            StateTrie::from_plain_accounts(plain.iter().map(|(address, acc)| (*address, acc)));
+        trie.apply_bundle(&output.bundle);
+        let header = test_case.blocks[0].block_header.as_mut().unwrap();
+        header.gas_used = U256::from(output.gas_used);
+        header.receipt_trie = receipts_root(&output.receipts);
+        header.state_root = trie.state_root();
+        verify_block(&test_case, 0, witness.clone()).unwrap();
+
+        // block that claims the unchanged post-state.
+        let post_state_root = trie.state_root();
+        let header = test_case.blocks[0].block_header.as_mut().unwrap();
+        header.state_root = test_case.genesis_block_header.state_root;
+        assert!(matches!(
+            verify_block(&test_case, 0, witness.clone()),
+            Err(Error::StateRootMismatch { got, .. }) if got == post_state_root
+        ));
+        let header = test_case.blocks[0].block_header.as_mut().unwrap();
+        header.state_root = post_state_root;
+
+        let mut tampered = witness.clone();
+        tampered
+            .storage
+            .get_mut(&COUNTER)
+            .unwrap()
+            .insert(U256::ZERO, U256::from(6));
+        assert!(matches!(
+            verify_block(&test_case, 0, tampered),
+            Err(Error::Witness(WitnessError::StorageMismatch(COUNTER, _)))
+        ));
+
+        let header = test_case.blocks[0].block_header.as_mut().unwrap();
+        header.receipt_trie = B256::ZERO;
+        assert!(matches!(
+            verify_block(&test_case, 0, witness),
+            Err(Error::ReceiptsRootMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn withdrawal_overflow() {
+        let mut test_case = test_case();
+        test_case.blocks[0].withdrawals.as_mut().unwrap()[0].amount = U256::MAX;
+        assert!(matches!(
+            generate_witness(&test_case, 0),
+            Err(Error::InvalidNumber(_))
+        ));
+    }
+}
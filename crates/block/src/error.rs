@@ -0,0 +1,125 @@
+use core::fmt;
+use primitives::Address;
+use std::string::String;
+
+/// Error that can happen while executing a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockExecutionError<E> {
+    /// Transaction at the given index failed to execute.
+    Transaction {
+        /// Index of the transaction inside the block.
+        index: usize,
+        /// Error returned by the EVM.
+        error: E,
+    },
+    /// Transaction gas limit is more than the gas left in the block.
+    TransactionGasLimitMoreThanAvailableBlockGas {
+        /// Index of the transaction inside the block.
+        index: usize,
+        /// Gas limit of the transaction.
+        transaction_gas_limit: u64,
+        /// Gas that is left in the block.
+        block_available_gas: u64,
+    },
+    /// System call to the given contract returned an EVM error.
+    SystemCall {
+        /// Address of the system contract.
+        address: Address,
+        /// Error returned by the EVM.
+        error: E,
+    },
+    /// System call to the given contract reverted or halted.
+    ///
+    /// Request system calls (EIP-7002 and EIP-7251) are required to succeed, otherwise the block is invalid.
+    SystemCallFailed {
+        /// Address of the system contract.
+        address: Address,
+    },
+    /// Request system contract (EIP-7002 or EIP-7251) has no code, the block is invalid.
+    SystemContractMissing {
+        /// Address of the system contract.
+        address: Address,
+    },
+    /// Database error that happened outside of transaction execution,
+    /// e.g. while applying block rewards or withdrawals.
+    Database(E),
+    /// Deposit event emitted by the deposit contract has invalid layout.
+    InvalidDepositEventLayout,
+    /// Custom error returned by the block hooks.
+    Custom(String),
+}
+
+impl<E> BlockExecutionError<E> {
+    /// Maps the EVM error with the given function.
+    pub fn map_err<F, OE>(self, op: F) -> BlockExecutionError<OE>
+    where
+        F: FnOnce(E) -> OE,
+    {
+        match self {
+            Self::Transaction { index, error } => BlockExecutionError::Transaction {
+                index,
+                error: op(error),
+            },
+            Self::TransactionGasLimitMoreThanAvailableBlockGas {
+                index,
+                transaction_gas_limit,
+                block_available_gas,
+            } => BlockExecutionError::TransactionGasLimitMoreThanAvailableBlockGas {
+                index,
+                transaction_gas_limit,
+                block_available_gas,
+            },
+            Self::SystemCall { address, error } => BlockExecutionError::SystemCall {
+                address,
+                error: op(error),
+            },
+            Self::SystemCallFailed { address } => BlockExecutionError::SystemCallFailed { address },
+            Self::SystemContractMissing { address } => {
+                BlockExecutionError::SystemContractMissing { address }
+            }
+            Self::Database(error) => BlockExecutionError::Database(op(error)),
+            Self::InvalidDepositEventLayout => BlockExecutionError::InvalidDepositEventLayout,
+            Self::Custom(e) => BlockExecutionError::Custom(e),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for BlockExecutionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transaction { index, error } => write!(f, "transaction {index} failed: {error}"),
+            Self::TransactionGasLimitMoreThanAvailableBlockGas {
+                index,
+                transaction_gas_limit,
+                block_available_gas,
+            } => write!(
+                f,
+                "transaction {index} gas limit {transaction_gas_limit} is more than available block gas {block_available_gas}"
+            ),
+            Self::SystemCall { address, error } => {
+                write!(f, "system call to {address} failed: {error}")
+            }
+            Self::SystemCallFailed { address } => {
+                write!(f, "system call to {address} reverted or halted")
+            }
+            Self::SystemContractMissing { address } => {
+                write!(f, "system contract {address} has no code")
+            }
+            Self::Database(error) => write!(f, "database error: {error}"),
+            Self::InvalidDepositEventLayout => write!(f, "invalid deposit event layout"),
+            Self::Custom(e) => f.write_str(e),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for BlockExecutionError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Transaction { error, .. }
+            | Self::SystemCall { error, .. }
+            | Self::Database(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{BlockExecutionError, BlockHooks, EthBlockHooks, Receipt, Requests};
+use context_interface::{result::ExecutionResult, Block, Cfg, ContextTr, Transaction};
+use core::borrow::BorrowMut;
+use database::{states::bundle_state::BundleRetention, BundleState, Database, State};
+use handler::{EvmTr, ExecuteCommitEvm};
+use primitives::{Address, B256, ONE_GWEI, U256};
+use std::vec::Vec;
+
+/// Withdrawal of the validator balance ([EIP-4895](https://eips.ethereum.org/EIPS/eip-4895)).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Withdrawal {
+    /// Monotonically increasing withdrawal index.
+    pub index: u64,
+    /// Index of the validator.
+    pub validator_index: u64,
+    /// Recipient of the withdrawal.
+    pub address: Address,
+    /// Withdrawal amount in gwei.
+    pub amount: u64,
+}
+
+impl Withdrawal {
+    /// Returns the withdrawal amount in wei.
+    #[inline]
+    pub fn amount_wei(&self) -> U256 {
+        U256::from(self.amount).saturating_mul(U256::from(ONE_GWEI))
+    }
+}
+
+/// Block data that is not part of the block env and is used by the [`BlockHooks`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockInput {
+    /// Hash of the parent block, used by the EIP-2935 system call.
+    pub parent_hash: Option<B256>,
+    /// Parent beacon block root, used by the EIP-4788 system call.
+    pub parent_beacon_block_root: Option<B256>,
+    /// Withdrawals of the block.
+    pub withdrawals: Vec<Withdrawal>,
+}
+
+/// Output of the block execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockExecutionOutput<R> {
+    /// Execution results of the transactions.
+    pub results: Vec<R>,
+    /// Receipts of the transactions.
+    pub receipts: Vec<Receipt>,
+    /// Gas used by all transactions of the block.
+    pub gas_used: u64,
+    /// EIP-7685 requests of the block.
+    pub requests: Requests,
+    /// Bundle containing the state changes of the block.
+    pub bundle: BundleState,
+}
+
+/// Executes blocks against the [`State`] that is the database of the EVM.
+///
+/// Block is executed in following steps:
+/// * Block env is set and [`BlockHooks::pre_block`] is called.
+/// * Every transaction is executed and committed. BAL index is bumped before each transaction.
+/// * [`BlockHooks::post_block`] is called and the requests are collected.
+/// * Transitions are merged and taken out of [`State`] as a [`BundleState`].
+///
+/// # Note
+///
+/// [`State`] needs to be built with [`StateBuilder::with_bundle_update`](database::StateBuilder::with_bundle_update)
+/// for the transitions to be recorded in the bundle.
+#[derive(Debug)]
+pub struct BlockExecutor<EVM, H = EthBlockHooks> {
+    /// EVM used for execution.
+    evm: EVM,
+    /// Pre- and post-block hooks.
+    hooks: H,
+    /// Retention used when merging transitions into the bundle.
+    retention: BundleRetention,
+}
+
+impl<EVM> BlockExecutor<EVM> {
+    /// Creates new block executor with mainnet block hooks.
+    pub fn mainnet(evm: EVM) -> Self {
+        Self::new(evm, EthBlockHooks::default())
+    }
+}
+
+impl<EVM, H> BlockExecutor<EVM, H> {
+    /// Creates new block executor.
+    pub fn new(evm: EVM, hooks: H) -> Self {
+        Self {
+            evm,
+            hooks,
+            retention: BundleRetention::Reverts,
+        }
+    }
+
+    /// Sets the bundle retention used when merging transitions. Default is [`BundleRetention::Reverts`].
+    pub fn with_bundle_retention(mut self, retention: BundleRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Returns a reference to the EVM.
+    pub fn evm(&self) -> &EVM {
+        &self.evm
+    }
+
+    /// Returns a mutable reference to the EVM.
+    pub fn evm_mut(&mut self) -> &mut EVM {
+        &mut self.evm
+    }
+
+    /// Returns a mutable reference to the block hooks.
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// Consumes the executor and returns the EVM.
+    pub fn into_evm(self) -> EVM {
+        self.evm
+    }
+}
+
+impl<EVM, H, HR> BlockExecutor<EVM, H>
+where
+    EVM: ExecuteCommitEvm<ExecutionResult = ExecutionResult<HR>> + EvmTr,
+    H: BlockHooks<EVM>,
+{
+    /// Executes the block with the given transactions.
+    ///
+    /// On error the block is considered invalid and the state of the [`State`] should be discarded.
+    #[allow(clippy::type_complexity)]
+    pub fn execute_block<DB>(
+        &mut self,
+        block: EVM::Block,
+        input: &BlockInput,
+        transactions: impl IntoIterator<Item = EVM::Tx>,
+    ) -> Result<BlockExecutionOutput<ExecutionResult<HR>>, BlockExecutionError<EVM::Error>>
+    where
+        DB: Database,
+        <EVM::Context as ContextTr>::Db: BorrowMut<State<DB>>,
+    {
+        self.evm.set_block(block);
+        self.state().reset_bal_index();
+
+        self.hooks.pre_block(&mut self.evm, input)?;
+
+        let block_gas_limit = self.evm.ctx_ref().block().gas_limit();
+        let check_block_gas_limit = !self.evm.ctx_ref().cfg().is_block_gas_limit_disabled();
+
+        let transactions = transactions.into_iter();
+        let (lower, _) = transactions.size_hint();
+        let mut results = Vec::with_capacity(lower);
+        let mut receipts = Vec::with_capacity(lower);
+        let mut gas_used = 0u64;
+        for (index, tx) in transactions.enumerate() {
+            let block_available_gas = block_gas_limit.saturating_sub(gas_used);
+            let transaction_gas_limit = tx.gas_limit();
+            if check_block_gas_limit && transaction_gas_limit > block_available_gas {
+                return Err(
+                    BlockExecutionError::TransactionGasLimitMoreThanAvailableBlockGas {
+                        index,
+                        transaction_gas_limit,
+                        block_available_gas,
+                    },
+                );
+            }
+            let tx_type = tx.tx_type();
+
+            self.state().bump_bal_index();
+            let result = self
+                .evm
+                .transact_commit(tx)
+                .map_err(|error| BlockExecutionError::Transaction { index, error })?;
+
+            gas_used += result.gas_used();
+            receipts.push(Receipt::new(tx_type, &result, gas_used));
+            results.push(result);
+        }
+
+        self.state().bump_bal_index();
+        let requests = self.hooks.post_block(&mut self.evm, input, &receipts)?;
+
+        let retention = self.retention;
+        let state = self.state();
+        state.merge_transitions(retention);
+        let bundle = state.take_bundle();
+
+        Ok(BlockExecutionOutput {
+            results,
+            receipts,
+            gas_used,
+            requests,
+            bundle,
+        })
+    }
+
+    /// Returns the [`State`] from the EVM context.
+    #[inline]
+    fn state<DB>(&mut self) -> &mut State<DB>
+    where
+        DB: Database,
+        <EVM::Context as ContextTr>::Db: BorrowMut<State<DB>>,
+    {
+        self.evm.ctx_mut().db_mut().borrow_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block_reward,
+        system_calls::{CONSOLIDATION_REQUEST_ADDRESS, WITHDRAWAL_REQUEST_ADDRESS},
+    };
+    use context::{BlockEnv, CfgEnv, Context, TxEnv};
+    use database::InMemoryDB;
+    use handler::{MainBuilder, MainContext, MainnetContext, MainnetEvm};
+    use primitives::{address, hardfork::SpecId, TxKind, ONE_ETHER};
+    use state::{bytecode::opcode, AccountInfo, Bytecode};
+    use std::vec;
+
+    const CALLER: Address = address!("0x1000000000000000000000000000000000000001");
+    const RECIPIENT: Address = address!("0x2000000000000000000000000000000000000002");
+    const BENEFICIARY: Address = address!("0x3000000000000000000000000000000000000003");
+
+    fn state() -> State<InMemoryDB> {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CALLER,
+            AccountInfo::default().with_balance(U256::from(ONE_ETHER)),
+        );
+        State::builder()
+            .with_database(db)
+            .with_bundle_update()
+            .build()
+    }
+
+    fn block() -> BlockEnv {
+        BlockEnv {
+            number: U256::ONE,
+            beneficiary: BENEFICIARY,
+            gas_limit: 30_000_000,
+            ..Default::default()
+        }
+    }
+
+    fn transfer(nonce: u64) -> TxEnv {
+        TxEnv::builder()
+            .caller(CALLER)
+            .kind(TxKind::Call(RECIPIENT))
+            .value(U256::from(1000))
+            .gas_limit(21_000)
+            .gas_price(0)
+            .nonce(nonce)
+            .build()
+            .unwrap()
+    }
+
+    fn executor(
+        state: &mut State<InMemoryDB>,
+        spec: SpecId,
+    ) -> BlockExecutor<MainnetEvm<MainnetContext<&mut State<InMemoryDB>>>> {
+        let mut cfg = CfgEnv::default();
+        cfg.set_spec_and_mainnet_gas_params(spec);
+        let evm = Context::mainnet()
+            .with_cfg(cfg)
+            .with_db(state)
+            .build_mainnet();
+        BlockExecutor::mainnet(evm)
+    }
+
+    #[test]
+    fn execute_block() {
+        let mut state = state();
+        // request contracts that return no requests.
+        for address in [WITHDRAWAL_REQUEST_ADDRESS, CONSOLIDATION_REQUEST_ADDRESS] {
+            let code = Bytecode::new_raw([opcode::STOP].into());
+            state.insert_account(address, AccountInfo::default().with_code(code));
+        }
+        let input = BlockInput {
+            withdrawals: vec![Withdrawal {
+                address: RECIPIENT,
+                amount: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let output = executor(&mut state, SpecId::PRAGUE)
+            .execute_block(block(), &input, [transfer(0), transfer(1)])
+            .unwrap();
+
+        assert_eq!(output.gas_used, 42_000);
+        assert_eq!(output.results.len(), 2);
+        assert_eq!(
+            output
+                .receipts
+                .iter()
+                .map(|r| r.cumulative_gas_used)
+                .collect::<Vec<_>>(),
+            vec![21_000, 42_000]
+        );
+        assert!(output.requests.is_empty());
+
+        let recipient = output.bundle.account(&RECIPIENT).unwrap();
+        assert_eq!(
+            recipient.info.as_ref().unwrap().balance,
+            U256::from(2000) + U256::from(ONE_GWEI)
+        );
+        let caller = output.bundle.account(&CALLER).unwrap();
+        assert_eq!(caller.info.as_ref().unwrap().nonce, 2);
+        assert!(output.bundle.account(&BENEFICIARY).is_none());
+    }
+
+    #[test]
+    fn block_reward_before_merge() {
+        let mut state = state();
+        let output = executor(&mut state, SpecId::LONDON)
+            .execute_block(block(), &BlockInput::default(), [transfer(0)])
+            .unwrap();
+
+        let beneficiary = output.bundle.account(&BENEFICIARY).unwrap();
+        assert_eq!(
+            beneficiary.info.as_ref().unwrap().balance,
+            U256::from(block_reward(SpecId::LONDON, 0))
+        );
+    }
+
+    #[test]
+    fn transaction_over_block_gas_limit() {
+        let mut state = state();
+        let block = BlockEnv {
+            gas_limit: 30_000,
+            ..block()
+        };
+        let err = executor(&mut state, SpecId::PRAGUE)
+            .execute_block(block, &BlockInput::default(), [transfer(0), transfer(1)])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            BlockExecutionError::TransactionGasLimitMoreThanAvailableBlockGas {
+                index: 1,
+                transaction_gas_limit: 21_000,
+                block_available_gas: 9_000,
+            }
+        );
+    }
+
+    #[test]
+    fn missing_request_contract() {
+        let mut state = state();
+        let err = executor(&mut state, SpecId::PRAGUE)
+            .execute_block(block(), &BlockInput::default(), [transfer(0)])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            BlockExecutionError::SystemContractMissing {
+                address: WITHDRAWAL_REQUEST_ADDRESS
+            }
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    requests::{
+        parse_deposits_from_receipts, CONSOLIDATION_REQUEST_TYPE, DEPOSIT_REQUEST_TYPE,
+        MAINNET_DEPOSIT_CONTRACT_ADDRESS, WITHDRAWAL_REQUEST_TYPE,
+    },
+    system_calls, BlockExecutionError, BlockInput, Receipt, Requests,
+};
+use context_interface::{result::ExecutionResult, Block, Cfg, ContextTr, JournalTr};
+use handler::{ContextTrDbError, EvmTr, ExecuteEvm, SystemCallCommitEvm};
+use primitives::{hardfork::SpecId, Address, ONE_ETHER, U256};
+
+/// Hooks that are called by the [`BlockExecutor`](crate::BlockExecutor) before the first and
+/// after the last transaction of the block.
+///
+/// Mainnet implementation is [`EthBlockHooks`]; chains with different block level state
+/// transitions implement their own hooks.
+pub trait BlockHooks<EVM: ExecuteEvm> {
+    /// Executed after the block env is set and before the first transaction.
+    fn pre_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+    ) -> Result<(), BlockExecutionError<EVM::Error>>;
+
+    /// Executed after the last transaction. Returns EIP-7685 requests of the block.
+    fn post_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+        receipts: &[Receipt],
+    ) -> Result<Requests, BlockExecutionError<EVM::Error>>;
+}
+
+impl<EVM: ExecuteEvm, H: BlockHooks<EVM>> BlockHooks<EVM> for &mut H {
+    fn pre_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+    ) -> Result<(), BlockExecutionError<EVM::Error>> {
+        (**self).pre_block(evm, input)
+    }
+
+    fn post_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+        receipts: &[Receipt],
+    ) -> Result<Requests, BlockExecutionError<EVM::Error>> {
+        (**self).post_block(evm, input, receipts)
+    }
+}
+
+/// Ethereum mainnet block hooks.
+///
+/// Pre-block: EIP-2935 block hash and EIP-4788 beacon root system calls.
+///
+/// Post-block: block reward before the Merge, EIP-4895 withdrawals, EIP-6110 deposit requests and
+/// EIP-7002/EIP-7251 request system calls.
+///
+/// # Note
+///
+/// Uncle rewards are not implemented.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthBlockHooks {
+    /// Address of the deposit contract whose logs are parsed into EIP-6110 deposit requests.
+    ///
+    /// If `None`, deposit requests are not collected.
+    pub deposit_contract: Option<Address>,
+}
+
+impl Default for EthBlockHooks {
+    fn default() -> Self {
+        Self {
+            deposit_contract: Some(MAINNET_DEPOSIT_CONTRACT_ADDRESS),
+        }
+    }
+}
+
+impl EthBlockHooks {
+    /// Creates new mainnet block hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the deposit contract address.
+    pub fn with_deposit_contract(mut self, deposit_contract: Option<Address>) -> Self {
+        self.deposit_contract = deposit_contract;
+        self
+    }
+}
+
+impl<EVM, H> BlockHooks<EVM> for EthBlockHooks
+where
+    EVM: SystemCallCommitEvm<
+            ExecutionResult = ExecutionResult<H>,
+            Error: From<ContextTrDbError<EVM::Context>>,
+        > + EvmTr,
+{
+    fn pre_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+    ) -> Result<(), BlockExecutionError<EVM::Error>> {
+        // skip system calls for the genesis block.
+        if evm.ctx_ref().block().number().is_zero() {
+            return Ok(());
+        }
+        let spec: SpecId = evm.ctx_ref().cfg().spec().into();
+
+        if let Some(parent_hash) = input.parent_hash {
+            if spec.is_enabled_in(SpecId::PRAGUE) {
+                system_calls::system_call_eip2935_blockhash(evm, parent_hash)?;
+            }
+        }
+
+        if let Some(parent_beacon_block_root) = input.parent_beacon_block_root {
+            if spec.is_enabled_in(SpecId::CANCUN) {
+                system_calls::system_call_eip4788_beacon_root(evm, parent_beacon_block_root)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn post_block(
+        &mut self,
+        evm: &mut EVM,
+        input: &BlockInput,
+        receipts: &[Receipt],
+    ) -> Result<Requests, BlockExecutionError<EVM::Error>> {
+        let spec: SpecId = evm.ctx_ref().cfg().spec().into();
+
+        let reward = block_reward(spec, 0);
+        if reward != 0 {
+            let beneficiary = evm.ctx_ref().block().beneficiary();
+            evm.ctx_mut()
+                .journal_mut()
+                .balance_incr(beneficiary, U256::from(reward))
+                .map_err(|e| BlockExecutionError::Database(e.into()))?;
+        }
+
+        if spec.is_enabled_in(SpecId::SHANGHAI) {
+            for withdrawal in &input.withdrawals {
+                evm.ctx_mut()
+                    .journal_mut()
+                    .balance_incr(withdrawal.address, withdrawal.amount_wei())
+                    .map_err(|e| BlockExecutionError::Database(e.into()))?;
+            }
+        }
+
+        evm.commit_inner();
+
+        let mut requests = Requests::new();
+        if spec.is_enabled_in(SpecId::PRAGUE) {
+            if let Some(deposit_contract) = self.deposit_contract {
+                let deposits = parse_deposits_from_receipts(deposit_contract, receipts)?;
+                requests.push_request_with_type(DEPOSIT_REQUEST_TYPE, deposits);
+            }
+            let withdrawals = system_calls::system_call_eip7002_withdrawal_requests(evm)?;
+            requests.push_request_with_type(WITHDRAWAL_REQUEST_TYPE, withdrawals);
+            let consolidations = system_calls::system_call_eip7251_consolidation_requests(evm)?;
+            requests.push_request_with_type(CONSOLIDATION_REQUEST_TYPE, consolidations);
+        }
+        Ok(requests)
+    }
+}
+
+/// Block reward for a block.
+#[inline]
+pub const fn block_reward(spec: SpecId, ommers: usize) -> u128 {
+    if spec.is_enabled_in(SpecId::MERGE) {
+        return 0;
+    }
+
+    let reward = if spec.is_enabled_in(SpecId::CONSTANTINOPLE) {
+        ONE_ETHER * 2
+    } else if spec.is_enabled_in(SpecId::BYZANTIUM) {
+        ONE_ETHER * 3
+    } else {
+        ONE_ETHER * 5
+    };
+
+    reward + (reward >> 5) * ommers as u128
+}
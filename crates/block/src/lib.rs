@@ -0,0 +1,41 @@
+//! Block execution on top of revm.
+//!
+//! [`BlockExecutor`] runs the full block pipeline against a [`State`](database::State):
+//! pre-block system calls, every transaction of the block, withdrawals and post-block
+//! request system calls. Chain specific steps are plugged in through [`BlockHooks`].
+#![cfg_attr(not(test), warn(unused_crate_dependencies))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc as std;
+
+/// Block execution errors.
+pub mod error;
+/// Block executor.
+pub mod executor;
+/// Pre- and post-block hooks.
+pub mod hooks;
+/// Optimistic parallel execution for blocks without a Block Access List.
+#[cfg(feature = "parallel")]
+pub mod optimistic;
+/// Parallel block execution driven by the Block Access List.
+#[cfg(feature = "parallel")]
+pub mod parallel;
+/// Transaction receipts.
+pub mod receipt;
+/// EIP-7685 execution layer requests.
+pub mod requests;
+/// System calls executed at block boundaries.
+pub mod system_calls;
+
+pub use error::BlockExecutionError;
+pub use executor::{BlockExecutionOutput, BlockExecutor, BlockInput, Withdrawal};
+pub use hooks::{block_reward, BlockHooks, EthBlockHooks};
+#[cfg(feature = "parallel")]
+pub use optimistic::{MvDatabase, OptimisticExecutor};
+#[cfg(feature = "parallel")]
+pub use parallel::{
+    ParallelBlockExecutor, ParallelExecutionOutput, ParallelFallbackReason, ParallelState,
+};
+pub use receipt::Receipt;
+pub use requests::Requests;
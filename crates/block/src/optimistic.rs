@@ -0,0 +1,777 @@
+use context_interface::{
+    result::{ExecResultAndState, ExecutionResult, TransactionIndexedError},
+    Block, Cfg, ContextTr, Transaction,
+};
+use database::{Database, DatabaseRef};
+use handler::{EvmTr, ExecuteEvm, PrecompileProvider};
+use primitives::{
+    hardfork::SpecId, Address, HashMap, StorageKey, StorageValue, TxKind, B256, U256,
+};
+use rayon::{prelude::*, ThreadPool};
+use state::{AccountInfo, Bytecode, EvmState};
+use std::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+/// Write of an account inside the multi-version memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AccountEntry {
+    /// Account info after the transaction, `None` if account was destroyed.
+    Write {
+        info: Option<AccountInfo>,
+        /// Storage was cleared, by selfdestruct or by creating the account.
+        storage_cleared: bool,
+    },
+    /// Balance increment of the beneficiary.
+    ///
+    /// Transactions that do not execute any bytecode can only increment the beneficiary
+    /// balance, so they do not depend on its value.
+    Delta(U256),
+}
+
+/// Value read by the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Read {
+    Account {
+        address: Address,
+        value: Option<AccountInfo>,
+        from_memory: bool,
+    },
+    Storage {
+        address: Address,
+        key: StorageKey,
+        value: StorageValue,
+        from_memory: bool,
+    },
+}
+
+/// Writes of one transaction execution.
+#[derive(Clone, Debug, Default)]
+struct WriteSet {
+    accounts: Vec<(Address, AccountEntry)>,
+    storage: Vec<((Address, StorageKey), StorageValue)>,
+    contracts: Vec<(B256, Bytecode)>,
+}
+
+/// Multi-version memory: values written by every transaction, indexed by the transaction index.
+///
+/// It is only read while transactions are executed, writes are applied between execution rounds.
+#[derive(Debug, Default)]
+pub struct MvMemory {
+    accounts: HashMap<Address, BTreeMap<usize, AccountEntry>>,
+    storage: HashMap<(Address, StorageKey), BTreeMap<usize, StorageValue>>,
+    /// Bytecodes of the accounts written by the transactions.
+    contracts: HashMap<B256, BTreeMap<usize, Bytecode>>,
+    /// Account values loaded from the database, used to resolve beneficiary deltas.
+    base_accounts: HashMap<Address, Option<AccountInfo>>,
+}
+
+impl MvMemory {
+    /// Returns `true` if account was written by a transaction before `index`.
+    fn has_account_entry(&self, address: &Address, index: usize) -> bool {
+        self.accounts
+            .get(address)
+            .is_some_and(|entries| entries.range(..index).next().is_some())
+    }
+
+    /// Resolves account value as seen by the transaction at `index`.
+    ///
+    /// Returns `None` if there are no writes before the transaction and the value needs to be
+    /// read from the database.
+    fn account<E>(
+        &self,
+        address: Address,
+        index: usize,
+        base: impl FnOnce() -> Result<Option<AccountInfo>, E>,
+    ) -> Result<Option<Option<AccountInfo>>, E> {
+        let Some(entries) = self.accounts.get(&address) else {
+            return Ok(None);
+        };
+        let mut delta = U256::ZERO;
+        let mut value = None;
+        let mut has_entry = false;
+        for (_, entry) in entries.range(..index).rev() {
+            has_entry = true;
+            match entry {
+                AccountEntry::Delta(d) => delta += d,
+                AccountEntry::Write { info, .. } => {
+                    value = Some(info.clone());
+                    break;
+                }
+            }
+        }
+        if !has_entry {
+            return Ok(None);
+        }
+        let mut info = match value {
+            Some(info) => info,
+            None => match self.base_accounts.get(&address) {
+                Some(info) => info.clone(),
+                None => base()?,
+            },
+        };
+        if !delta.is_zero() {
+            let info = info.get_or_insert_with(AccountInfo::default);
+            info.balance = info.balance.saturating_add(delta);
+        }
+        Ok(Some(info))
+    }
+
+    /// Resolves storage value as seen by the transaction at `index`.
+    ///
+    /// Returns `None` if the value needs to be read from the database.
+    fn storage(&self, address: Address, key: StorageKey, index: usize) -> Option<StorageValue> {
+        let written = self
+            .storage
+            .get(&(address, key))
+            .and_then(|entries| entries.range(..index).next_back());
+        let cleared = self.accounts.get(&address).and_then(|entries| {
+            entries.range(..index).rev().find(|(_, entry)| {
+                matches!(
+                    entry,
+                    AccountEntry::Write {
+                        storage_cleared: true,
+                        ..
+                    }
+                )
+            })
+        });
+        match (written, cleared) {
+            // storage written by the same or later transaction than the one that cleared it.
+            (Some((written_index, value)), Some((cleared_index, _)))
+                if written_index >= cleared_index =>
+            {
+                Some(*value)
+            }
+            (_, Some(_)) => Some(StorageValue::ZERO),
+            (Some((_, value)), None) => Some(*value),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns the bytecode written by a transaction before `index`.
+    ///
+    /// Code is addressed by its hash, so it does not need to be validated: accounts that point
+    /// to it are.
+    fn code(&self, code_hash: &B256, index: usize) -> Option<Bytecode> {
+        self.contracts
+            .get(code_hash)
+            .and_then(|entries| entries.range(..index).next_back())
+            .map(|(_, code)| code.clone())
+    }
+
+    /// Removes writes of the transaction and applies the new ones.
+    fn apply(&mut self, index: usize, old: Option<&WriteSet>, new: &WriteSet) {
+        if let Some(old) = old {
+            for (address, _) in &old.accounts {
+                if let Some(entries) = self.accounts.get_mut(address) {
+                    entries.remove(&index);
+                }
+            }
+            for (slot, _) in &old.storage {
+                if let Some(entries) = self.storage.get_mut(slot) {
+                    entries.remove(&index);
+                }
+            }
+            for (code_hash, _) in &old.contracts {
+                if let Some(entries) = self.contracts.get_mut(code_hash) {
+                    entries.remove(&index);
+                }
+            }
+        }
+        for (address, entry) in &new.accounts {
+            self.accounts
+                .entry(*address)
+                .or_default()
+                .insert(index, entry.clone());
+        }
+        for (slot, value) in &new.storage {
+            self.storage.entry(*slot).or_default().insert(index, *value);
+        }
+        for (code_hash, code) in &new.contracts {
+            self.contracts
+                .entry(*code_hash)
+                .or_default()
+                .insert(index, code.clone());
+        }
+    }
+
+    /// Checks that the value read by the transaction at `index` is still the same.
+    fn validate<DB: DatabaseRef>(&self, db: &DB, index: usize, read: &Read) -> bool {
+        match read {
+            Read::Account {
+                address,
+                value,
+                from_memory,
+            } => {
+                // base values never change.
+                if !from_memory && !self.has_account_entry(address, index) {
+                    return true;
+                }
+                matches!(
+                    self.account(*address, index, || db.basic_ref(*address)),
+                    Ok(Some(current)) if current == *value
+                )
+            }
+            Read::Storage {
+                address,
+                key,
+                value,
+                from_memory,
+            } => match self.storage(*address, *key, index) {
+                Some(current) => current == *value,
+                None => !from_memory,
+            },
+        }
+    }
+}
+
+/// Database used by the transactions executed with the [`OptimisticExecutor`].
+///
+/// Reads values written by previous transactions from the multi-version memory, falling back
+/// to the shared database, and records every read so it can be validated after execution.
+#[derive(Debug)]
+pub struct MvDatabase<'db, DB> {
+    db: &'db DB,
+    memory: Arc<MvMemory>,
+    /// Index of the transaction inside the block.
+    index: usize,
+    reads: Vec<Read>,
+}
+
+impl<'db, DB> MvDatabase<'db, DB> {
+    fn new(db: &'db DB, memory: Arc<MvMemory>, index: usize) -> Self {
+        Self {
+            db,
+            memory,
+            index,
+            reads: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the transaction that is executed.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<DB: DatabaseRef> Database for MvDatabase<'_, DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let db = self.db;
+        let (value, from_memory) = match self
+            .memory
+            .account(address, self.index, || db.basic_ref(address))?
+        {
+            Some(value) => (value, true),
+            None => (db.basic_ref(address)?, false),
+        };
+        self.reads.push(Read::Account {
+            address,
+            value: value.clone(),
+            from_memory,
+        });
+        Ok(value)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        // code deployed by previous transactions is not in the database.
+        if let Some(code) = self.memory.code(&code_hash, self.index) {
+            return Ok(code);
+        }
+        self.db.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        let (value, from_memory) = match self.memory.storage(address, index, self.index) {
+            Some(value) => (value, true),
+            None => (self.db.storage_ref(address, index)?, false),
+        };
+        self.reads.push(Read::Storage {
+            address,
+            key: index,
+            value,
+            from_memory,
+        });
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.db.block_hash_ref(number)
+    }
+}
+
+/// One execution of the transaction.
+#[derive(Debug)]
+struct Incarnation<R, E> {
+    result: Result<ExecResultAndState<R>, E>,
+    reads: Vec<Read>,
+    writes: WriteSet,
+    /// Beneficiary and its balance increment, if the write was recorded as a delta.
+    beneficiary_delta: Option<(Address, U256)>,
+}
+
+/// Optimistic parallel executor for blocks without a Block Access List.
+///
+/// Implements a round based variant of Block-STM:
+/// * All pending transactions are executed in parallel. Every transaction reads through
+///   [`MvDatabase`], that records the read set and returns values written by previous
+///   transactions in the multi-version memory.
+/// * Write sets, taken from the [`EvmState`] output of the journal, are applied to the memory.
+/// * Read sets are validated in transaction order. The prefix of valid transactions is final,
+///   invalid transactions are re-executed in the next round.
+///
+/// The first invalid transaction of a round only depends on final transactions, so every round
+/// finalizes at least one transaction. If less than half of the executed transactions get
+/// finalized, remaining transactions are executed sequentially to bound the wasted work.
+///
+/// Beneficiary fees of transactions that do not execute any bytecode are recorded as balance
+/// increments, so plain transfers do not conflict on the beneficiary account.
+///
+/// Outputs are the same as the outputs of sequential execution. Committing them in order
+/// with [`DatabaseCommit`](database::DatabaseCommit) gives the same state as
+/// [`ExecuteCommitEvm::transact_many_commit`](handler::ExecuteCommitEvm::transact_many_commit).
+#[derive(Debug)]
+pub struct OptimisticExecutor<F> {
+    /// Creates EVM from the [`MvDatabase`]. EVM needs to have block and cfg set.
+    evm_factory: F,
+    /// Thread pool used for execution. If `None`, global rayon pool is used.
+    thread_pool: Option<Arc<ThreadPool>>,
+}
+
+impl<F> OptimisticExecutor<F> {
+    /// Creates new optimistic executor.
+    pub fn new(evm_factory: F) -> Self {
+        Self {
+            evm_factory,
+            thread_pool: None,
+        }
+    }
+
+    /// Sets the thread pool used for execution. Default is the global rayon pool.
+    pub fn with_thread_pool(mut self, thread_pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    /// Executes transactions on top of the `db` and returns the result and state of every
+    /// transaction.
+    ///
+    /// On error, the index of the first failed transaction is returned, same as in
+    /// [`ExecuteEvm::transact_many`].
+    #[allow(clippy::type_complexity)]
+    pub fn transact_many<'db, DB, EVM, HR>(
+        &self,
+        db: &'db DB,
+        transactions: &[EVM::Tx],
+    ) -> Result<Vec<ExecResultAndState<ExecutionResult<HR>>>, TransactionIndexedError<EVM::Error>>
+    where
+        DB: DatabaseRef + Sync,
+        F: Fn(MvDatabase<'db, DB>) -> EVM + Sync,
+        EVM: ExecuteEvm<ExecutionResult = ExecutionResult<HR>, State = EvmState> + EvmTr,
+        EVM::Context: ContextTr<Db = MvDatabase<'db, DB>>,
+        EVM::Tx: Clone + Sync,
+        EVM::Error: Send,
+        HR: Send,
+    {
+        let len = transactions.len();
+        let mut memory = Arc::new(MvMemory::default());
+        let mut incarnations: Vec<Option<Incarnation<ExecutionResult<HR>, EVM::Error>>> =
+            (0..len).map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..len).collect();
+        // number of final transactions.
+        let mut finalized = 0;
+
+        while finalized < len {
+            let executed = {
+                let memory = &memory;
+                let execute = || {
+                    pending
+                        .par_iter()
+                        .map(|index| self.execute(db, memory, transactions, *index))
+                        .collect::<Vec<_>>()
+                };
+                match &self.thread_pool {
+                    Some(pool) => pool.install(execute),
+                    None => execute(),
+                }
+            };
+            let executed_len = executed.len();
+            let memory_mut = Arc::get_mut(&mut memory).expect("EVMs are dropped after execution");
+            for (index, incarnation) in pending.drain(..).zip(executed) {
+                memory_mut.store(index, incarnations[index].as_ref(), &incarnation);
+                incarnations[index] = Some(incarnation);
+            }
+
+            let round_start = finalized;
+            for (index, incarnation) in incarnations.iter().enumerate().skip(round_start) {
+                let incarnation = incarnation.as_ref().expect("executed");
+                let valid = incarnation
+                    .reads
+                    .iter()
+                    .all(|read| memory.validate(db, index, read));
+                if !valid {
+                    pending.push(index);
+                } else if pending.is_empty() {
+                    finalized += 1;
+                }
+            }
+
+            // too many conflicts, execute remaining transactions sequentially.
+            if finalized < len && (finalized - round_start) * 2 < executed_len {
+                for (index, slot) in incarnations.iter_mut().enumerate().skip(finalized) {
+                    let incarnation = self.execute(db, &memory, transactions, index);
+                    let memory_mut =
+                        Arc::get_mut(&mut memory).expect("EVMs are dropped after execution");
+                    memory_mut.store(index, slot.as_ref(), &incarnation);
+                    *slot = Some(incarnation);
+                }
+                pending.clear();
+                finalized = len;
+            }
+        }
+
+        let mut outputs = Vec::with_capacity(len);
+        for (index, incarnation) in incarnations.into_iter().enumerate() {
+            let incarnation = incarnation.expect("all transactions are executed");
+            let mut output = incarnation
+                .result
+                .map_err(|error| TransactionIndexedError::new(error, index))?;
+            if let Some((beneficiary, delta)) = incarnation.beneficiary_delta {
+                // apply the balance increment on top of the final beneficiary value.
+                let previous =
+                    match memory.account(beneficiary, index, || db.basic_ref(beneficiary)) {
+                        Ok(Some(info)) => info,
+                        Ok(None) => db.basic_ref(beneficiary).ok().flatten(),
+                        Err(_) => None,
+                    };
+                if let Some(account) = output.state.get_mut(&beneficiary) {
+                    let mut info = previous.unwrap_or_default();
+                    *account.original_info = info.clone();
+                    info.balance = info.balance.saturating_add(delta);
+                    account.info = info;
+                }
+            }
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+
+    /// Executes one transaction against the multi-version memory.
+    fn execute<'db, DB, EVM, HR>(
+        &self,
+        db: &'db DB,
+        memory: &Arc<MvMemory>,
+        transactions: &[EVM::Tx],
+        index: usize,
+    ) -> Incarnation<ExecutionResult<HR>, EVM::Error>
+    where
+        DB: DatabaseRef,
+        F: Fn(MvDatabase<'db, DB>) -> EVM,
+        EVM: ExecuteEvm<ExecutionResult = ExecutionResult<HR>, State = EvmState> + EvmTr,
+        EVM::Context: ContextTr<Db = MvDatabase<'db, DB>>,
+        EVM::Tx: Clone,
+    {
+        let tx = &transactions[index];
+        let mut evm = (self.evm_factory)(MvDatabase::new(db, memory.clone(), index));
+        let result = evm.transact(tx.clone());
+        let mut reads = core::mem::take(&mut evm.ctx_mut().db_mut().reads);
+
+        let Ok(output) = &result else {
+            return Incarnation {
+                result,
+                reads,
+                writes: WriteSet::default(),
+                beneficiary_delta: None,
+            };
+        };
+
+        let spec: SpecId = evm.ctx_ref().cfg().spec().into();
+        let beneficiary = evm.ctx_ref().block().beneficiary();
+        let beneficiary_delta = is_plain_transfer(&mut evm, tx, &reads, beneficiary)
+            .then(|| beneficiary_delta(&output.state, &reads, beneficiary))
+            .flatten();
+        if beneficiary_delta.is_some() {
+            // transaction does not depend on the beneficiary value.
+            reads.retain(
+                |read| !matches!(read, Read::Account { address, .. } if *address == beneficiary),
+            );
+        }
+
+        let mut writes = WriteSet::default();
+        for (address, account) in &output.state {
+            if !account.is_touched() {
+                continue;
+            }
+            if let (Some(delta), true) = (beneficiary_delta, *address == beneficiary) {
+                writes.accounts.push((*address, AccountEntry::Delta(delta)));
+                continue;
+            }
+            if account.is_selfdestructed() || account.state_clear_aware_is_empty(spec) {
+                writes.accounts.push((
+                    *address,
+                    AccountEntry::Write {
+                        info: None,
+                        storage_cleared: true,
+                    },
+                ));
+                continue;
+            }
+            let is_created = account.is_created();
+            if let Some(code) = account.info.code.as_ref() {
+                if !account.info.is_empty_code_hash() {
+                    writes
+                        .contracts
+                        .push((account.info.code_hash, code.clone()));
+                }
+            }
+            writes.accounts.push((
+                *address,
+                AccountEntry::Write {
+                    info: Some(account.info.clone()),
+                    storage_cleared: is_created,
+                },
+            ));
+            writes.storage.extend(
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| is_created || slot.is_changed())
+                    .map(|(key, slot)| ((*address, *key), slot.present_value)),
+            );
+        }
+
+        Incarnation {
+            result,
+            reads,
+            writes,
+            beneficiary_delta: beneficiary_delta.map(|delta| (beneficiary, delta)),
+        }
+    }
+}
+
+impl MvMemory {
+    /// Stores writes of the new incarnation and the database values it read.
+    fn store<R, E>(
+        &mut self,
+        index: usize,
+        old: Option<&Incarnation<R, E>>,
+        new: &Incarnation<R, E>,
+    ) {
+        self.apply(index, old.map(|old| &old.writes), &new.writes);
+        for read in &new.reads {
+            if let Read::Account {
+                address,
+                value,
+                from_memory: false,
+            } = read
+            {
+                self.base_accounts
+                    .entry(*address)
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+/// Returns `true` if the transaction is a call that does not execute any bytecode and
+/// does not involve the beneficiary, so the beneficiary is only touched by the fee payment.
+fn is_plain_transfer<EVM: EvmTr>(
+    evm: &mut EVM,
+    tx: &impl Transaction,
+    reads: &[Read],
+    beneficiary: Address,
+) -> bool {
+    let TxKind::Call(target) = tx.kind() else {
+        return false;
+    };
+    if target == beneficiary
+        || tx.caller() == beneficiary
+        || tx.authorization_list_len() != 0
+        || evm.ctx_precompiles().1.contains(&target)
+    {
+        return false;
+    }
+    reads.iter().any(|read| {
+        matches!(read, Read::Account { address, value, .. }
+            if *address == target
+                && value.as_ref().is_none_or(|info| info.is_empty_code_hash()))
+    })
+}
+
+/// Returns the beneficiary balance increment if the transaction only changed its balance.
+fn beneficiary_delta(state: &EvmState, reads: &[Read], beneficiary: Address) -> Option<U256> {
+    let account = state.get(&beneficiary)?;
+    let read = reads.iter().find_map(|read| match read {
+        Read::Account { address, value, .. } if *address == beneficiary => Some(value),
+        _ => None,
+    })?;
+    let read = read.clone().unwrap_or_default();
+    (account.info.nonce == read.nonce && account.info.code_hash == read.code_hash)
+        .then(|| account.info.balance.checked_sub(read.balance))
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::{BlockEnv, CfgEnv, Context, TxEnv};
+    use database::{CacheDB, DatabaseCommit, EmptyDB, InMemoryDB};
+    use handler::{ExecuteCommitEvm, MainBuilder, MainContext, MainnetContext, MainnetEvm};
+    use primitives::{address, bytes, Bytes, ONE_ETHER};
+    use state::AccountInfo;
+
+    const BENEFICIARY: Address = address!("0x3000000000000000000000000000000000000003");
+    const COUNTER: Address = address!("0x4000000000000000000000000000000000000004");
+
+    fn caller(i: u64) -> Address {
+        Address::with_last_byte(0x10 + i as u8)
+    }
+
+    /// Increments storage slot 0 on every call.
+    ///
+    /// `PUSH0 SLOAD PUSH1 1 ADD PUSH0 SSTORE STOP`
+    const COUNTER_CODE: Bytes = bytes!("5f546001015f5500");
+
+    fn db() -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        for i in 0..8 {
+            db.insert_account_info(
+                caller(i),
+                AccountInfo::default().with_balance(U256::from(ONE_ETHER)),
+            );
+        }
+        db.insert_account_info(
+            COUNTER,
+            AccountInfo::default().with_code(Bytecode::new_raw(COUNTER_CODE)),
+        );
+        db
+    }
+
+    fn evm<DB: Database>(db: DB) -> MainnetEvm<MainnetContext<DB>> {
+        let mut cfg = CfgEnv::default();
+        cfg.set_spec_and_mainnet_gas_params(SpecId::PRAGUE);
+        Context::mainnet()
+            .with_cfg(cfg)
+            .with_block(BlockEnv {
+                beneficiary: BENEFICIARY,
+                gas_limit: 30_000_000,
+                basefee: 1,
+                ..Default::default()
+            })
+            .with_db(db)
+            .build_mainnet()
+    }
+
+    fn tx(caller_index: u64, nonce: u64, to: Address) -> TxEnv {
+        TxEnv::builder()
+            .caller(caller(caller_index))
+            .kind(TxKind::Call(to))
+            .value(U256::from(1000))
+            .gas_limit(100_000)
+            .gas_price(2)
+            .nonce(nonce)
+            .build()
+            .unwrap()
+    }
+
+    /// Executes transactions optimistically and sequentially and compares the outputs.
+    fn compare(transactions: Vec<TxEnv>) {
+        let db = db();
+        let outputs = OptimisticExecutor::new(evm)
+            .transact_many(&db, &transactions)
+            .unwrap();
+        let mut optimistic_db = db.clone();
+        let mut results = Vec::new();
+        for output in outputs {
+            optimistic_db.commit(output.state);
+            results.push(output.result);
+        }
+
+        let mut sequential = evm(db);
+        let expected = sequential
+            .transact_many_commit(transactions.into_iter())
+            .unwrap();
+        assert_eq!(results, expected);
+
+        let sequential_db: &CacheDB<EmptyDB> = &sequential.ctx.journaled_state.database;
+        for (address, account) in &sequential_db.cache.accounts {
+            let optimistic = optimistic_db.cache.accounts.get(address).unwrap();
+            assert_eq!(optimistic.info, account.info, "{address}");
+            assert_eq!(optimistic.storage, account.storage, "{address}");
+        }
+    }
+
+    #[test]
+    fn independent_transfers() {
+        compare((0..8).map(|i| tx(i, 0, caller((i + 1) % 8))).collect());
+    }
+
+    #[test]
+    fn dependent_transactions() {
+        let mut transactions = Vec::new();
+        for nonce in 0..3 {
+            for i in 0..4 {
+                // same sender, shared counter and transfers to other senders.
+                transactions.push(tx(i, nonce, COUNTER));
+                transactions.push(tx(i + 4, nonce, caller(i)));
+            }
+        }
+        compare(transactions);
+    }
+
+    #[test]
+    fn create_then_call() {
+        // `PUSH8 COUNTER_CODE PUSH0 MSTORE PUSH1 8 PUSH1 24 RETURN`
+        let init_code = bytes!("675f546001015f55005f5260086018f3");
+        let created = caller(0).create(0);
+        let create = TxEnv::builder()
+            .caller(caller(0))
+            .kind(TxKind::Create)
+            .data(init_code)
+            .gas_limit(100_000)
+            .gas_price(2)
+            .build()
+            .unwrap();
+        compare(vec![
+            create,
+            tx(1, 0, created),
+            tx(2, 0, created),
+            tx(0, 1, created),
+        ]);
+    }
+
+    #[test]
+    fn code_written_by_previous_transaction() {
+        let code = Bytecode::new_raw(COUNTER_CODE);
+        let code_hash = code.hash_slow();
+        let mut memory = MvMemory::default();
+        let writes = WriteSet {
+            contracts: vec![(code_hash, code.clone())],
+            ..Default::default()
+        };
+        memory.apply(0, None, &writes);
+        let memory = Arc::new(memory);
+
+        let db = InMemoryDB::default();
+        let mut later = MvDatabase::new(&db, memory.clone(), 1);
+        assert_eq!(later.code_by_hash(code_hash).unwrap(), code);
+        // code is not visible to the transaction that wrote it.
+        let mut same = MvDatabase::new(&db, memory, 0);
+        assert!(same.code_by_hash(code_hash).unwrap().is_empty());
+    }
+
+    #[test]
+    fn first_error_is_returned() {
+        let db = db();
+        // nonce of the second transaction is too high.
+        let transactions = [tx(0, 0, COUNTER), tx(1, 1, COUNTER), tx(0, 1, COUNTER)];
+        let err = OptimisticExecutor::new(evm)
+            .transact_many(&db, &transactions)
+            .unwrap_err();
+        assert_eq!(err.transaction_index, 1);
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{
+    BlockExecutionError, BlockExecutionOutput, BlockExecutor, BlockHooks, BlockInput,
+    EthBlockHooks, Receipt,
+};
+use context_interface::{
+    result::{ExecResultAndState, ExecutionResult},
+    Block, Cfg, ContextTr, Transaction,
+};
+use core::fmt;
+use database::{
+    states::bundle_state::BundleRetention, Database, DatabaseRef, State, WrapDatabaseRef,
+};
+use handler::{EvmTr, ExecuteCommitEvm};
+use rayon::{prelude::*, ThreadPool};
+use state::{
+    bal::{Bal, BalDiff},
+    EvmState,
+};
+use std::{sync::Arc, vec::Vec};
+
+/// State used by the [`ParallelBlockExecutor`], reads go to the shared database.
+pub type ParallelState<'db, DB> = State<WrapDatabaseRef<&'db DB>>;
+
+/// Reason why parallel execution was abandoned and the block was executed sequentially.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParallelFallbackReason {
+    /// Transaction failed when executed against its BAL view.
+    ///
+    /// This includes accesses to accounts or storage slots that are not in the BAL.
+    TransactionFailed {
+        /// Index of the transaction inside the block.
+        index: usize,
+    },
+    /// Transaction gas limit is more than the gas left in the block.
+    BlockGasLimitExceeded {
+        /// Index of the transaction inside the block.
+        index: usize,
+    },
+    /// Pre- or post-block hook failed.
+    HookFailed,
+    /// BAL built from the execution differs from the provided BAL.
+    BalMismatch(BalDiff),
+}
+
+impl fmt::Display for ParallelFallbackReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionFailed { index } => {
+                write!(f, "transaction {index} failed against BAL state")
+            }
+            Self::BlockGasLimitExceeded { index } => {
+                write!(f, "transaction {index} exceeds available block gas")
+            }
+            Self::HookFailed => write!(f, "block hook failed against BAL state"),
+            Self::BalMismatch(diff) => {
+                write!(f, "built BAL does not match provided BAL: {diff}")
+            }
+        }
+    }
+}
+
+/// Output of the parallel block execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParallelExecutionOutput<R> {
+    /// Output of the block execution.
+    pub output: BlockExecutionOutput<R>,
+    /// Set if the block was executed sequentially, with the reason why parallel execution failed.
+    pub fallback: Option<ParallelFallbackReason>,
+}
+
+/// Executes transactions of the block in parallel using the provided Block Access List (BAL).
+///
+/// Every transaction is executed on the thread pool against its own [`State`] that reads
+/// the shared database through the BAL at the transaction `bal_index`, so transactions see
+/// the writes of all previous transactions without waiting for them.
+///
+/// After execution, transaction states are committed in order on the main [`State`] that
+/// builds a new BAL from them. If any transaction fails or the built BAL differs from the
+/// provided one, the parallel results are discarded and the block is executed sequentially
+/// with [`BlockExecutor`].
+///
+/// EVM is created by the `evm_factory` for every transaction and for the block hooks.
+#[derive(Debug)]
+pub struct ParallelBlockExecutor<F, H = EthBlockHooks> {
+    /// Creates EVM from the [`State`].
+    evm_factory: F,
+    /// Pre- and post-block hooks.
+    hooks: H,
+    /// Retention used when merging transitions into the bundle.
+    retention: BundleRetention,
+    /// Thread pool used for execution. If `None`, global rayon pool is used.
+    thread_pool: Option<Arc<ThreadPool>>,
+}
+
+impl<F> ParallelBlockExecutor<F> {
+    /// Creates new parallel block executor with mainnet block hooks.
+    pub fn mainnet(evm_factory: F) -> Self {
+        Self::new(evm_factory, EthBlockHooks::default())
+    }
+}
+
+impl<F, H> ParallelBlockExecutor<F, H> {
+    /// Creates new parallel block executor.
+    pub fn new(evm_factory: F, hooks: H) -> Self {
+        Self {
+            evm_factory,
+            hooks,
+            retention: BundleRetention::Reverts,
+            thread_pool: None,
+        }
+    }
+
+    /// Sets the bundle retention used when merging transitions. Default is [`BundleRetention::Reverts`].
+    pub fn with_bundle_retention(mut self, retention: BundleRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Sets the thread pool used for execution. Default is the global rayon pool.
+    pub fn with_thread_pool(mut self, thread_pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    /// Returns a mutable reference to the block hooks.
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// Executes the block on top of the `db` using the `bal`.
+    ///
+    /// Falls back to sequential execution if parallel execution fails or the BAL is invalid.
+    /// Errors are only returned from the sequential execution.
+    #[allow(clippy::type_complexity)]
+    pub fn execute_block<'db, DB, EVM, HR>(
+        &mut self,
+        db: &'db DB,
+        bal: Arc<Bal>,
+        block: EVM::Block,
+        input: &BlockInput,
+        transactions: &[EVM::Tx],
+    ) -> Result<ParallelExecutionOutput<ExecutionResult<HR>>, BlockExecutionError<EVM::Error>>
+    where
+        DB: DatabaseRef + Sync,
+        F: Fn(ParallelState<'db, DB>) -> EVM + Sync,
+        EVM: ExecuteCommitEvm<ExecutionResult = ExecutionResult<HR>, State = EvmState> + EvmTr,
+        EVM::Context: ContextTr<Db = ParallelState<'db, DB>>,
+        EVM::Block: Clone + Sync,
+        EVM::Tx: Clone + Sync,
+        HR: Send,
+        H: BlockHooks<EVM>,
+    {
+        let fallback = match self.execute_parallel(db, bal, block.clone(), input, transactions) {
+            Ok(output) => {
+                return Ok(ParallelExecutionOutput {
+                    output,
+                    fallback: None,
+                })
+            }
+            Err(reason) => reason,
+        };
+
+        let state = State::builder()
+            .with_database_ref(db)
+            .with_bundle_update()
+            .build();
+        let output = BlockExecutor::new((self.evm_factory)(state), &mut self.hooks)
+            .with_bundle_retention(self.retention)
+            .execute_block(block, input, transactions.iter().cloned())?;
+        Ok(ParallelExecutionOutput {
+            output,
+            fallback: Some(fallback),
+        })
+    }
+
+    /// Executes transactions in parallel and validates the BAL.
+    fn execute_parallel<'db, DB, EVM, HR>(
+        &mut self,
+        db: &'db DB,
+        bal: Arc<Bal>,
+        block: EVM::Block,
+        input: &BlockInput,
+        transactions: &[EVM::Tx],
+    ) -> Result<BlockExecutionOutput<ExecutionResult<HR>>, ParallelFallbackReason>
+    where
+        DB: DatabaseRef + Sync,
+        F: Fn(ParallelState<'db, DB>) -> EVM + Sync,
+        EVM: ExecuteCommitEvm<ExecutionResult = ExecutionResult<HR>, State = EvmState> + EvmTr,
+        EVM::Context: ContextTr<Db = ParallelState<'db, DB>>,
+        EVM::Block: Clone + Sync,
+        EVM::Tx: Clone + Sync,
+        HR: Send,
+        H: BlockHooks<EVM>,
+    {
+        let evm_factory = &self.evm_factory;
+        let execute_transactions = || {
+            transactions
+                .par_iter()
+                .enumerate()
+                .map(|(index, tx)| {
+                    let mut state = State::builder()
+                        .with_database_ref(db)
+                        .with_bal(bal.clone())
+                        .build();
+                    // index 0 is reserved for pre-block system calls.
+                    state.set_bal_index(index as u64 + 1);
+                    let mut evm = evm_factory(state);
+                    evm.set_block(block.clone());
+                    evm.transact(tx.clone())
+                        .map_err(|_| ParallelFallbackReason::TransactionFailed { index })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let outputs = match &self.thread_pool {
+            Some(pool) => pool.install(execute_transactions),
+            None => execute_transactions(),
+        }?;
+
+        let state = State::builder()
+            .with_database_ref(db)
+            .with_bal(bal.clone())
+            .with_bal_builder()
+            .with_bundle_update()
+            .build();
+        let mut evm = evm_factory(state);
+        evm.set_block(block);
+        evm.ctx_mut().db_mut().reset_bal_index();
+
+        self.hooks
+            .pre_block(&mut evm, input)
+            .map_err(|_| ParallelFallbackReason::HookFailed)?;
+
+        let block_gas_limit = evm.ctx_ref().block().gas_limit();
+        let check_block_gas_limit = !evm.ctx_ref().cfg().is_block_gas_limit_disabled();
+
+        let mut results = Vec::with_capacity(outputs.len());
+        let mut receipts = Vec::with_capacity(outputs.len());
+        let mut gas_used = 0u64;
+        for (index, (tx, output)) in transactions.iter().zip(outputs).enumerate() {
+            if check_block_gas_limit && tx.gas_limit() > block_gas_limit.saturating_sub(gas_used) {
+                return Err(ParallelFallbackReason::BlockGasLimitExceeded { index });
+            }
+            let ExecResultAndState { result, state } = output;
+
+            let db = evm.ctx_mut().db_mut();
+            db.bump_bal_index();
+            // accounts need to be loaded in the cache before the commit.
+            for (address, account) in &state {
+                if account.is_touched() {
+                    db.basic(*address)
+                        .map_err(|_| ParallelFallbackReason::TransactionFailed { index })?;
+                }
+            }
+            evm.commit(state);
+
+            gas_used += result.gas_used();
+            receipts.push(Receipt::new(tx.tx_type(), &result, gas_used));
+            results.push(result);
+        }
+
+        evm.ctx_mut().db_mut().bump_bal_index();
+        let requests = self
+            .hooks
+            .post_block(&mut evm, input, &receipts)
+            .map_err(|_| ParallelFallbackReason::HookFailed)?;
+
+        let state = evm.ctx_mut().db_mut();
+        let built = state.take_built_bal().unwrap_or_default();
+        bal.validate_against(&built)
+            .map_err(ParallelFallbackReason::BalMismatch)?;
+        state.merge_transitions(self.retention);
+        let bundle = state.take_bundle();
+
+        Ok(BlockExecutionOutput {
+            results,
+            receipts,
+            gas_used,
+            requests,
+            bundle,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_calls::{CONSOLIDATION_REQUEST_ADDRESS, WITHDRAWAL_REQUEST_ADDRESS};
+    use context::{BlockEnv, CfgEnv, Context, TxEnv};
+    use database::InMemoryDB;
+    use handler::{MainBuilder, MainContext, MainnetContext, MainnetEvm};
+    use primitives::{address, hardfork::SpecId, Address, TxKind, ONE_ETHER, U256};
+    use state::{bytecode::opcode, AccountInfo, Bytecode};
+
+    const CALLER: Address = address!("0x1000000000000000000000000000000000000001");
+    const RECIPIENT: Address = address!("0x2000000000000000000000000000000000000002");
+
+    type TestEvm<'db> = MainnetEvm<MainnetContext<ParallelState<'db, InMemoryDB>>>;
+
+    fn db() -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            CALLER,
+            AccountInfo::default().with_balance(U256::from(ONE_ETHER)),
+        );
+        // request contracts that return no requests.
+        for address in [WITHDRAWAL_REQUEST_ADDRESS, CONSOLIDATION_REQUEST_ADDRESS] {
+            let code = Bytecode::new_raw([opcode::STOP].into());
+            db.insert_account_info(address, AccountInfo::default().with_code(code));
+        }
+        db
+    }
+
+    fn block() -> BlockEnv {
+        BlockEnv {
+            number: U256::ONE,
+            gas_limit: 30_000_000,
+            ..Default::default()
+        }
+    }
+
+    fn transfers() -> Vec<TxEnv> {
+        (0..4)
+            .map(|nonce| {
+                TxEnv::builder()
+                    .caller(CALLER)
+                    .kind(TxKind::Call(RECIPIENT))
+                    .value(U256::from(1000))
+                    .gas_limit(21_000)
+                    .gas_price(0)
+                    .nonce(nonce)
+                    .build()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn evm_factory(state: ParallelState<'_, InMemoryDB>) -> TestEvm<'_> {
+        let mut cfg = CfgEnv::default();
+        cfg.set_spec_and_mainnet_gas_params(SpecId::PRAGUE);
+        Context::mainnet()
+            .with_cfg(cfg)
+            .with_db(state)
+            .build_mainnet()
+    }
+
+    /// Executes the block sequentially and returns the output with the built BAL.
+    fn sequential(db: &InMemoryDB) -> (BlockExecutionOutput<ExecutionResult>, Bal) {
+        let state = State::builder()
+            .with_database_ref(db)
+            .with_bal_builder()
+            .with_bundle_update()
+            .build();
+        let mut executor = BlockExecutor::mainnet(evm_factory(state));
+        let output = executor
+            .execute_block(block(), &BlockInput::default(), transfers())
+            .unwrap();
+        let bal = executor
+            .evm_mut()
+            .ctx_mut()
+            .db_mut()
+            .take_built_bal()
+            .unwrap();
+        (output, bal)
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let db = db();
+        let (expected, bal) = sequential(&db);
+
+        let output = ParallelBlockExecutor::mainnet(evm_factory)
+            .execute_block(
+                &db,
+                Arc::new(bal),
+                block(),
+                &BlockInput::default(),
+                &transfers(),
+            )
+            .unwrap();
+        assert_eq!(output.fallback, None);
+        assert_eq!(output.output, expected);
+    }
+
+    #[test]
+    fn invalid_bal_falls_back() {
+        let db = db();
+        let (expected, mut bal) = sequential(&db);
+
+        // wrong balance written by the first transaction.
+        let recipient = bal.accounts.get_mut(&RECIPIENT).unwrap();
+        recipient.account_info.balance.writes[0].1 = U256::from(1);
+
+        let output = ParallelBlockExecutor::mainnet(evm_factory)
+            .execute_block(
+                &db,
+                Arc::new(bal),
+                block(),
+                &BlockInput::default(),
+                &transfers(),
+            )
+            .unwrap();
+        assert!(matches!(
+            output.fallback,
+            Some(ParallelFallbackReason::BalMismatch(diff)) if !diff.is_empty()
+        ));
+        assert_eq!(output.output, expected);
+    }
+
+    #[test]
+    fn missing_account_falls_back() {
+        let db = db();
+        let (expected, mut bal) = sequential(&db);
+        bal.accounts.shift_remove(&RECIPIENT);
+
+        let output = ParallelBlockExecutor::mainnet(evm_factory)
+            .execute_block(
+                &db,
+                Arc::new(bal),
+                block(),
+                &BlockInput::default(),
+                &transfers(),
+            )
+            .unwrap();
+        assert!(matches!(
+            output.fallback,
+            Some(ParallelFallbackReason::TransactionFailed { .. })
+        ));
+        assert_eq!(output.output, expected);
+    }
+}
@@ -0,0 +1,32 @@
+use context_interface::result::ExecutionResult;
+use primitives::Log;
+use std::vec::Vec;
+
+/// Receipt of the transaction executed inside a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Receipt {
+    /// Transaction type.
+    pub tx_type: u8,
+    /// Whether the transaction was successful.
+    pub success: bool,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// Gas used by this and all previous transactions in the block.
+    pub cumulative_gas_used: u64,
+    /// Logs emitted by the transaction.
+    pub logs: Vec<Log>,
+}
+
+impl Receipt {
+    /// Creates a new receipt from the transaction execution result.
+    pub fn new<H>(tx_type: u8, result: &ExecutionResult<H>, cumulative_gas_used: u64) -> Self {
+        Self {
+            tx_type,
+            success: result.is_success(),
+            gas_used: result.gas_used(),
+            cumulative_gas_used,
+            logs: result.logs().to_vec(),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! [EIP-7685](https://eips.ethereum.org/EIPS/eip-7685) execution layer requests.
+//!
+//! Requests are collected at the end of the block from deposit contract logs
+//! ([EIP-6110](https://eips.ethereum.org/EIPS/eip-6110)) and from the withdrawal
+//! ([EIP-7002](https://eips.ethereum.org/EIPS/eip-7002)) and consolidation
+//! ([EIP-7251](https://eips.ethereum.org/EIPS/eip-7251)) request system calls.
+use crate::{BlockExecutionError, Receipt};
+use primitives::{address, b256, Address, Bytes, B256};
+use sha2::{Digest, Sha256};
+use std::vec::Vec;
+
+/// Request type of the EIP-6110 deposit requests.
+pub const DEPOSIT_REQUEST_TYPE: u8 = 0x00;
+/// Request type of the EIP-7002 withdrawal requests.
+pub const WITHDRAWAL_REQUEST_TYPE: u8 = 0x01;
+/// Request type of the EIP-7251 consolidation requests.
+pub const CONSOLIDATION_REQUEST_TYPE: u8 = 0x02;
+
+/// Address of the mainnet deposit contract.
+pub const MAINNET_DEPOSIT_CONTRACT_ADDRESS: Address =
+    address!("0x00000000219ab540356cBB839Cbe05303d7705Fa");
+
+/// Topic of the `DepositEvent(bytes,bytes,bytes,bytes,bytes)` event.
+pub const DEPOSIT_EVENT_TOPIC: B256 =
+    b256!("0x649bbc62d0e31342afea4e5cd82d4049e7e1ee912fc0889aa790803be39038c5");
+
+/// Size of the ABI encoded `DepositEvent` data.
+const DEPOSIT_EVENT_DATA_SIZE: usize = 576;
+
+/// Offset and size of each field of the `DepositEvent`, in order:
+/// pubkey, withdrawal credentials, amount, signature and index.
+const DEPOSIT_EVENT_FIELDS: [(usize, usize); 5] =
+    [(160, 48), (256, 32), (320, 8), (384, 96), (512, 8)];
+
+/// Ordered list of EIP-7685 requests.
+///
+/// Every request is prefixed with its one byte request type.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Requests(Vec<Bytes>);
+
+impl Requests {
+    /// Creates new empty requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes the request data with the given request type.
+    ///
+    /// Requests with empty data are skipped as defined by EIP-7685.
+    pub fn push_request_with_type(&mut self, request_type: u8, data: impl AsRef<[u8]>) {
+        let data = data.as_ref();
+        if data.is_empty() {
+            return;
+        }
+        let mut request = Vec::with_capacity(data.len() + 1);
+        request.push(request_type);
+        request.extend_from_slice(data);
+        self.0.push(request.into());
+    }
+
+    /// Returns the requests.
+    pub fn requests(&self) -> &[Bytes] {
+        &self.0
+    }
+
+    /// Consumes the type and returns the requests.
+    pub fn into_inner(self) -> Vec<Bytes> {
+        self.0
+    }
+
+    /// Returns `true` if there are no requests.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the requests hash as defined in EIP-7685.
+    ///
+    /// `sha256(sha256(requests_0) ++ sha256(requests_1) ++ ...)`
+    pub fn requests_hash(&self) -> B256 {
+        let mut hasher = Sha256::new();
+        for request in &self.0 {
+            hasher.update(Sha256::digest(request));
+        }
+        B256::from_slice(&hasher.finalize())
+    }
+}
+
+/// Parses EIP-6110 deposit requests from the `DepositEvent` logs of the deposit contract.
+///
+/// Returns the concatenated deposit request data, without the request type.
+pub fn parse_deposits_from_receipts<E>(
+    deposit_contract: Address,
+    receipts: &[Receipt],
+) -> Result<Vec<u8>, BlockExecutionError<E>> {
+    let mut out = Vec::new();
+    let logs = receipts
+        .iter()
+        .flat_map(|receipt| &receipt.logs)
+        .filter(|log| {
+            log.address == deposit_contract && log.topics().first() == Some(&DEPOSIT_EVENT_TOPIC)
+        });
+    for log in logs {
+        let data = &log.data.data;
+        if data.len() != DEPOSIT_EVENT_DATA_SIZE {
+            return Err(BlockExecutionError::InvalidDepositEventLayout);
+        }
+        for (i, (offset, size)) in DEPOSIT_EVENT_FIELDS.into_iter().enumerate() {
+            // check that both the offset of the field and the size prefix are as expected.
+            if read_word(data, i * 32) != Some(offset) || read_word(data, offset) != Some(size) {
+                return Err(BlockExecutionError::InvalidDepositEventLayout);
+            }
+            out.extend_from_slice(&data[offset + 32..offset + 32 + size]);
+        }
+    }
+    Ok(out)
+}
+
+/// Reads a 32 byte big endian word at the given offset as `usize`.
+///
+/// Returns `None` if the value does not fit in `usize`.
+fn read_word(data: &[u8], offset: usize) -> Option<usize> {
+    let word = data.get(offset..offset + 32)?;
+    let (high, low) = word.split_at(32 - core::mem::size_of::<usize>());
+    if high.iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(usize::from_be_bytes(low.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::{hex, Log, LogData};
+    use std::vec;
+
+    fn deposit_event_data() -> Vec<u8> {
+        let mut data = vec![0u8; DEPOSIT_EVENT_DATA_SIZE];
+        for (i, (offset, size)) in DEPOSIT_EVENT_FIELDS.into_iter().enumerate() {
+            data[i * 32 + 31] = offset as u8;
+            data[i * 32 + 30] = (offset >> 8) as u8;
+            data[offset + 31] = size as u8;
+            data[offset + 32..offset + 32 + size].fill(i as u8 + 1);
+        }
+        data
+    }
+
+    fn receipt_with_log(address: Address, data: Vec<u8>) -> Receipt {
+        Receipt {
+            logs: vec![Log {
+                address,
+                data: LogData::new_unchecked(vec![DEPOSIT_EVENT_TOPIC], data.into()),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_deposit() {
+        let receipts = [
+            receipt_with_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, deposit_event_data()),
+            // log from other contract is skipped.
+            receipt_with_log(Address::ZERO, deposit_event_data()),
+        ];
+        let deposits =
+            parse_deposits_from_receipts::<()>(MAINNET_DEPOSIT_CONTRACT_ADDRESS, &receipts)
+                .unwrap();
+        assert_eq!(deposits.len(), 192);
+        assert_eq!(deposits[..48], [1; 48]);
+        assert_eq!(deposits[48..80], [2; 32]);
+        assert_eq!(deposits[80..88], [3; 8]);
+        assert_eq!(deposits[88..184], [4; 96]);
+        assert_eq!(deposits[184..], [5; 8]);
+    }
+
+    #[test]
+    fn parse_deposit_invalid_layout() {
+        let mut data = deposit_event_data();
+        // wrong pubkey size
+        data[160 + 31] = 47;
+        let receipts = [receipt_with_log(MAINNET_DEPOSIT_CONTRACT_ADDRESS, data)];
+        assert_eq!(
+            parse_deposits_from_receipts::<()>(MAINNET_DEPOSIT_CONTRACT_ADDRESS, &receipts),
+            Err(BlockExecutionError::InvalidDepositEventLayout)
+        );
+    }
+
+    #[test]
+    fn requests_hash() {
+        // hash of empty requests is sha256 of empty input.
+        assert_eq!(
+            Requests::new().requests_hash(),
+            B256::from(hex!(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            ))
+        );
+
+        let mut requests = Requests::new();
+        requests.push_request_with_type(DEPOSIT_REQUEST_TYPE, []);
+        assert!(requests.is_empty());
+        requests.push_request_with_type(WITHDRAWAL_REQUEST_TYPE, [0xaa]);
+        assert_eq!(requests.requests(), &[Bytes::from_static(&[0x01, 0xaa])]);
+
+        let expected = Sha256::digest(Sha256::digest([0x01, 0xaa]));
+        assert_eq!(requests.requests_hash(), B256::from_slice(&expected));
+    }
+}
@@ -0,0 +1,126 @@
+//! System calls that are executed at the block boundaries.
+//!
+//! Pre-block: [EIP-2935](https://eips.ethereum.org/EIPS/eip-2935) (block hashes) and
+//! [EIP-4788](https://eips.ethereum.org/EIPS/eip-4788) (beacon roots).
+//!
+//! Post-block: [EIP-7002](https://eips.ethereum.org/EIPS/eip-7002) (withdrawal requests) and
+//! [EIP-7251](https://eips.ethereum.org/EIPS/eip-7251) (consolidation requests).
+use crate::BlockExecutionError;
+use context_interface::{result::ExecutionResult, ContextTr, JournalTr};
+use handler::{ContextTrDbError, EvmTr, SystemCallCommitEvm};
+use primitives::{address, Address, Bytes, B256};
+
+/// Address of the EIP-2935 history storage contract.
+pub const HISTORY_STORAGE_ADDRESS: Address = address!("0x0000F90827F1C53a10cb7A02335B175320002935");
+
+/// Address of the EIP-4788 beacon roots contract.
+pub const BEACON_ROOTS_ADDRESS: Address = address!("0x000F3df6D732807Ef1319fB7B8bB8522d0Beac02");
+
+/// Address of the EIP-7002 withdrawal request contract.
+pub const WITHDRAWAL_REQUEST_ADDRESS: Address =
+    address!("0x00000961Ef480Eb55e80D19ad83579A64c007002");
+
+/// Address of the EIP-7251 consolidation request contract.
+pub const CONSOLIDATION_REQUEST_ADDRESS: Address =
+    address!("0x0000BBdDc7CE488642fb579F8B00f3a590007251");
+
+/// Executes the system call and commits its state.
+#[inline]
+fn system_call<EVM, H>(
+    evm: &mut EVM,
+    address: Address,
+    data: Bytes,
+) -> Result<ExecutionResult<H>, BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<ExecutionResult = ExecutionResult<H>>,
+{
+    evm.system_call_commit(address, data)
+        .map_err(|error| BlockExecutionError::SystemCall { address, error })
+}
+
+/// Block hash system call EIP-2935.
+///
+/// Stores the parent block hash inside the history storage contract.
+pub fn system_call_eip2935_blockhash<EVM, H>(
+    evm: &mut EVM,
+    parent_block_hash: B256,
+) -> Result<(), BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<ExecutionResult = ExecutionResult<H>>,
+{
+    system_call(evm, HISTORY_STORAGE_ADDRESS, parent_block_hash.0.into())?;
+    Ok(())
+}
+
+/// Beacon root system call EIP-4788.
+///
+/// Stores the parent beacon block root inside the beacon roots contract.
+pub fn system_call_eip4788_beacon_root<EVM, H>(
+    evm: &mut EVM,
+    parent_beacon_block_root: B256,
+) -> Result<(), BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<ExecutionResult = ExecutionResult<H>>,
+{
+    system_call(evm, BEACON_ROOTS_ADDRESS, parent_beacon_block_root.0.into())?;
+    Ok(())
+}
+
+/// Withdrawal requests system call EIP-7002.
+///
+/// Returns the withdrawal requests data. Block is invalid if the contract has no code or the
+/// call does not succeed.
+pub fn system_call_eip7002_withdrawal_requests<EVM, H>(
+    evm: &mut EVM,
+) -> Result<Bytes, BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<
+            ExecutionResult = ExecutionResult<H>,
+            Error: From<ContextTrDbError<EVM::Context>>,
+        > + EvmTr,
+{
+    request_system_call(evm, WITHDRAWAL_REQUEST_ADDRESS)
+}
+
+/// Consolidation requests system call EIP-7251.
+///
+/// Returns the consolidation requests data. Block is invalid if the contract has no code or the
+/// call does not succeed.
+pub fn system_call_eip7251_consolidation_requests<EVM, H>(
+    evm: &mut EVM,
+) -> Result<Bytes, BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<
+            ExecutionResult = ExecutionResult<H>,
+            Error: From<ContextTrDbError<EVM::Context>>,
+        > + EvmTr,
+{
+    request_system_call(evm, CONSOLIDATION_REQUEST_ADDRESS)
+}
+
+/// Calls the request contract with empty data and returns its output.
+#[inline]
+fn request_system_call<EVM, H>(
+    evm: &mut EVM,
+    address: Address,
+) -> Result<Bytes, BlockExecutionError<EVM::Error>>
+where
+    EVM: SystemCallCommitEvm<
+            ExecutionResult = ExecutionResult<H>,
+            Error: From<ContextTrDbError<EVM::Context>>,
+        > + EvmTr,
+{
+    // Unlike the pre-block system calls, a missing request contract is not skipped.
+    let account = evm
+        .ctx_mut()
+        .journal_mut()
+        .load_account_with_code(address)
+        .map_err(|e| BlockExecutionError::Database(e.into()))?;
+    if account.info.is_empty_code_hash() {
+        return Err(BlockExecutionError::SystemContractMissing { address });
+    }
+    match system_call(evm, address, Bytes::new())? {
+        ExecutionResult::Success { output, .. } => Ok(output.into_data()),
+        _ => Err(BlockExecutionError::SystemCallFailed { address }),
+    }
+}
@@ -449,14 +449,22 @@ impl<ENTRY: JournalEntryTr> JournalInner<ENTRY> {
     /// Makes a checkpoint that in case of Revert can bring back state to this point.
     #[inline]
     pub fn checkpoint(&mut self) -> JournalCheckpoint {
-        let checkpoint = JournalCheckpoint {
-            log_i: self.logs.len(),
-            journal_i: self.journal.len(),
-        };
+        let checkpoint = self.current_checkpoint();
         self.depth += 1;
         checkpoint
     }
 
+    /// Returns the checkpoint of the current state without entering a new depth.
+    ///
+    /// State can be brought back to it with [`JournalInner::revert_to_checkpoint`].
+    #[inline]
+    pub fn current_checkpoint(&self) -> JournalCheckpoint {
+        JournalCheckpoint {
+            log_i: self.logs.len(),
+            journal_i: self.journal.len(),
+        }
+    }
+
     /// Commits the checkpoint.
     #[inline]
     pub fn checkpoint_commit(&mut self) {
@@ -466,10 +474,19 @@ impl<ENTRY: JournalEntryTr> JournalInner<ENTRY> {
     /// Reverts all changes to state until given checkpoint.
     #[inline]
     pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
+        self.depth = self.depth.saturating_sub(1);
+        self.revert_to_checkpoint(checkpoint);
+    }
+
+    /// Reverts all changes to state until given checkpoint, depth of the journal is not changed.
+    ///
+    /// Checkpoint must be taken at the current depth or in one of the subcalls that are already
+    /// finished, see [`JournalInner::current_checkpoint`].
+    #[inline]
+    pub fn revert_to_checkpoint(&mut self, checkpoint: JournalCheckpoint) {
         let is_spurious_dragon_enabled = self.spec.is_enabled_in(SPURIOUS_DRAGON);
         let state = &mut self.state;
         let transient_storage = &mut self.transient_storage;
-        self.depth = self.depth.saturating_sub(1);
         self.logs.truncate(checkpoint.log_i);
 
         // iterate over last N journals sets and revert our global state
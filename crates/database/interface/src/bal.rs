@@ -132,11 +132,12 @@ impl BalState {
         if let Some(bal) = &self.bal {
             let is_none = basic.is_none();
             let mut bal_basic = core::mem::take(basic).unwrap_or_default();
-            bal.populate_account_info(account_id, self.bal_index, &mut bal_basic)
+            let changed = bal
+                .populate_account_info(account_id, self.bal_index, &mut bal_basic)
                 .expect("Invalid account id");
 
             // if it is not changed, check if it is none and return it.
-            if is_none {
+            if !changed && is_none {
                 return true;
             }
 
@@ -389,3 +390,55 @@ impl<DB: DatabaseCommit> DatabaseCommit for BalDatabase<DB> {
         self.db.commit(changes);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::U256;
+    use state::bal::{AccountBal, AccountInfoBal, BalWrites};
+
+    #[test]
+    fn basic_by_account_id() {
+        let account = AccountBal {
+            account_info: AccountInfoBal {
+                balance: BalWrites::new(std::vec![(1, U256::from(100))]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bal_state = BalState {
+            bal: Some(Arc::new(Bal::from_iter([(Address::ZERO, account)]))),
+            ..Default::default()
+        };
+
+        // unchanged and none, account stays not existing.
+        let mut basic = None;
+        assert!(bal_state.basic_by_account_id(0, &mut basic));
+        assert_eq!(basic, None);
+
+        // unchanged and some, database value is kept.
+        let info = AccountInfo::default().with_nonce(1);
+        let mut basic = Some(info.clone());
+        assert!(bal_state.basic_by_account_id(0, &mut basic));
+        assert_eq!(basic, Some(info.clone()));
+
+        // changed and none, account is created from the BAL.
+        bal_state.bal_index = 2;
+        let mut basic = None;
+        assert!(bal_state.basic_by_account_id(0, &mut basic));
+        assert_eq!(
+            basic,
+            Some(AccountInfo::default().with_balance(U256::from(100)))
+        );
+
+        // changed and some, BAL overrides the database value.
+        let mut basic = Some(info.clone());
+        assert!(bal_state.basic_by_account_id(0, &mut basic));
+        assert_eq!(basic, Some(info.with_balance(U256::from(100))));
+
+        // without BAL the value is untouched.
+        let mut basic = None;
+        assert!(!BalState::default().basic_by_account_id(0, &mut basic));
+        assert_eq!(basic, None);
+    }
+}
@@ -23,6 +23,8 @@ pub enum AlloyDBError {
     /// - The node has pruned the block data
     /// - Using a light client that doesn't have the block
     BlockNotFound(u64),
+    /// Block not found for the given block id.
+    BlockIdNotFound(BlockId),
 }
 
 impl DBErrorMarker for AlloyDBError {}
@@ -32,6 +34,7 @@ impl Display for AlloyDBError {
         match self {
             Self::Transport(err) => write!(f, "Transport error: {err}"),
             Self::BlockNotFound(number) => write!(f, "Block not found: {number}"),
+            Self::BlockIdNotFound(id) => write!(f, "Block not found: {id}"),
         }
     }
 }
@@ -40,7 +43,7 @@ impl Error for AlloyDBError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             Self::Transport(err) => Some(err),
-            Self::BlockNotFound(_) => None,
+            Self::BlockNotFound(_) | Self::BlockIdNotFound(_) => None,
         }
     }
 }
@@ -77,6 +80,16 @@ impl<N: Network, P: Provider<N>> AlloyDB<N, P> {
     pub fn set_block_number(&mut self, block_number: BlockId) {
         self.block_number = block_number;
     }
+
+    /// Returns the provider.
+    pub(crate) fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Returns the block on which the queries are based on.
+    pub(crate) fn block_id(&self) -> BlockId {
+        self.block_number
+    }
 }
 
 impl<N: Network, P: Provider<N>> DatabaseAsyncRef for AlloyDB<N, P> {
@@ -0,0 +1,515 @@
+//! Persistent on-disk cache for [`AlloyDB`].
+
+use crate::alloydb::{AlloyDB, AlloyDBError, BlockId};
+use alloy_eips::BlockNumberOrTag;
+use alloy_provider::{
+    network::{primitives::HeaderResponse, BlockResponse},
+    Network, Provider,
+};
+use database_interface::async_db::DatabaseAsyncRef;
+use primitives::{Address, Bytes, HashMap, StorageKey, StorageValue, B256, U256};
+use state::{AccountInfo, Bytecode};
+use std::{
+    fs, io,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+/// Magic bytes and version of the cache file format.
+const MAGIC: &[u8; 8] = b"revmfc\0\x01";
+
+/// Cache of the state fetched from a provider at one block.
+///
+/// The cache is stored in `{dir}/{chain_id}/{block_hash}.bin`, so a reorged block does not reuse
+/// the entries of the block it replaced. Unreadable or corrupted files are discarded.
+///
+/// The cache is written by [`ForkCache::flush`] and when it is dropped, if it has new entries.
+#[derive(Debug)]
+pub struct ForkCache {
+    path: Option<PathBuf>,
+    block_hash: B256,
+    data: RwLock<ForkCacheData>,
+    dirty: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ForkCacheData {
+    pub(crate) accounts: HashMap<Address, Option<AccountInfo>>,
+    pub(crate) contracts: HashMap<B256, Bytecode>,
+    pub(crate) storage: HashMap<(Address, StorageKey), StorageValue>,
+    pub(crate) block_hashes: HashMap<u64, B256>,
+}
+
+impl ForkCache {
+    /// Opens the cache of the block in the given directory.
+    pub fn open(dir: impl AsRef<Path>, chain_id: u64, block_hash: B256) -> Self {
+        let path = dir
+            .as_ref()
+            .join(chain_id.to_string())
+            .join(format!("{block_hash}.bin"));
+        let data = fs::read(&path)
+            .ok()
+            .and_then(|bytes| ForkCacheData::decode(&mut bytes.as_slice(), block_hash).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            block_hash,
+            data: RwLock::new(data),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates a cache that is not stored on disk.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            block_hash: B256::ZERO,
+            data: RwLock::default(),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the path of the cache file, `None` if the cache is not stored on disk.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        let data = self.read();
+        data.accounts.is_empty() && data.storage.is_empty() && data.block_hashes.is_empty()
+    }
+
+    /// Removes all entries, the file is cleared on the next flush.
+    pub fn clear(&self) {
+        *self.write() = ForkCacheData::default();
+    }
+
+    /// Writes the cache to disk if it has changed since the last flush.
+    pub fn flush(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mut bytes = Vec::new();
+        self.read().encode(&mut bytes, self.block_hash)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first so a concurrent reader never sees a partial file.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path).inspect_err(|_| self.dirty.store(true, Ordering::Relaxed))
+    }
+
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, ForkCacheData> {
+        self.data.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, ForkCacheData> {
+        self.dirty.store(true, Ordering::Relaxed);
+        self.data.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for ForkCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl ForkCacheData {
+    fn encode(&self, w: &mut impl Write, block_hash: B256) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(block_hash.as_slice())?;
+
+        write_len(w, self.contracts.len())?;
+        for (hash, code) in &self.contracts {
+            w.write_all(hash.as_slice())?;
+            write_bytes(w, code.original_byte_slice())?;
+        }
+        write_len(w, self.accounts.len())?;
+        for (address, info) in &self.accounts {
+            w.write_all(address.as_slice())?;
+            match info {
+                Some(info) => {
+                    w.write_all(&[1])?;
+                    w.write_all(&info.balance.to_be_bytes::<32>())?;
+                    w.write_all(&info.nonce.to_le_bytes())?;
+                    w.write_all(info.code_hash.as_slice())?;
+                }
+                None => w.write_all(&[0])?,
+            }
+        }
+        write_len(w, self.storage.len())?;
+        for ((address, key), value) in &self.storage {
+            w.write_all(address.as_slice())?;
+            w.write_all(&key.to_be_bytes::<32>())?;
+            w.write_all(&value.to_be_bytes::<32>())?;
+        }
+        write_len(w, self.block_hashes.len())?;
+        for (number, hash) in &self.block_hashes {
+            w.write_all(&number.to_le_bytes())?;
+            w.write_all(hash.as_slice())?;
+        }
+        Ok(())
+    }
+
+    fn decode(r: &mut impl Read, block_hash: B256) -> io::Result<Self> {
+        if read_array::<8>(r)? != *MAGIC || B256::new(read_array(r)?) != block_hash {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let mut data = Self::default();
+
+        for _ in 0..read_len(r)? {
+            let hash = B256::new(read_array(r)?);
+            let code = Bytecode::new_raw(read_bytes(r)?);
+            data.contracts.insert(hash, code);
+        }
+        for _ in 0..read_len(r)? {
+            let address = Address::new(read_array(r)?);
+            let info = match read_array::<1>(r)? {
+                [0] => None,
+                [1] => {
+                    let balance = U256::from_be_bytes::<32>(read_array(r)?);
+                    let nonce = u64::from_le_bytes(read_array(r)?);
+                    let code_hash = B256::new(read_array(r)?);
+                    let code = data
+                        .contracts
+                        .get(&code_hash)
+                        .cloned()
+                        .ok_or(io::ErrorKind::InvalidData)?;
+                    Some(AccountInfo::new(balance, nonce, code_hash, code))
+                }
+                _ => return Err(io::ErrorKind::InvalidData.into()),
+            };
+            data.accounts.insert(address, info);
+        }
+        for _ in 0..read_len(r)? {
+            let address = Address::new(read_array(r)?);
+            let key = StorageKey::from_be_bytes::<32>(read_array(r)?);
+            let value = StorageValue::from_be_bytes::<32>(read_array(r)?);
+            data.storage.insert((address, key), value);
+        }
+        for _ in 0..read_len(r)? {
+            let number = u64::from_le_bytes(read_array(r)?);
+            data.block_hashes.insert(number, B256::new(read_array(r)?));
+        }
+        Ok(data)
+    }
+}
+
+fn write_len(w: &mut impl Write, len: usize) -> io::Result<()> {
+    w.write_all(&(len as u64).to_le_bytes())
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_len(w, bytes.len())?;
+    w.write_all(bytes)
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_len(r: &mut impl Read) -> io::Result<u64> {
+    read_array(r).map(u64::from_le_bytes)
+}
+
+fn read_bytes(r: &mut impl Read) -> io::Result<Bytes> {
+    let len = read_len(r)?;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes.into())
+}
+
+/// [`AlloyDB`] that serves the lookups from a persistent [`ForkCache`].
+///
+/// The block is resolved to its hash when the database is created and the queries are pinned to
+/// that hash, so tags like `latest` use the cache of the block they resolved to.
+/// The `pending` block changes over time, its queries are not pinned and are only cached in
+/// memory.
+#[derive(Debug)]
+pub struct CachedAlloyDB<N: Network, P: Provider<N>> {
+    pub(crate) db: AlloyDB<N, P>,
+    pub(crate) cache: Arc<ForkCache>,
+    /// Maximum number of requests in a prefetch batch.
+    pub(crate) batch_size: usize,
+}
+
+impl<N: Network, P: Provider<N>> CachedAlloyDB<N, P> {
+    /// Creates a new CachedAlloyDB that stores the cache in the given directory.
+    ///
+    /// Fetches the chain id and the block from the provider, except for the `pending` block.
+    pub async fn new(
+        provider: P,
+        block_id: BlockId,
+        cache_dir: impl AsRef<Path>,
+    ) -> Result<Self, AlloyDBError> {
+        if block_id == BlockId::Number(BlockNumberOrTag::Pending) {
+            return Ok(Self {
+                db: AlloyDB::new(provider, block_id),
+                cache: Arc::new(ForkCache::in_memory()),
+                batch_size: 100,
+            });
+        }
+
+        let chain_id = provider.get_chain_id().await?;
+        let block = provider
+            .get_block(block_id)
+            .await?
+            .ok_or(AlloyDBError::BlockIdNotFound(block_id))?;
+        let hash = B256::new(*block.header().hash());
+
+        Ok(Self {
+            db: AlloyDB::new(provider, BlockId::hash(hash)),
+            cache: Arc::new(ForkCache::open(cache_dir, chain_id, hash)),
+            batch_size: 100,
+        })
+    }
+
+    /// Sets the maximum number of requests sent in one JSON-RPC batch by
+    /// [`CachedAlloyDB::prefetch`](crate::CachedAlloyDB::prefetch), defaults to 100.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the cache, it can be used to flush the cache while the database is in use.
+    pub fn cache(&self) -> &Arc<ForkCache> {
+        &self.cache
+    }
+}
+
+impl<N: Network, P: Provider<N>> DatabaseAsyncRef for CachedAlloyDB<N, P> {
+    type Error = AlloyDBError;
+
+    async fn basic_async_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        if let Some(info) = self.cache.read().accounts.get(&address) {
+            return Ok(info.clone());
+        }
+        let info = self.db.basic_async_ref(address).await?;
+        let mut data = self.cache.write();
+        if let Some(info) = &info {
+            if let Some(code) = &info.code {
+                data.contracts.insert(info.code_hash, code.clone());
+            }
+        }
+        data.accounts.insert(address, info.clone());
+        Ok(info)
+    }
+
+    async fn block_hash_async_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        if let Some(hash) = self.cache.read().block_hashes.get(&number) {
+            return Ok(*hash);
+        }
+        let hash = self.db.block_hash_async_ref(number).await?;
+        self.cache.write().block_hashes.insert(number, hash);
+        Ok(hash)
+    }
+
+    async fn code_by_hash_async_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = self.cache.read().contracts.get(&code_hash) {
+            return Ok(code.clone());
+        }
+        self.db.code_by_hash_async_ref(code_hash).await
+    }
+
+    async fn storage_async_ref(
+        &self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        if let Some(value) = self.cache.read().storage.get(&(address, index)) {
+            return Ok(*value);
+        }
+        let value = self.db.storage_async_ref(address, index).await?;
+        self.cache.write().storage.insert((address, index), value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloy_provider::ProviderBuilder;
+    use alloy_rpc_client::RpcClient;
+    use alloy_transport::mock::{Asserter, MockTransport};
+    use primitives::{address, b256, bytes};
+    use serde_json::{json, Value};
+    use std::{
+        sync::Mutex,
+        task::{Context, Poll},
+    };
+
+    /// Mocked transport that records the sent requests.
+    #[derive(Clone, Debug)]
+    struct RecordingTransport {
+        inner: MockTransport,
+        requests: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl<R: serde::Serialize> tower::Service<R> for RecordingTransport
+    where
+        MockTransport: tower::Service<R>,
+    {
+        type Response = <MockTransport as tower::Service<R>>::Response;
+        type Error = <MockTransport as tower::Service<R>>::Error;
+        type Future = <MockTransport as tower::Service<R>>::Future;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: R) -> Self::Future {
+            let request = serde_json::to_value(&req).unwrap();
+            self.requests.lock().unwrap().push(request);
+            self.inner.call(req)
+        }
+    }
+
+    /// Creates a mocked provider, returns it with the asserter and the recorded requests.
+    pub(crate) fn mocked_provider() -> (impl Provider, Asserter, Arc<Mutex<Vec<Value>>>) {
+        let asserter = Asserter::new();
+        let requests = Arc::default();
+        let transport = RecordingTransport {
+            inner: MockTransport::new(asserter.clone()),
+            requests: Arc::clone(&requests),
+        };
+        let provider = ProviderBuilder::new().connect_client(RpcClient::new(transport, true));
+        (provider, asserter, requests)
+    }
+
+    /// Returns the block parameter of the last request.
+    fn last_block_param(requests: &Mutex<Vec<Value>>) -> Value {
+        let requests = requests.lock().unwrap();
+        let params = requests.last().unwrap()["params"].as_array().unwrap();
+        params.last().unwrap().clone()
+    }
+
+    /// Returns the JSON-RPC response of the block.
+    pub(crate) fn block(number: u64, hash: B256) -> Value {
+        json!({
+            "hash": hash,
+            "parentHash": B256::ZERO,
+            "sha3Uncles": B256::ZERO,
+            "miner": Address::ZERO,
+            "stateRoot": B256::ZERO,
+            "transactionsRoot": B256::ZERO,
+            "receiptsRoot": B256::ZERO,
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "difficulty": "0x0",
+            "number": format!("{number:#x}"),
+            "gasLimit": "0x0",
+            "gasUsed": "0x0",
+            "timestamp": "0x0",
+            "extraData": "0x",
+            "mixHash": B256::ZERO,
+            "nonce": "0x0000000000000000",
+            "uncles": [],
+            "transactions": [],
+        })
+    }
+
+    /// Creates the database with the mocked responses of the chain id and the block.
+    async fn cached_db(
+        dir: &Path,
+        block_id: BlockId,
+        number: u64,
+        hash: B256,
+    ) -> (
+        CachedAlloyDB<alloy_provider::network::Ethereum, impl Provider>,
+        Asserter,
+        Arc<Mutex<Vec<Value>>>,
+    ) {
+        let (provider, asserter, requests) = mocked_provider();
+        asserter.push_success(&"0x1");
+        asserter.push_success(&block(number, hash));
+        let db = CachedAlloyDB::new(provider, block_id, dir).await.unwrap();
+        (db, asserter, requests)
+    }
+
+    #[tokio::test]
+    async fn cache_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("revm-fork-cache-{}", std::process::id()));
+        let hash = b256!("0x1111111111111111111111111111111111111111111111111111111111111111");
+        let address = address!("0x0d4a11d5EEaaC28EC3F61d100daF4d40471f1852");
+        let key = StorageKey::from(1);
+
+        let (db, asserter, requests) = cached_db(&dir, BlockId::latest(), 16, hash).await;
+        asserter.push_success(&"0x2a");
+        assert_eq!(
+            db.storage_async_ref(address, key).await.unwrap(),
+            U256::from(42)
+        );
+        assert_eq!(last_block_param(&requests), json!({ "blockHash": hash }));
+        asserter.push_success(&"0x1");
+        asserter.push_success(&"0x64");
+        asserter.push_success(&bytes!("0x6000"));
+        let info = db.basic_async_ref(address).await.unwrap().unwrap();
+        assert_eq!(info.balance, U256::from(100));
+        drop(db);
+        assert!(dir.join("1").join(format!("{hash}.bin")).exists());
+
+        // same block, every lookup is served from disk without responses.
+        let (db, ..) = cached_db(&dir, BlockId::number(16), 16, hash).await;
+        assert_eq!(
+            db.storage_async_ref(address, key).await.unwrap(),
+            U256::from(42)
+        );
+        let cached = db.basic_async_ref(address).await.unwrap().unwrap();
+        assert_eq!(cached, info);
+        let code = db.code_by_hash_async_ref(info.code_hash).await.unwrap();
+        assert_eq!(code.original_byte_slice(), &[0x60, 0x00]);
+        drop(db);
+
+        // `latest` moved to the next block.
+        let next = b256!("0x2222222222222222222222222222222222222222222222222222222222222222");
+        let (db, asserter, _) = cached_db(&dir, BlockId::latest(), 17, next).await;
+        asserter.push_success(&"0x7");
+        assert_eq!(
+            db.storage_async_ref(address, key).await.unwrap(),
+            U256::from(7)
+        );
+        drop(db);
+
+        // block 16 was reorged.
+        let reorged = b256!("0x3333333333333333333333333333333333333333333333333333333333333333");
+        let (db, asserter, _) = cached_db(&dir, BlockId::number(16), 16, reorged).await;
+        assert!(db.cache().is_empty());
+        asserter.push_success(&"0x8");
+        assert_eq!(
+            db.storage_async_ref(address, key).await.unwrap(),
+            U256::from(8)
+        );
+        drop(db);
+
+        // pending block is not stored and its queries are not pinned.
+        let (provider, asserter, requests) = mocked_provider();
+        let db = CachedAlloyDB::new(provider, BlockId::pending(), &dir)
+            .await
+            .unwrap();
+        assert!(db.cache().path().is_none());
+        asserter.push_success(&"0x9");
+        assert_eq!(
+            db.storage_async_ref(address, key).await.unwrap(),
+            U256::from(9)
+        );
+        assert_eq!(last_block_param(&requests), json!("pending"));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        drop(db);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
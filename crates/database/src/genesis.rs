@@ -0,0 +1,314 @@
+//! Import and dump of the state in the geth genesis `alloc` and anvil `dump_state` formats.
+
+use crate::{Cache, CacheDB, CacheState, State};
+use bytecode::Bytecode;
+use database_interface::{Database, DatabaseRef};
+use primitives::{
+    ruint::aliases::U64, Address, Bytes, HashMap, StorageKey, StorageValue, B256, KECCAK_EMPTY,
+    U256,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use state::AccountInfo;
+use std::collections::BTreeMap;
+
+/// Accounts of the genesis keyed by their address.
+pub type GenesisAlloc = BTreeMap<Address, GenesisAccount>;
+
+/// Geth-style `genesis.json` file.
+///
+/// Only the `alloc` is read, the chain config and the genesis block fields are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis {
+    /// Accounts of the genesis.
+    #[serde(default)]
+    pub alloc: GenesisAlloc,
+}
+
+/// Account of the genesis `alloc`.
+///
+/// Numbers are read from JSON numbers, decimal or hex strings. Nonce is written as a hex
+/// string and storage as 32 byte hex strings, as geth does.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAccount {
+    /// Account balance in wei.
+    pub balance: U256,
+    /// Account nonce.
+    #[serde(
+        default,
+        skip_serializing_if = "is_zero",
+        serialize_with = "serialize_quantity",
+        deserialize_with = "deserialize_quantity"
+    )]
+    pub nonce: u64,
+    /// Account bytecode.
+    #[serde(default, skip_serializing_if = "<[u8]>::is_empty")]
+    pub code: Bytes,
+    /// Account storage.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_storage"
+    )]
+    pub storage: BTreeMap<StorageKey, StorageValue>,
+}
+
+impl GenesisAccount {
+    /// Creates the account from its info and storage.
+    ///
+    /// Code of the info has to be set, the code is empty otherwise.
+    pub fn new(info: &AccountInfo, storage: BTreeMap<StorageKey, StorageValue>) -> Self {
+        Self {
+            balance: info.balance,
+            nonce: info.nonce,
+            code: info
+                .code
+                .as_ref()
+                .map(Bytecode::original_bytes)
+                .unwrap_or_default(),
+            storage,
+        }
+    }
+
+    /// Returns the account info with the decoded code.
+    pub fn account_info(&self) -> AccountInfo {
+        if self.code.is_empty() {
+            return AccountInfo::new(self.balance, self.nonce, KECCAK_EMPTY, Bytecode::new());
+        }
+        // Malformed EIP-7702 code is kept as is, like the clients do.
+        let code = Bytecode::new_raw_checked(self.code.clone())
+            .unwrap_or_else(|_| Bytecode::new_legacy(self.code.clone()));
+        AccountInfo::default()
+            .with_balance(self.balance)
+            .with_nonce(self.nonce)
+            .with_code(code)
+    }
+}
+
+/// State written by anvil `anvil_dumpState` and `--dump-state`.
+///
+/// Only the accounts are read, the blocks and transactions of the dump are ignored, so the
+/// state can be loaded with `anvil_loadState` or `--load-state`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnvilState {
+    /// Accounts keyed by their address.
+    pub accounts: BTreeMap<Address, AnvilAccount>,
+}
+
+/// Account of the [`AnvilState`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnvilAccount {
+    /// Account nonce.
+    #[serde(deserialize_with = "deserialize_quantity")]
+    pub nonce: u64,
+    /// Account balance in wei.
+    pub balance: U256,
+    /// Account bytecode.
+    pub code: Bytes,
+    /// Account storage.
+    #[serde(serialize_with = "serialize_storage")]
+    pub storage: BTreeMap<StorageKey, StorageValue>,
+}
+
+impl From<GenesisAccount> for AnvilAccount {
+    fn from(account: GenesisAccount) -> Self {
+        Self {
+            nonce: account.nonce,
+            balance: account.balance,
+            code: account.code,
+            storage: account.storage,
+        }
+    }
+}
+
+impl From<AnvilAccount> for GenesisAccount {
+    fn from(account: AnvilAccount) -> Self {
+        Self {
+            balance: account.balance,
+            nonce: account.nonce,
+            code: account.code,
+            storage: account.storage,
+        }
+    }
+}
+
+impl From<GenesisAlloc> for AnvilState {
+    fn from(alloc: GenesisAlloc) -> Self {
+        Self {
+            accounts: alloc
+                .into_iter()
+                .map(|(address, account)| (address, account.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<AnvilState> for GenesisAlloc {
+    fn from(state: AnvilState) -> Self {
+        state
+            .accounts
+            .into_iter()
+            .map(|(address, account)| (address, account.into()))
+            .collect()
+    }
+}
+
+impl Cache {
+    /// Dumps the existing accounts with their code and cached storage.
+    pub fn to_genesis_alloc(&self) -> GenesisAlloc {
+        self.accounts
+            .iter()
+            .filter_map(|(address, account)| {
+                let mut info = account.info()?;
+                if info.code.is_none() {
+                    info.code = self.contracts.get(&info.code_hash).cloned();
+                }
+                let storage = account.storage.iter().map(|(k, v)| (*k, *v)).collect();
+                Some((*address, GenesisAccount::new(&info, storage)))
+            })
+            .collect()
+    }
+}
+
+impl CacheState {
+    /// Dumps the existing accounts with their code and cached storage.
+    pub fn to_genesis_alloc(&self) -> GenesisAlloc {
+        self.accounts
+            .iter()
+            .filter_map(|(address, account)| {
+                let account = account.account.as_ref()?;
+                let mut info = account.info.clone();
+                if info.code.is_none() {
+                    info.code = self.contracts.get(&info.code_hash).cloned();
+                }
+                let storage = account.storage.iter().map(|(k, v)| (*k, *v)).collect();
+                Some((*address, GenesisAccount::new(&info, storage)))
+            })
+            .collect()
+    }
+}
+
+impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
+    /// Inserts the accounts with their code and storage.
+    ///
+    /// Account info is overridden, storage slots that are not in the alloc are kept.
+    pub fn insert_genesis_alloc(&mut self, alloc: &GenesisAlloc) -> Result<(), ExtDB::Error> {
+        for (address, account) in alloc {
+            self.insert_account_info(*address, account.account_info());
+            for (key, value) in &account.storage {
+                self.insert_account_storage(*address, *key, *value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<DB: Database> State<DB> {
+    /// Inserts the accounts with their code and storage into the cache.
+    ///
+    /// Accounts are inserted as loaded from the database, so they are not part of the bundle.
+    /// They are never evicted by the [cache budget](crate::StateBuilder::with_cache_budget), as
+    /// the database has no copy of them.
+    pub fn insert_genesis_alloc(&mut self, alloc: &GenesisAlloc) {
+        for (address, account) in alloc {
+            let info = account.account_info();
+            if let Some(code) = info.code.clone().filter(|code| !code.is_empty()) {
+                self.cache.contracts.insert(info.code_hash, code);
+            }
+            let storage: HashMap<_, _> = account.storage.iter().map(|(k, v)| (*k, *v)).collect();
+            self.insert_account_with_storage(*address, info, storage);
+        }
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn serialize_quantity<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    U64::from(*value).serialize(serializer)
+}
+
+fn deserialize_quantity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(U64::deserialize(deserializer)?.to())
+}
+
+fn serialize_storage<S: Serializer>(
+    storage: &BTreeMap<StorageKey, StorageValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        storage
+            .iter()
+            .map(|(key, value)| (B256::from(*key), B256::from(*value))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryDB;
+    use primitives::{address, bytes, keccak256};
+
+    const GENESIS: &str = r#"{
+        "config": { "chainId": 1337 },
+        "gasLimit": "0x1c9c380",
+        "alloc": {
+            "0x1000000000000000000000000000000000000001": {
+                "balance": "1000000000000000000"
+            },
+            "0x2000000000000000000000000000000000000002": {
+                "balance": "0x0",
+                "nonce": "0x1",
+                "code": "0x602a60005500",
+                "storage": {
+                    "0x0000000000000000000000000000000000000000000000000000000000000001": "0x2a"
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn genesis_roundtrip() {
+        let user = address!("0x1000000000000000000000000000000000000001");
+        let contract = address!("0x2000000000000000000000000000000000000002");
+        let genesis: Genesis = serde_json::from_str(GENESIS).unwrap();
+
+        let mut db = InMemoryDB::default();
+        db.insert_genesis_alloc(&genesis.alloc).unwrap();
+        let info = db.basic_ref(user).unwrap().unwrap();
+        assert_eq!(info.balance, U256::from(10).pow(U256::from(18)));
+        let info = db.basic_ref(contract).unwrap().unwrap();
+        assert_eq!(info.nonce, 1);
+        assert_eq!(info.code_hash, keccak256(bytes!("0x602a60005500")));
+        assert_eq!(
+            db.storage_ref(contract, StorageKey::from(1)).unwrap(),
+            StorageValue::from(42)
+        );
+        assert_eq!(db.cache.to_genesis_alloc(), genesis.alloc);
+
+        let mut state = State::builder().build();
+        state.insert_genesis_alloc(&genesis.alloc);
+        assert_eq!(state.cache.to_genesis_alloc(), genesis.alloc);
+
+        // geth format is written with hex quantities and full storage words.
+        let json = serde_json::to_value(&genesis.alloc[&contract]).unwrap();
+        assert_eq!(json["nonce"], "0x1");
+        let (key, value) = json["storage"].as_object().unwrap().iter().next().unwrap();
+        assert_eq!(key, &B256::with_last_byte(1).to_string());
+        assert_eq!(value, &B256::with_last_byte(42).to_string());
+
+        // anvil format has numeric nonces and always has code and storage.
+        let anvil = AnvilState::from(genesis.alloc.clone());
+        let json = serde_json::to_value(&anvil).unwrap();
+        let account = &json["accounts"]
+            .as_object()
+            .unwrap()
+            .values()
+            .next()
+            .unwrap();
+        assert_eq!(account["nonce"], 0);
+        assert_eq!(account["code"], "0x");
+        let anvil: AnvilState = serde_json::from_value(json).unwrap();
+        assert_eq!(GenesisAlloc::from(anvil), genesis.alloc);
+    }
+}
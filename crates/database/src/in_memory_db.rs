@@ -8,7 +8,7 @@ use primitives::{
     KECCAK_EMPTY, U256,
 };
 use state::{Account, AccountInfo, Bytecode};
-use std::vec::Vec;
+use std::{collections::BTreeMap, vec::Vec};
 
 /// A [Database] implementation that stores all state changes in memory.
 pub type InMemoryDB = CacheDB<EmptyDB>;
@@ -50,6 +50,10 @@ impl Default for Cache {
 /// A [Database] implementation that stores all state changes in memory.
 ///
 /// This implementation wraps a [DatabaseRef] that is used to load data ([AccountInfo]).
+///
+/// Numbered snapshots of the cache can be taken with [CacheDB::snapshot] and reverted with
+/// [CacheDB::revert_to_snapshot]. While a snapshot exists, the changes are recorded in an undo
+/// log, so taking a snapshot does not copy the cache.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CacheDB<ExtDB> {
@@ -59,6 +63,11 @@ pub struct CacheDB<ExtDB> {
     ///
     /// Note: This is read-only, data is never written to this database.
     pub db: ExtDB,
+    /// Undo log of the active snapshots.
+    ///
+    /// Struct literals that don't take snapshots can set it to [CacheSnapshots::default].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub snapshots: CacheSnapshots,
 }
 
 impl<ExtDB: Default> Default for CacheDB<ExtDB> {
@@ -88,7 +97,10 @@ impl<ExtDb> CacheDB<CacheDB<ExtDb>> {
             ..
         } = self;
 
-        inner.cache.accounts.extend(accounts);
+        for (address, account) in accounts {
+            let previous = inner.cache.accounts.insert(address, account);
+            inner.snapshots.record_replaced(address, previous);
+        }
         inner.cache.contracts.extend(contracts);
         inner.cache.logs.extend(logs);
         inner.cache.block_hashes.extend(block_hashes);
@@ -104,9 +116,15 @@ impl<ExtDb> CacheDB<CacheDB<ExtDb>> {
 impl<ExtDB> CacheDB<ExtDB> {
     /// Creates a new cache with the given external database.
     pub fn new(db: ExtDB) -> Self {
+        Self::new_with_cache(db, Cache::default())
+    }
+
+    /// Creates a new [CacheDB] from the existing cache and the external database.
+    pub fn new_with_cache(db: ExtDB, cache: Cache) -> Self {
         Self {
-            cache: Cache::default(),
+            cache,
             db,
+            snapshots: CacheSnapshots::default(),
         }
     }
 
@@ -135,6 +153,7 @@ impl<ExtDB> CacheDB<ExtDB> {
     /// Inserts account info but not override storage
     pub fn insert_account_info(&mut self, address: Address, mut info: AccountInfo) {
         self.insert_contract(&mut info);
+        self.snapshots.record_info(&self.cache, address);
         let account_entry = self.cache.accounts.entry(address).or_default();
         account_entry.update_info(info);
         if account_entry.account_state == AccountState::NotExisting {
@@ -146,13 +165,91 @@ impl<ExtDB> CacheDB<ExtDB> {
     pub fn nest(self) -> CacheDB<Self> {
         CacheDB::new(self)
     }
+
+    /// Takes a snapshot of the cache and returns its id.
+    ///
+    /// Changes made through the [CacheDB] methods and [DatabaseCommit::commit] are recorded until
+    /// the snapshot is reverted. Direct changes to the [CacheDB::cache] fields are not recorded.
+    pub fn snapshot(&mut self) -> u64 {
+        let id = self.snapshots.next_id;
+        self.snapshots.next_id += 1;
+        self.snapshots
+            .snapshots
+            .insert(id, (self.snapshots.journal.len(), self.cache.logs.len()));
+        id
+    }
+
+    /// Reverts the cache to the snapshot with the given id.
+    ///
+    /// The snapshot and every snapshot taken after it are removed, older snapshots can still be
+    /// reverted to. Returns `false` if there is no such snapshot.
+    pub fn revert_to_snapshot(&mut self, id: u64) -> bool {
+        let Some((journal_len, logs_len)) = self.snapshots.snapshots.get(&id).copied() else {
+            return false;
+        };
+        self.snapshots.snapshots.split_off(&id);
+        for undo in self.snapshots.journal.drain(journal_len..).rev() {
+            undo.apply(&mut self.cache);
+        }
+        self.cache.logs.truncate(logs_len);
+        true
+    }
+
+    /// Returns the ids of the snapshots that can be reverted to.
+    pub fn snapshots(&self) -> impl Iterator<Item = u64> + '_ {
+        self.snapshots.snapshots.keys().copied()
+    }
+
+    /// Removes all snapshots and the undo log, keeping the current state.
+    pub fn clear_snapshots(&mut self) {
+        self.snapshots.snapshots.clear();
+        self.snapshots.journal.clear();
+    }
 }
 
 impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
     /// Returns the account for the given address.
     ///
     /// If the account was not found in the cache, it will be loaded from the underlying database.
+    ///
+    /// The account can be changed freely, so the whole account is recorded while a snapshot is
+    /// active. Use [CacheDB::load_account_mut] to record only the changed info and storage slots.
     pub fn load_account(&mut self, address: Address) -> Result<&mut DbAccount, ExtDB::Error> {
+        self.load_account_unrecorded(address)?;
+        if self.snapshots.is_active() {
+            let previous = self.cache.accounts.get(&address).cloned();
+            self.snapshots.record_replaced(address, previous);
+        }
+        self.load_account_unrecorded(address)
+    }
+
+    /// Returns the account for the given address, loading it like [CacheDB::load_account].
+    ///
+    /// Account is changed through the [DbAccountMut] methods, so only the changed info and
+    /// storage slots are recorded for the active snapshots.
+    pub fn load_account_mut(&mut self, address: Address) -> Result<DbAccountMut<'_>, ExtDB::Error> {
+        self.load_account_unrecorded(address)?;
+        let account = self
+            .cache
+            .accounts
+            .get_mut(&address)
+            .expect("account is loaded");
+        Ok(DbAccountMut {
+            address,
+            account,
+            snapshots: &mut self.snapshots,
+            info_recorded: false,
+        })
+    }
+
+    /// Returns the account, loading it from the database without recording it for the snapshots.
+    ///
+    /// Loading does not change the state, the loaded values are the ones at the time of the
+    /// snapshot.
+    fn load_account_unrecorded(
+        &mut self,
+        address: Address,
+    ) -> Result<&mut DbAccount, ExtDB::Error> {
         let db = &self.db;
         match self.cache.accounts.entry(address) {
             Entry::Occupied(entry) => Ok(entry.into_mut()),
@@ -174,7 +271,9 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
         slot: StorageKey,
         value: StorageValue,
     ) -> Result<(), ExtDB::Error> {
-        let account = self.load_account(address)?;
+        self.load_account_unrecorded(address)?;
+        self.snapshots.record_slot(&self.cache, address, slot);
+        let account = self.load_account_unrecorded(address)?;
         account.storage.insert(slot, value);
         Ok(())
     }
@@ -185,8 +284,11 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
         address: Address,
         storage: HashMap<StorageKey, StorageValue>,
     ) -> Result<(), ExtDB::Error> {
-        let account = self.load_account(address)?;
+        self.load_account_unrecorded(address)?;
+        self.snapshots.record_info(&self.cache, address);
+        let account = self.cache.accounts.entry(address).or_default();
         account.account_state = AccountState::StorageCleared;
+        self.snapshots.clear_storage(address, &mut account.storage);
         account.storage = storage.into_iter().collect();
         Ok(())
     }
@@ -285,9 +387,11 @@ impl<ExtDB> DatabaseCommit for CacheDB<ExtDB> {
             if !account.is_touched() {
                 continue;
             }
+            self.snapshots.record_info(&self.cache, address);
             if account.is_selfdestructed() {
                 let db_account = self.cache.accounts.entry(address).or_default();
-                db_account.storage.clear();
+                self.snapshots
+                    .clear_storage(address, &mut db_account.storage);
                 db_account.account_state = AccountState::NotExisting;
                 db_account.info = AccountInfo::default();
                 continue;
@@ -299,7 +403,8 @@ impl<ExtDB> DatabaseCommit for CacheDB<ExtDB> {
             db_account.info = account.info;
 
             db_account.account_state = if is_newly_created {
-                db_account.storage.clear();
+                self.snapshots
+                    .clear_storage(address, &mut db_account.storage);
                 AccountState::StorageCleared
             } else if db_account.account_state.is_storage_cleared() {
                 // Preserve old account state if it already exists
@@ -307,6 +412,12 @@ impl<ExtDB> DatabaseCommit for CacheDB<ExtDB> {
             } else {
                 AccountState::Touched
             };
+            if self.snapshots.is_active() {
+                for key in account.storage.keys() {
+                    self.snapshots
+                        .record_slot_of(address, *key, &db_account.storage);
+                }
+            }
             db_account.storage.extend(
                 account
                     .storage
@@ -533,6 +644,209 @@ impl AccountState {
     }
 }
 
+/// Mutable access to a [DbAccount] of the [CacheDB], returned by [CacheDB::load_account_mut].
+///
+/// Changes are recorded in the undo log of the active snapshots: the account info and state are
+/// recorded on the first change, storage slots are recorded one by one.
+#[derive(Debug)]
+pub struct DbAccountMut<'a> {
+    address: Address,
+    account: &'a mut DbAccount,
+    snapshots: &'a mut CacheSnapshots,
+    /// Whether the info and state were recorded.
+    info_recorded: bool,
+}
+
+impl DbAccountMut<'_> {
+    /// Returns the mutable account info.
+    pub fn info_mut(&mut self) -> &mut AccountInfo {
+        self.record_info();
+        &mut self.account.info
+    }
+
+    /// Updates the account information.
+    pub fn update_info(&mut self, info: AccountInfo) {
+        *self.info_mut() = info;
+    }
+
+    /// Updates the account state.
+    pub fn update_account_state(&mut self, account_state: AccountState) {
+        self.record_info();
+        self.account.account_state = account_state;
+    }
+
+    /// Inserts the storage slot, returns the previous cached value.
+    pub fn insert_storage(&mut self, key: StorageKey, value: StorageValue) -> Option<StorageValue> {
+        self.snapshots
+            .record_slot_of(self.address, key, &self.account.storage);
+        self.account.storage.insert(key, value)
+    }
+
+    /// Removes the storage slot from the cache, returns the removed value.
+    pub fn remove_storage(&mut self, key: StorageKey) -> Option<StorageValue> {
+        self.snapshots
+            .record_slot_of(self.address, key, &self.account.storage);
+        self.account.storage.remove(&key)
+    }
+
+    /// Clears the cached storage, the account state is not changed.
+    pub fn clear_storage(&mut self) {
+        self.snapshots
+            .clear_storage(self.address, &mut self.account.storage);
+    }
+
+    fn record_info(&mut self) {
+        if !core::mem::replace(&mut self.info_recorded, true) {
+            self.snapshots
+                .record_info_of(self.address, Some(self.account));
+        }
+    }
+}
+
+impl core::ops::Deref for DbAccountMut<'_> {
+    type Target = DbAccount;
+
+    fn deref(&self) -> &Self::Target {
+        self.account
+    }
+}
+
+/// Undo log of the [CacheDB] snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct CacheSnapshots {
+    /// Changes since the oldest active snapshot.
+    journal: Vec<CacheUndo>,
+    /// Journal and logs lengths keyed by the snapshot id.
+    snapshots: BTreeMap<u64, (usize, usize)>,
+    /// Id of the next snapshot.
+    next_id: u64,
+}
+
+/// Value of the [Cache] before a change, `None` if it was not cached.
+#[derive(Debug, Clone)]
+enum CacheUndo {
+    /// Whole account that was replaced.
+    Account {
+        address: Address,
+        previous: Option<DbAccount>,
+    },
+    /// Account info and state.
+    Info {
+        address: Address,
+        previous: Option<(AccountInfo, AccountState)>,
+    },
+    /// Storage slot.
+    Slot {
+        address: Address,
+        key: StorageKey,
+        previous: Option<StorageValue>,
+    },
+    /// Storage that was cleared.
+    Storage {
+        address: Address,
+        previous: HashMap<StorageKey, StorageValue>,
+    },
+}
+
+impl CacheSnapshots {
+    fn is_active(&self) -> bool {
+        !self.snapshots.is_empty()
+    }
+
+    /// Records the account that was replaced, the account is moved to the journal.
+    fn record_replaced(&mut self, address: Address, previous: Option<DbAccount>) {
+        if self.is_active() {
+            self.journal.push(CacheUndo::Account { address, previous });
+        }
+    }
+
+    fn record_info(&mut self, cache: &Cache, address: Address) {
+        self.record_info_of(address, cache.accounts.get(&address));
+    }
+
+    fn record_info_of(&mut self, address: Address, account: Option<&DbAccount>) {
+        if self.is_active() {
+            let previous =
+                account.map(|account| (account.info.clone(), account.account_state.clone()));
+            self.journal.push(CacheUndo::Info { address, previous });
+        }
+    }
+
+    fn record_slot(&mut self, cache: &Cache, address: Address, key: StorageKey) {
+        if let Some(account) = cache.accounts.get(&address) {
+            self.record_slot_of(address, key, &account.storage);
+        }
+    }
+
+    fn record_slot_of(
+        &mut self,
+        address: Address,
+        key: StorageKey,
+        storage: &HashMap<StorageKey, StorageValue>,
+    ) {
+        if self.is_active() {
+            let previous = storage.get(&key).copied();
+            self.journal.push(CacheUndo::Slot {
+                address,
+                key,
+                previous,
+            });
+        }
+    }
+
+    /// Clears the storage, moving it to the journal if a snapshot is active.
+    fn clear_storage(&mut self, address: Address, storage: &mut HashMap<StorageKey, StorageValue>) {
+        if self.is_active() {
+            let previous = core::mem::take(storage);
+            self.journal.push(CacheUndo::Storage { address, previous });
+        } else {
+            storage.clear();
+        }
+    }
+}
+
+impl CacheUndo {
+    fn apply(self, cache: &mut Cache) {
+        match self {
+            Self::Account { address, previous } => match previous {
+                Some(account) => {
+                    cache.accounts.insert(address, account);
+                }
+                None => {
+                    cache.accounts.remove(&address);
+                }
+            },
+            Self::Info { address, previous } => match previous {
+                Some((info, account_state)) => {
+                    let account = cache.accounts.entry(address).or_default();
+                    account.info = info;
+                    account.account_state = account_state;
+                }
+                None => {
+                    cache.accounts.remove(&address);
+                }
+            },
+            Self::Slot {
+                address,
+                key,
+                previous,
+            } => {
+                if let Some(account) = cache.accounts.get_mut(&address) {
+                    match previous {
+                        Some(value) => account.storage.insert(key, value),
+                        None => account.storage.remove(&key),
+                    };
+                }
+            }
+            Self::Storage { address, previous } => {
+                if let Some(account) = cache.accounts.get_mut(&address) {
+                    account.storage = previous;
+                }
+            }
+        }
+    }
+}
+
 /// Custom benchmarking DB that only has account info for the zero address.
 ///
 /// Any other address will return an empty account.
@@ -598,9 +912,9 @@ impl Database for BenchmarkDB {
 
 #[cfg(test)]
 mod tests {
-    use super::{CacheDB, EmptyDB};
+    use super::{AccountState, CacheDB, EmptyDB};
     use database_interface::Database;
-    use primitives::{Address, HashMap, StorageKey, StorageValue};
+    use primitives::{Address, HashMap, StorageKey, StorageValue, U256};
     use state::AccountInfo;
 
     #[test]
@@ -727,4 +1041,103 @@ mod tests {
             nonce
         );
     }
+
+    #[test]
+    fn test_snapshot_revert() {
+        use database_interface::DatabaseCommit;
+        use state::{Account, EvmStorageSlot};
+
+        let account = Address::with_last_byte(42);
+        let other = Address::with_last_byte(43);
+        let (key, other_key) = (StorageKey::from(1), StorageKey::from(2));
+        let commit = |db: &mut CacheDB<EmptyDB>, nonce: u64, value: u64, selfdestruct: bool| {
+            let mut changed = Account::from(AccountInfo {
+                nonce,
+                ..Default::default()
+            })
+            .with_storage(
+                [(
+                    key,
+                    EvmStorageSlot::new_changed(StorageValue::ZERO, StorageValue::from(value), 0),
+                )]
+                .into_iter(),
+            );
+            changed.mark_touch();
+            if selfdestruct {
+                changed.mark_selfdestruct();
+            }
+            db.commit(HashMap::from_iter([(account, changed)]));
+        };
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(account, AccountInfo::default());
+        db.insert_account_storage(account, other_key, StorageValue::from(7))
+            .unwrap();
+        let first = db.snapshot();
+        commit(&mut db, 1, 10, false);
+        let second = db.snapshot();
+        commit(&mut db, 2, 20, false);
+        db.insert_account_info(other, AccountInfo::default());
+        let third = db.snapshot();
+        commit(&mut db, 3, 0, true);
+        assert_eq!(db.basic(account).unwrap(), None);
+        assert_eq!(db.snapshots().collect::<Vec<_>>(), [first, second, third]);
+
+        // revert the middle snapshot first, the later one is removed.
+        assert!(db.revert_to_snapshot(second));
+        assert!(!db.revert_to_snapshot(third));
+        assert_eq!(db.basic(account).unwrap().unwrap().nonce, 1);
+        assert_eq!(db.storage(account, key), Ok(StorageValue::from(10)));
+        assert_eq!(db.storage(account, other_key), Ok(StorageValue::from(7)));
+        assert!(!db.cache.accounts.contains_key(&other));
+
+        commit(&mut db, 4, 40, false);
+        assert!(db.revert_to_snapshot(first));
+        assert_eq!(db.basic(account).unwrap().unwrap().nonce, 0);
+        assert_eq!(db.storage(account, key), Ok(StorageValue::ZERO));
+        assert_eq!(db.storage(account, other_key), Ok(StorageValue::from(7)));
+        assert_eq!(db.snapshots().count(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_load_account() {
+        let account = Address::with_last_byte(42);
+        let (key, other_key) = (StorageKey::from(1), StorageKey::from(2));
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(account, AccountInfo::default());
+        db.insert_account_storage(account, key, StorageValue::from(1))
+            .unwrap();
+        db.insert_account_storage(account, other_key, StorageValue::from(2))
+            .unwrap();
+
+        let snapshot = db.snapshot();
+        let mut loaded = db.load_account_mut(account).unwrap();
+        loaded.info_mut().nonce = 1;
+        loaded.info_mut().balance = U256::from(10);
+        loaded.insert_storage(key, StorageValue::from(10));
+        loaded.update_account_state(AccountState::Touched);
+        // only the info and the changed slot are recorded.
+        assert_eq!(db.snapshots.journal.len(), 2);
+
+        assert!(db.revert_to_snapshot(snapshot));
+        let cached = &db.cache.accounts[&account];
+        assert_eq!(cached.info, AccountInfo::default());
+        assert_eq!(cached.account_state, AccountState::None);
+        assert_eq!(cached.storage.get(&key), Some(&StorageValue::from(1)));
+        assert_eq!(cached.storage.get(&other_key), Some(&StorageValue::from(2)));
+
+        // direct changes to the loaded account are reverted with the whole account.
+        let snapshot = db.snapshot();
+        let loaded = db.load_account(account).unwrap();
+        loaded.info.nonce = 2;
+        loaded.storage.insert(other_key, StorageValue::from(20));
+        loaded.storage.remove(&key);
+        assert_eq!(db.snapshots.journal.len(), 1);
+
+        assert!(db.revert_to_snapshot(snapshot));
+        let cached = &db.cache.accounts[&account];
+        assert_eq!(cached.info, AccountInfo::default());
+        assert_eq!(cached.storage.get(&key), Some(&StorageValue::from(1)));
+        assert_eq!(cached.storage.get(&other_key), Some(&StorageValue::from(2)));
+    }
 }
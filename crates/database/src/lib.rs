@@ -6,20 +6,34 @@ extern crate alloc as std;
 
 #[cfg(feature = "alloydb")]
 mod alloydb;
+#[cfg(feature = "alloydb")]
+mod fork_cache;
+#[cfg(feature = "alloydb")]
+mod prefetch;
 
 pub use database_interface::*;
 
+/// Genesis alloc import and state dump.
+#[cfg(feature = "serde")]
+pub mod genesis;
 /// In-memory database implementations.
 pub mod in_memory_db;
 /// State management and tracking.
 pub mod states;
+/// Execution witness recording and stateless execution.
+pub mod witness;
 
 #[cfg(feature = "alloydb")]
 pub use alloydb::{AlloyDB, AlloyDBError, BlockId};
+#[cfg(feature = "alloydb")]
+pub use fork_cache::{CachedAlloyDB, ForkCache};
+#[cfg(feature = "alloydb")]
+pub use prefetch::PrefetchHints;
 
 pub use in_memory_db::*;
 pub use states::{
-    AccountRevert, AccountStatus, BundleAccount, BundleState, CacheState, DBBox,
-    OriginalValuesKnown, PlainAccount, RevertToSlot, State, StateBuilder, StateDBBox,
-    StorageWithOriginalValues, TransitionAccount, TransitionState,
+    AccountRevert, AccountStatus, BundleAccount, BundleState, CacheEviction, CacheState, DBBox,
+    EvictionPolicy, OriginalValuesKnown, PlainAccount, RevertToSlot, State, StateBuilder,
+    StateDBBox, StorageWithOriginalValues, TransitionAccount, TransitionState,
 };
+pub use witness::{RecordingDb, Witness, WitnessAccount, WitnessDb, WitnessDbError, WitnessError};
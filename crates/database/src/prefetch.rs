@@ -0,0 +1,331 @@
+//! Prefetching of the state into [`CachedAlloyDB`] with batched requests.
+
+use crate::{alloydb::AlloyDBError, fork_cache::CachedAlloyDB, witness::Witness};
+use alloy_eips::{eip2930::AccessList, BlockNumberOrTag};
+use alloy_provider::{
+    network::{primitives::HeaderResponse, BlockResponse},
+    Network, Provider,
+};
+use alloy_rpc_client::BatchRequest;
+use primitives::{ruint::aliases::U64, Address, Bytes, StorageKey, B256, KECCAK_EMPTY, U256};
+use state::{bal::Bal, AccountInfo, Bytecode};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Accounts, storage slots and block hashes expected to be read by the execution.
+///
+/// Hints can be created from a transaction access list, a block access list or the [`Witness`]
+/// of a previous run, and combined with [`PrefetchHints::extend`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrefetchHints {
+    /// Accounts with the storage slots to fetch.
+    pub accounts: BTreeMap<Address, BTreeSet<StorageKey>>,
+    /// Numbers of the block hashes to fetch.
+    pub block_hashes: BTreeSet<u64>,
+}
+
+impl PrefetchHints {
+    /// Creates new empty hints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if there is nothing to fetch.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.block_hashes.is_empty()
+    }
+
+    /// Adds the account.
+    pub fn add_account(&mut self, address: Address) {
+        self.accounts.entry(address).or_default();
+    }
+
+    /// Adds the storage slot and its account.
+    pub fn add_storage(&mut self, address: Address, key: StorageKey) {
+        self.accounts.entry(address).or_default().insert(key);
+    }
+
+    /// Adds the block hash.
+    pub fn add_block_hash(&mut self, number: u64) {
+        self.block_hashes.insert(number);
+    }
+
+    /// Extends the hints with other hints.
+    pub fn extend(&mut self, other: PrefetchHints) {
+        for (address, keys) in other.accounts {
+            self.accounts.entry(address).or_default().extend(keys);
+        }
+        self.block_hashes.extend(other.block_hashes);
+    }
+}
+
+impl From<&AccessList> for PrefetchHints {
+    fn from(access_list: &AccessList) -> Self {
+        let mut hints = Self::new();
+        for item in access_list.iter() {
+            hints.add_account(item.address);
+            for key in &item.storage_keys {
+                hints.add_storage(item.address, (*key).into());
+            }
+        }
+        hints
+    }
+}
+
+impl From<&Bal> for PrefetchHints {
+    fn from(bal: &Bal) -> Self {
+        let mut hints = Self::new();
+        for (address, account) in &bal.accounts {
+            hints
+                .accounts
+                .entry(*address)
+                .or_default()
+                .extend(account.storage.storage.keys().copied());
+        }
+        hints
+    }
+}
+
+impl From<&Witness> for PrefetchHints {
+    fn from(witness: &Witness) -> Self {
+        let mut hints = Self::new();
+        for address in witness.accounts.keys() {
+            hints.add_account(*address);
+        }
+        for (address, storage) in &witness.storage {
+            hints
+                .accounts
+                .entry(*address)
+                .or_default()
+                .extend(storage.keys().copied());
+        }
+        hints
+            .block_hashes
+            .extend(witness.block_hashes.keys().copied());
+        hints
+    }
+}
+
+/// Response of `eth_getProof`, without the proofs.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountProof {
+    balance: U256,
+    nonce: U64,
+    code_hash: B256,
+    storage_proof: Vec<StorageProof>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StorageProof {
+    value: U256,
+}
+
+impl<N: Network, P: Provider<N>> CachedAlloyDB<N, P> {
+    /// Fetches the hinted state that is not cached yet before the execution.
+    ///
+    /// Accounts and their storage slots are fetched with one `eth_getProof` per account, followed
+    /// by `eth_getCode` for the new bytecodes and `eth_getBlockByNumber` for the block hashes.
+    /// Requests are sent as JSON-RPC batches, so every step costs one round-trip per
+    /// [batch](CachedAlloyDB::with_batch_size) instead of one per value.
+    pub async fn prefetch(&self, hints: &PrefetchHints) -> Result<(), AlloyDBError> {
+        let provider = self.db.provider();
+        let block_id = self.db.block_id();
+
+        // Accounts and slots that are not cached yet.
+        let requests: Vec<(Address, Vec<StorageKey>)> = {
+            let data = self.cache.read();
+            hints
+                .accounts
+                .iter()
+                .filter_map(|(address, keys)| {
+                    let keys: Vec<_> = keys
+                        .iter()
+                        .filter(|key| !data.storage.contains_key(&(*address, **key)))
+                        .copied()
+                        .collect();
+                    (!keys.is_empty() || !data.accounts.contains_key(address))
+                        .then_some((*address, keys))
+                })
+                .collect()
+        };
+
+        let mut accounts = Vec::new();
+        for chunk in requests.chunks(self.batch_size) {
+            let mut batch = BatchRequest::new(provider.client());
+            let waiters = chunk
+                .iter()
+                .map(|(address, keys)| {
+                    let keys: Vec<B256> = keys.iter().map(|key| B256::from(*key)).collect();
+                    batch.add_call::<_, AccountProof>("eth_getProof", &(address, keys, block_id))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            batch.send().await?;
+
+            for ((address, keys), waiter) in chunk.iter().zip(waiters) {
+                let proof = waiter.await?;
+                let mut data = self.cache.write();
+                for (key, slot) in keys.iter().zip(&proof.storage_proof) {
+                    data.storage.insert((*address, *key), slot.value);
+                }
+                if !data.accounts.contains_key(address) {
+                    accounts.push((*address, proof));
+                }
+            }
+        }
+
+        // Bytecodes that are not cached yet, nodes return a zero hash for missing accounts.
+        let codes: Vec<(Address, B256)> = {
+            let data = self.cache.read();
+            let mut seen = BTreeSet::new();
+            accounts
+                .iter()
+                .filter(|(_, proof)| {
+                    !matches!(proof.code_hash, KECCAK_EMPTY | B256::ZERO)
+                        && !data.contracts.contains_key(&proof.code_hash)
+                        && seen.insert(proof.code_hash)
+                })
+                .map(|(address, proof)| (*address, proof.code_hash))
+                .collect()
+        };
+        for chunk in codes.chunks(self.batch_size) {
+            let mut batch = BatchRequest::new(provider.client());
+            let waiters = chunk
+                .iter()
+                .map(|(address, _)| batch.add_call::<_, Bytes>("eth_getCode", &(address, block_id)))
+                .collect::<Result<Vec<_>, _>>()?;
+            batch.send().await?;
+
+            for ((_, code_hash), waiter) in chunk.iter().zip(waiters) {
+                let code = Bytecode::new_raw(waiter.await?);
+                self.cache.write().contracts.insert(*code_hash, code);
+            }
+        }
+
+        {
+            let mut data = self.cache.write();
+            for (address, proof) in accounts {
+                let info = if matches!(proof.code_hash, KECCAK_EMPTY | B256::ZERO) {
+                    AccountInfo::new(
+                        proof.balance,
+                        proof.nonce.to(),
+                        KECCAK_EMPTY,
+                        Bytecode::new(),
+                    )
+                } else if let Some(code) = data.contracts.get(&proof.code_hash) {
+                    AccountInfo::new(
+                        proof.balance,
+                        proof.nonce.to(),
+                        proof.code_hash,
+                        code.clone(),
+                    )
+                } else {
+                    // The cache was cleared after the code was fetched, the account is fetched
+                    // again on lookup.
+                    continue;
+                };
+                data.accounts.insert(address, Some(info));
+            }
+        }
+
+        // Block hashes that are not cached yet.
+        let numbers: Vec<u64> = {
+            let data = self.cache.read();
+            hints
+                .block_hashes
+                .iter()
+                .filter(|number| !data.block_hashes.contains_key(number))
+                .copied()
+                .collect()
+        };
+        for chunk in numbers.chunks(self.batch_size) {
+            let mut batch = BatchRequest::new(provider.client());
+            let waiters = chunk
+                .iter()
+                .map(|number| {
+                    batch.add_call::<_, Option<N::BlockResponse>>(
+                        "eth_getBlockByNumber",
+                        &(BlockNumberOrTag::Number(*number), false),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            batch.send().await?;
+
+            for (number, waiter) in chunk.iter().zip(waiters) {
+                let block = waiter.await?.ok_or(AlloyDBError::BlockNotFound(*number))?;
+                let hash = B256::new(*block.header().hash());
+                self.cache.write().block_hashes.insert(*number, hash);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fork_cache::tests::{block, mocked_provider},
+        BlockId,
+    };
+    use alloy_eips::eip2930::AccessListItem;
+    use database_interface::async_db::DatabaseAsyncRef;
+    use primitives::{address, b256, bytes, keccak256};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn prefetch_serves_lookups() {
+        let contract = address!("0x0d4a11d5EEaaC28EC3F61d100daF4d40471f1852");
+        let user = address!("0x1000000000000000000000000000000000000001");
+        let code = bytes!("0x6000");
+        let code_hash = keccak256(&code);
+
+        let (provider, asserter, _) = mocked_provider();
+        // pending block is only cached in memory.
+        let db = CachedAlloyDB::new(provider, BlockId::pending(), "")
+            .await
+            .unwrap();
+
+        let mut hints = PrefetchHints::from(&AccessList(vec![AccessListItem {
+            address: contract,
+            storage_keys: vec![B256::with_last_byte(1), B256::with_last_byte(2)],
+        }]));
+        hints.add_account(user);
+        hints.add_block_hash(15);
+
+        // first batch with the proofs.
+        asserter.push_success(&json!({
+            "balance": "0x0",
+            "nonce": "0x1",
+            "codeHash": code_hash,
+            "storageProof": [{ "value": "0x2a" }, { "value": "0x0" }],
+        }));
+        asserter.push_success(&json!({
+            "balance": "0x64",
+            "nonce": "0x0",
+            "codeHash": KECCAK_EMPTY,
+            "storageProof": [],
+        }));
+        // second batch with the code, third with the block hash.
+        asserter.push_success(&code);
+        let hash = b256!("0x2222222222222222222222222222222222222222222222222222222222222222");
+        asserter.push_success(&block(15, hash));
+        db.prefetch(&hints).await.unwrap();
+        assert!(asserter.read_q().is_empty());
+
+        // every lookup is served from the cache, the mock has no responses left.
+        let key = StorageKey::from(1);
+        assert_eq!(
+            db.storage_async_ref(contract, key).await.unwrap(),
+            U256::from(42)
+        );
+        let info = db.basic_async_ref(contract).await.unwrap().unwrap();
+        assert_eq!(info.code_hash, code_hash);
+        assert_eq!(info.code.unwrap().original_bytes(), code);
+        let info = db.basic_async_ref(user).await.unwrap().unwrap();
+        assert_eq!(info.balance, U256::from(100));
+        assert_eq!(db.block_hash_async_ref(15).await.unwrap(), hash);
+
+        // cached values are not fetched again.
+        db.prefetch(&hints).await.unwrap();
+    }
+}
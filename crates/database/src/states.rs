@@ -12,6 +12,8 @@ pub mod bundle_state;
 pub mod cache;
 /// Cache account representation.
 pub mod cache_account;
+/// Memory budget of the cache state.
+pub mod cache_eviction;
 /// State changeset tracking.
 pub mod changes;
 /// Plain account representation.
@@ -33,6 +35,7 @@ pub use bundle_account::BundleAccount;
 pub use bundle_state::{BundleBuilder, BundleState, OriginalValuesKnown};
 pub use cache::CacheState;
 pub use cache_account::CacheAccount;
+pub use cache_eviction::{CacheEviction, EvictionPolicy};
 pub use changes::{PlainStateReverts, PlainStorageChangeset, PlainStorageRevert, StateChangeset};
 pub use plain_account::{PlainAccount, StorageSlot, StorageWithOriginalValues};
 pub use reverts::{AccountRevert, RevertToSlot};
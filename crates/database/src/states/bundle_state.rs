@@ -376,7 +376,7 @@ impl BundleBuilder {
 }
 
 /// Bundle retention policy for applying substate to the bundle.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BundleRetention {
     /// Only plain state is updated.
     PlainState,
@@ -0,0 +1,188 @@
+use super::{BundleState, CacheAccount, CacheState, TransitionState};
+use core::mem::size_of;
+use primitives::{Address, AddressMap, HashSet, StorageKey, StorageValue};
+use std::vec::Vec;
+
+/// Estimated size of the cached account without its storage.
+const ACCOUNT_SIZE: usize = size_of::<(Address, CacheAccount)>();
+/// Estimated size of the cached storage slot.
+const SLOT_SIZE: usize = size_of::<(StorageKey, StorageValue)>();
+
+/// Policy that selects the cache entries evicted when [`State`](super::State) goes over its
+/// memory budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EvictionPolicy {
+    /// Evicts the least recently used unmodified accounts with their storage until the cache is
+    /// back under three quarters of the budget.
+    #[default]
+    Lru,
+    /// Evicts all unmodified accounts with their storage that are not referenced by the bundle or
+    /// the transition state.
+    BundleReferenced,
+}
+
+/// Memory budget of the [`CacheState`] inside [`State`](super::State).
+///
+/// The size of the cache is estimated from the number of accounts and storage slots, contracts
+/// and hash map overhead are not counted. Evicted entries are read again from the bundle or the
+/// database when needed, so eviction does not change the execution results.
+///
+/// Modified accounts are never evicted. Accounts inserted with [`State::insert_account`],
+/// [`State::insert_account_with_storage`] and [`State::insert_not_existing`] have no copy in the
+/// database and are never evicted either, accounts inserted directly into
+/// [`State::cache`] are not tracked.
+///
+/// [`State::insert_account`]: super::State::insert_account
+/// [`State::insert_account_with_storage`]: super::State::insert_account_with_storage
+/// [`State::insert_not_existing`]: super::State::insert_not_existing
+/// [`State::cache`]: super::State::cache
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheEviction {
+    budget: usize,
+    policy: EvictionPolicy,
+    /// Estimated size of the cache, exact after every eviction pass.
+    size: usize,
+    /// Estimated size that triggers the next eviction pass.
+    threshold: usize,
+    /// Last access of the cached accounts, only tracked by [`EvictionPolicy::Lru`].
+    last_used: AddressMap<u64>,
+    tick: u64,
+    /// Accounts that are not in the database and are never evicted.
+    pinned: HashSet<Address>,
+}
+
+impl CacheEviction {
+    /// Creates the budget for the given cache.
+    pub(crate) fn new(budget: usize, policy: EvictionPolicy, cache: &CacheState) -> Self {
+        let size = cache_size(cache);
+        Self {
+            budget,
+            policy,
+            size,
+            threshold: budget.max(size + budget / 4),
+            last_used: AddressMap::default(),
+            tick: 0,
+            pinned: HashSet::default(),
+        }
+    }
+
+    /// Returns the memory budget in bytes.
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Returns the eviction policy.
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// Returns the estimated size of the cache in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Records the access of the account that added the given number of storage slots.
+    pub(crate) fn touch(&mut self, address: Address, slots: usize) {
+        self.size += slots * SLOT_SIZE;
+        if self.policy == EvictionPolicy::Lru {
+            self.tick += 1;
+            self.last_used.insert(address, self.tick);
+        }
+    }
+
+    /// Records the newly loaded account.
+    pub(crate) fn insert(&mut self, address: Address, account: &CacheAccount) {
+        self.size += account_size(account);
+        self.touch(address, 0);
+    }
+
+    /// Records the account inserted into the cache that is never evicted.
+    ///
+    /// The replaced account, if any, has to be passed so its size is not counted twice.
+    pub(crate) fn pin(
+        &mut self,
+        address: Address,
+        account: &CacheAccount,
+        replaced: Option<&CacheAccount>,
+    ) {
+        self.size = self.size.saturating_sub(replaced.map_or(0, account_size));
+        self.insert(address, account);
+        self.pinned.insert(address);
+    }
+
+    /// Records the committed storage slots, the count is an upper bound of the new slots.
+    pub(crate) fn commit(&mut self, slots: usize) {
+        self.size += slots * SLOT_SIZE;
+    }
+
+    /// Returns `true` if the estimated size is over the threshold.
+    pub(crate) fn is_over_budget(&self) -> bool {
+        self.size > self.threshold
+    }
+
+    /// Evicts the cache entries selected by the policy.
+    pub(crate) fn evict(
+        &mut self,
+        cache: &mut CacheState,
+        bundle: &BundleState,
+        transitions: Option<&TransitionState>,
+    ) {
+        match self.policy {
+            EvictionPolicy::Lru => {
+                let target = self.budget / 4 * 3;
+                let mut size = cache_size(cache);
+                if size > target {
+                    let mut candidates: Vec<_> = cache
+                        .accounts
+                        .iter()
+                        .filter(|(address, account)| self.is_evictable(address, account))
+                        .map(|(address, account)| {
+                            let tick = self.last_used.get(address).copied().unwrap_or_default();
+                            (tick, *address, account_size(account))
+                        })
+                        .collect();
+                    candidates.sort_unstable();
+                    for (_, address, account_size) in candidates {
+                        if size <= target {
+                            break;
+                        }
+                        cache.accounts.remove(&address);
+                        size -= account_size;
+                    }
+                }
+                self.last_used
+                    .retain(|address, _| cache.accounts.contains_key(address));
+                self.size = size;
+            }
+            EvictionPolicy::BundleReferenced => {
+                cache.accounts.retain(|address, account| {
+                    !self.is_evictable(address, account)
+                        || bundle.state.contains_key(address)
+                        || transitions.is_some_and(|t| t.transitions.contains_key(address))
+                });
+                self.size = cache_size(cache);
+            }
+        }
+        self.threshold = self.budget.max(self.size + self.budget / 4);
+    }
+
+    /// Returns `true` if the account can be read again from the bundle or the database.
+    fn is_evictable(&self, address: &Address, account: &CacheAccount) -> bool {
+        account.status.is_not_modified() && !self.pinned.contains(address)
+    }
+}
+
+/// Returns the estimated size of the cached account with its storage.
+fn account_size(account: &CacheAccount) -> usize {
+    ACCOUNT_SIZE
+        + account
+            .account
+            .as_ref()
+            .map_or(0, |account| account.storage.len() * SLOT_SIZE)
+}
+
+/// Returns the estimated size of the cached accounts.
+fn cache_size(cache: &CacheState) -> usize {
+    cache.accounts.values().map(account_size).sum()
+}
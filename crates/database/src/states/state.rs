@@ -2,7 +2,7 @@ use crate::states::block_hash_cache::BlockHashCache;
 
 use super::{
     bundle_state::BundleRetention, cache::CacheState, plain_account::PlainStorage, BundleState,
-    CacheAccount, StateBuilder, TransitionAccount, TransitionState,
+    CacheAccount, CacheEviction, StateBuilder, TransitionAccount, TransitionState,
 };
 use bytecode::Bytecode;
 use database_interface::{
@@ -38,6 +38,11 @@ pub struct State<DB> {
     ///
     /// Additionally, we can introduce some preloading of data from database.
     pub cache: CacheState,
+    /// Memory budget of the cache
+    ///
+    /// If set, unmodified entries of the cache are evicted after the commit that took it
+    /// over the budget.
+    pub cache_eviction: Option<CacheEviction>,
     /// Optional database that we use to fetch data from
     ///
     /// If database is not present, we will return not existing account and storage.
@@ -95,24 +100,43 @@ impl<DB: Database> State<DB> {
     }
 
     /// Inserts a non-existing account into the state.
+    ///
+    /// Inserted accounts are never evicted from the cache.
     pub fn insert_not_existing(&mut self, address: Address) {
-        self.cache.insert_not_existing(address)
+        let replaced = self.cache.accounts.remove(&address);
+        self.cache.insert_not_existing(address);
+        self.pin_cache_account(address, replaced);
     }
 
     /// Inserts an account into the state.
+    ///
+    /// Inserted accounts are never evicted from the cache.
     pub fn insert_account(&mut self, address: Address, info: AccountInfo) {
-        self.cache.insert_account(address, info)
+        let replaced = self.cache.accounts.remove(&address);
+        self.cache.insert_account(address, info);
+        self.pin_cache_account(address, replaced);
     }
 
     /// Inserts an account with storage into the state.
+    ///
+    /// Inserted accounts are never evicted from the cache.
     pub fn insert_account_with_storage(
         &mut self,
         address: Address,
         info: AccountInfo,
         storage: PlainStorage,
     ) {
+        let replaced = self.cache.accounts.remove(&address);
         self.cache
-            .insert_account_with_storage(address, info, storage)
+            .insert_account_with_storage(address, info, storage);
+        self.pin_cache_account(address, replaced);
+    }
+
+    /// Records the inserted account in the [`CacheEviction`], it has no copy in the database.
+    fn pin_cache_account(&mut self, address: Address, replaced: Option<CacheAccount>) {
+        if let Some(eviction) = self.cache_eviction.as_mut() {
+            eviction.pin(address, &self.cache.accounts[&address], replaced.as_ref());
+        }
     }
 
     /// Applies evm transitions to transition state.
@@ -145,6 +169,7 @@ impl<DB: Database> State<DB> {
     pub fn load_cache_account(&mut self, address: Address) -> Result<&mut CacheAccount, DB::Error> {
         Self::load_cache_account_with(
             &mut self.cache,
+            &mut self.cache_eviction,
             self.use_preloaded_bundle,
             &self.bundle_state,
             &mut self.database,
@@ -161,6 +186,7 @@ impl<DB: Database> State<DB> {
     /// returns a cached account with the lifetime of the provided cache reference.
     fn load_cache_account_with<'a>(
         cache: &'a mut CacheState,
+        cache_eviction: &mut Option<CacheEviction>,
         use_preloaded_bundle: bool,
         bundle_state: &BundleState,
         database: &mut DB,
@@ -171,6 +197,9 @@ impl<DB: Database> State<DB> {
                 if use_preloaded_bundle {
                     // Load account from bundle state
                     if let Some(account) = bundle_state.account(&address).map(Into::into) {
+                        if let Some(eviction) = cache_eviction {
+                            eviction.insert(address, &account);
+                        }
                         return Ok(entry.insert(account));
                     }
                 }
@@ -183,9 +212,17 @@ impl<DB: Database> State<DB> {
                     }
                     Some(acc) => CacheAccount::new_loaded(acc, HashMap::default()),
                 };
+                if let Some(eviction) = cache_eviction {
+                    eviction.insert(address, &account);
+                }
                 entry.insert(account)
             }
-            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Occupied(entry) => {
+                if let Some(eviction) = cache_eviction {
+                    eviction.touch(address, 0);
+                }
+                entry.into_mut()
+            }
         })
     }
 
@@ -240,12 +277,38 @@ impl<DB: Database> State<DB> {
         self.bal_state.bal = bal;
     }
 
+    /// Evicts the cache entries selected by the policy of the [`CacheEviction`].
+    ///
+    /// Eviction runs after the commits that take the cache over its budget, this forces it.
+    /// Does nothing if the state has no memory budget.
+    pub fn evict_cache(&mut self) {
+        if let Some(eviction) = self.cache_eviction.as_mut() {
+            eviction.evict(
+                &mut self.cache,
+                &self.bundle_state,
+                self.transition_state.as_ref(),
+            );
+        }
+    }
+
+    /// Evicts the cache entries if the cache is over its budget.
+    fn evict_cache_if_needed(&mut self) {
+        if self
+            .cache_eviction
+            .as_ref()
+            .is_some_and(CacheEviction::is_over_budget)
+        {
+            self.evict_cache();
+        }
+    }
+
     /// Gets storage value of address at index.
     #[inline]
     fn storage(&mut self, address: Address, index: StorageKey) -> Result<StorageValue, DB::Error> {
         // If account is not found in cache, it will be loaded from database.
         let account = Self::load_cache_account_with(
             &mut self.cache,
+            &mut self.cache_eviction,
             self.use_preloaded_bundle,
             &self.bundle_state,
             &mut self.database,
@@ -267,6 +330,9 @@ impl<DB: Database> State<DB> {
                     } else {
                         self.database.storage(address, index)?
                     };
+                    if let Some(eviction) = self.cache_eviction.as_mut() {
+                        eviction.touch(address, 1);
+                    }
                     entry.insert(value);
                     Ok(value)
                 }
@@ -374,6 +440,9 @@ impl<DB: Database> Database for State<DB> {
 impl<DB: Database> DatabaseCommit for State<DB> {
     fn commit(&mut self, changes: HashMap<Address, Account>) {
         self.bal_state.commit(&changes);
+        if let Some(eviction) = self.cache_eviction.as_mut() {
+            eviction.commit(changes.values().map(|account| account.storage.len()).sum());
+        }
         let transitions = self.cache.apply_evm_state_iter(changes, |_, _| {});
         if let Some(s) = self.transition_state.as_mut() {
             s.add_transitions(transitions)
@@ -381,12 +450,15 @@ impl<DB: Database> DatabaseCommit for State<DB> {
             // Advance the iter to apply all state updates.
             transitions.for_each(|_| {});
         }
+        self.evict_cache_if_needed();
     }
 
     fn commit_iter(&mut self, changes: &mut dyn Iterator<Item = (Address, Account)>) {
+        let mut slots = 0;
         let transitions = self
             .cache
             .apply_evm_state_iter(changes, |address, account| {
+                slots += account.storage.len();
                 self.bal_state.commit_one(*address, account);
             });
         if let Some(s) = self.transition_state.as_mut() {
@@ -395,6 +467,10 @@ impl<DB: Database> DatabaseCommit for State<DB> {
             // Advance the iter to apply all state updates.
             transitions.for_each(|_| {});
         }
+        if let Some(eviction) = self.cache_eviction.as_mut() {
+            eviction.commit(slots);
+        }
+        self.evict_cache_if_needed();
     }
 }
 
@@ -501,7 +577,7 @@ mod tests {
     use super::*;
     use crate::{
         states::{reverts::AccountInfoRevert, StorageSlot},
-        AccountRevert, AccountStatus, BundleAccount, RevertToSlot,
+        AccountRevert, AccountStatus, BundleAccount, EvictionPolicy, RevertToSlot,
     };
     use primitives::{keccak256, BLOCK_HASH_HISTORY, U256};
     #[test]
@@ -1004,4 +1080,172 @@ mod tests {
             )])])
         )
     }
+
+    #[test]
+    fn cache_eviction_lru() {
+        let mut db = crate::InMemoryDB::default();
+        let addresses: Vec<Address> = (1..=8).map(Address::with_last_byte).collect();
+        for (i, address) in addresses.iter().enumerate() {
+            let value = U256::from(i + 1);
+            db.insert_account_info(
+                *address,
+                AccountInfo {
+                    balance: value,
+                    ..Default::default()
+                },
+            );
+            db.insert_account_storage(*address, StorageKey::from(1), value)
+                .unwrap();
+        }
+
+        // every account with its slot has the same size, keep three of them.
+        let load = |budget| {
+            let mut state = State::builder()
+                .with_database(db.clone())
+                .with_cache_budget(budget, EvictionPolicy::Lru)
+                .build();
+            for address in &addresses {
+                state.basic(*address).unwrap();
+                Database::storage(&mut state, *address, StorageKey::from(1)).unwrap();
+            }
+            state
+        };
+        let size = load(usize::MAX).cache_eviction.unwrap().size();
+        let mut state = load(size / 2);
+
+        // modify the least recently used account.
+        let mut account = Account::from(state.basic(addresses[0]).unwrap().unwrap());
+        account.info.balance = U256::from(100);
+        account.mark_touch();
+        state.commit(HashMap::from_iter([(addresses[0], account)]));
+
+        let mut cached: Vec<_> = state.cache.accounts.keys().copied().collect();
+        cached.sort();
+        assert_eq!(cached, [addresses[0], addresses[6], addresses[7]]);
+
+        // evicted accounts are read again from the database.
+        for (i, address) in addresses.iter().enumerate().skip(1) {
+            let value = U256::from(i + 1);
+            assert_eq!(state.basic(*address).unwrap().unwrap().balance, value);
+            assert_eq!(
+                Database::storage(&mut state, *address, StorageKey::from(1)).unwrap(),
+                value
+            );
+        }
+        assert_eq!(
+            state.basic(addresses[0]).unwrap().unwrap().balance,
+            U256::from(100)
+        );
+    }
+
+    #[test]
+    fn cache_eviction_bundle_referenced() {
+        use state::EvmStorageSlot;
+
+        let (modified, loaded, inserted) = (
+            Address::with_last_byte(1),
+            Address::with_last_byte(2),
+            Address::with_last_byte(3),
+        );
+        let key = StorageKey::from(1);
+        let mut db = crate::InMemoryDB::default();
+        for address in [modified, loaded] {
+            db.insert_account_info(
+                address,
+                AccountInfo {
+                    balance: U256::from(1),
+                    ..Default::default()
+                },
+            );
+            db.insert_account_storage(address, key, U256::from(1))
+                .unwrap();
+        }
+
+        // reads the account and increments its balance and storage slot.
+        let increment = |state: &mut State<crate::InMemoryDB>, address: Address| {
+            let info = state.basic(address).unwrap().unwrap_or_default();
+            let value = Database::storage(state, address, key).unwrap();
+            let mut account = Account::from(info);
+            account.info.balance += U256::from(1);
+            account.storage.insert(
+                key,
+                EvmStorageSlot::new_changed(value, value + U256::from(1), 0),
+            );
+            account.mark_touch();
+            state.commit(HashMap::from_iter([(address, account)]));
+        };
+
+        for bundle_update in [false, true] {
+            let mut builder = State::builder()
+                .with_database(db.clone())
+                .with_cache_budget(usize::MAX, EvictionPolicy::BundleReferenced);
+            if bundle_update {
+                builder = builder.with_bundle_update();
+            }
+            let mut state = builder.build();
+            state.insert_account_with_storage(
+                inserted,
+                AccountInfo {
+                    balance: U256::from(5),
+                    ..Default::default()
+                },
+                HashMap::from_iter([(key, U256::from(5))]),
+            );
+            state.basic(loaded).unwrap();
+            increment(&mut state, modified);
+            state.merge_transitions(BundleRetention::Reverts);
+            let bundle = state.take_bundle();
+            assert_eq!(bundle.state.contains_key(&modified), bundle_update);
+
+            // database is not updated with the bundle.
+            state.evict_cache();
+            let mut cached: Vec<_> = state.cache.accounts.keys().copied().collect();
+            cached.sort();
+            assert_eq!(cached, [modified, inserted]);
+
+            increment(&mut state, modified);
+            increment(&mut state, inserted);
+            state.evict_cache();
+            assert_eq!(
+                state.basic(modified).unwrap().unwrap().balance,
+                U256::from(3)
+            );
+            assert_eq!(
+                Database::storage(&mut state, modified, key).unwrap(),
+                U256::from(3)
+            );
+            assert_eq!(
+                state.basic(inserted).unwrap().unwrap().balance,
+                U256::from(6)
+            );
+            assert_eq!(
+                Database::storage(&mut state, inserted, key).unwrap(),
+                U256::from(6)
+            );
+            assert_eq!(state.basic(loaded).unwrap().unwrap().balance, U256::from(1));
+        }
+    }
+
+    #[test]
+    fn cache_eviction_keeps_inserted_accounts() {
+        let address = Address::with_last_byte(1);
+        let mut state = State::builder()
+            .with_cache_budget(0, EvictionPolicy::Lru)
+            .build();
+        state.insert_account(
+            address,
+            AccountInfo {
+                balance: U256::from(1),
+                ..Default::default()
+            },
+        );
+        state.basic(Address::with_last_byte(2)).unwrap();
+        state.evict_cache();
+
+        assert_eq!(state.cache.accounts.len(), 1);
+        assert_eq!(
+            state.basic(address).unwrap().unwrap().balance,
+            U256::from(1)
+        );
+    }
 }
@@ -1,6 +1,9 @@
 use crate::states::block_hash_cache::BlockHashCache;
 
-use super::{cache::CacheState, state::DBBox, BundleState, State, TransitionState};
+use super::{
+    cache::CacheState, state::DBBox, BundleState, CacheEviction, EvictionPolicy, State,
+    TransitionState,
+};
 use database_interface::{
     bal::BalState, DBErrorMarker, Database, DatabaseRef, EmptyDB, WrapDatabaseRef,
 };
@@ -33,6 +36,10 @@ pub struct StateBuilder<DB> {
     with_background_transition_merge: bool,
     /// If we want to set different block hashes,
     with_block_hashes: BlockHashCache,
+    /// Memory budget of the cache in bytes and the policy that evicts entries over it.
+    ///
+    /// Default is unbounded cache.
+    with_cache_budget: Option<(usize, EvictionPolicy)>,
     /// BAL state.
     bal_state: BalState,
 }
@@ -64,6 +71,7 @@ impl<DB: Database> StateBuilder<DB> {
             with_bundle_update: false,
             with_background_transition_merge: false,
             with_block_hashes: BlockHashCache::new(),
+            with_cache_budget: None,
             bal_state: BalState::default(),
         }
     }
@@ -80,6 +88,7 @@ impl<DB: Database> StateBuilder<DB> {
             with_bundle_update: self.with_bundle_update,
             with_background_transition_merge: self.with_background_transition_merge,
             with_block_hashes: self.with_block_hashes,
+            with_cache_budget: self.with_cache_budget,
             bal_state: self.bal_state,
         }
     }
@@ -166,6 +175,18 @@ impl<DB: Database> StateBuilder<DB> {
         }
     }
 
+    /// Bounds the memory used by the cache.
+    ///
+    /// When the estimated size of the cache goes over the budget after a commit, unmodified
+    /// entries are evicted by the given policy and read again from the database when needed.
+    /// See [`CacheEviction`] for more info.
+    pub fn with_cache_budget(self, budget: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            with_cache_budget: Some((budget, policy)),
+            ..self
+        }
+    }
+
     /// With BAL.
     pub fn with_bal(mut self, bal: Arc<Bal>) -> Self {
         self.bal_state.bal = Some(bal);
@@ -186,10 +207,14 @@ impl<DB: Database> StateBuilder<DB> {
         } else {
             self.with_bundle_prestate.is_some()
         };
+        let cache = self
+            .with_cache_prestate
+            .unwrap_or_else(|| CacheState::new(self.with_state_clear));
         State {
-            cache: self
-                .with_cache_prestate
-                .unwrap_or_else(|| CacheState::new(self.with_state_clear)),
+            cache_eviction: self
+                .with_cache_budget
+                .map(|(budget, policy)| CacheEviction::new(budget, policy, &cache)),
+            cache,
             database: self.database,
             transition_state: self.with_bundle_update.then(TransitionState::default),
             bundle_state: self.with_bundle_prestate.unwrap_or_default(),
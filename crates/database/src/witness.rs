@@ -0,0 +1,651 @@
+//! Execution witness recording and stateless execution.
+//!
+//! [`RecordingDb`] wraps a database and records every value read from it into a [`Witness`].
+//! [`WitnessDb`] serves execution only from the witness and fails on any missing key, so a
+//! block can be re-executed without access to the full state.
+//!
+//! Trie nodes that prove the recorded values are added by the owner of the state trie, see
+//! [`Witness::insert_node`], and the values are checked against the pre-state root with
+//! [`Witness::verify`].
+use alloy_rlp::{Decodable, Header, EMPTY_STRING_CODE};
+use core::{error::Error, fmt};
+use database_interface::{DBErrorMarker, Database, DatabaseCommit, DatabaseRef};
+use primitives::{
+    keccak256, Address, Bytes, HashMap, StorageKey, StorageValue, B256, KECCAK_EMPTY, U256,
+};
+use state::{Account, AccountInfo, Bytecode};
+use std::{collections::BTreeMap, vec::Vec};
+
+/// Root of the empty trie, `keccak256(rlp(""))`.
+const EMPTY_ROOT_HASH: B256 =
+    primitives::b256!("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// Account fields read from the database, code is stored in [`Witness::bytecodes`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WitnessAccount {
+    /// Account balance.
+    pub balance: U256,
+    /// Account nonce.
+    pub nonce: u64,
+    /// Hash of the account code.
+    pub code_hash: B256,
+}
+
+impl From<&AccountInfo> for WitnessAccount {
+    fn from(info: &AccountInfo) -> Self {
+        Self {
+            balance: info.balance,
+            nonce: info.nonce,
+            code_hash: info.code_hash,
+        }
+    }
+}
+
+/// Pre-state read during the execution.
+///
+/// Contains every account, storage slot, bytecode and block hash that was read from the
+/// database, with the value it had before the execution.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Witness {
+    /// Accounts, `None` if the account does not exist.
+    pub accounts: BTreeMap<Address, Option<WitnessAccount>>,
+    /// Storage slots of the accounts.
+    pub storage: BTreeMap<Address, BTreeMap<StorageKey, StorageValue>>,
+    /// Bytecodes by their hash.
+    pub bytecodes: BTreeMap<B256, Bytecode>,
+    /// Block hashes by block number.
+    pub block_hashes: BTreeMap<u64, B256>,
+    /// RLP encoded nodes of the state and storage tries by their hash.
+    ///
+    /// Nodes prove the accounts and storage slots against the pre-state root.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub nodes: BTreeMap<B256, Bytes>,
+}
+
+impl Witness {
+    /// Creates new empty witness.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if nothing was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+            && self.storage.is_empty()
+            && self.bytecodes.is_empty()
+            && self.block_hashes.is_empty()
+            && self.nodes.is_empty()
+    }
+
+    /// Inserts the RLP encoded trie node.
+    pub fn insert_node(&mut self, node: Bytes) {
+        self.nodes.entry(keccak256(&node)).or_insert(node);
+    }
+
+    /// Verifies the witness against the pre-state root.
+    ///
+    /// Every account, including the missing ones, and every storage slot has to be proven by the
+    /// [trie nodes](Witness::nodes), and every bytecode has to match its hash. Block hashes are
+    /// not part of the state and are not verified.
+    pub fn verify(&self, state_root: B256) -> Result<(), WitnessError> {
+        for (hash, bytecode) in &self.bytecodes {
+            if keccak256(bytecode.original_byte_slice()) != *hash {
+                return Err(WitnessError::BytecodeMismatch(*hash));
+            }
+        }
+        let addresses = self.accounts.keys().chain(self.storage.keys());
+        for address in addresses {
+            let proven = self
+                .trie_get(state_root, keccak256(address))?
+                .map(|leaf| decode_account(&leaf))
+                .transpose()
+                .map_err(|_| WitnessError::InvalidAccount(*address))?;
+            if let Some(account) = self.accounts.get(address) {
+                let matches = match (&proven, account) {
+                    (None, None) => true,
+                    (Some((account, _)), Some(expected)) => account == expected,
+                    _ => false,
+                };
+                if !matches {
+                    return Err(WitnessError::AccountMismatch(*address));
+                }
+            }
+            let storage_root = proven.map_or(EMPTY_ROOT_HASH, |(_, root)| root);
+            for (key, value) in self.storage.get(address).into_iter().flatten() {
+                let proven = self
+                    .trie_get(storage_root, keccak256(key.to_be_bytes::<32>()))?
+                    .map(|leaf| U256::decode(&mut leaf.as_slice()))
+                    .transpose()
+                    .map_err(|_| WitnessError::StorageMismatch(*address, *key))?
+                    .unwrap_or_default();
+                if proven != *value {
+                    return Err(WitnessError::StorageMismatch(*address, *key));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the value at the key of the secure trie with the given root.
+    fn trie_get(&self, root: B256, key: B256) -> Result<Option<Vec<u8>>, WitnessError> {
+        if root == EMPTY_ROOT_HASH {
+            return Ok(None);
+        }
+        let path: Vec<u8> = key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect();
+        let mut path = path.as_slice();
+        let mut hash = root;
+        let mut node = self.node(&hash)?;
+        loop {
+            let invalid = |_| WitnessError::InvalidNode(hash);
+            let items = decode_node(node).map_err(invalid)?;
+            let child = match items.as_slice() {
+                [key, value] => {
+                    let encoded = Header::decode_bytes(&mut &key[..], false).map_err(invalid)?;
+                    let (is_leaf, node_path) = decode_path(encoded);
+                    if is_leaf {
+                        if path != node_path.as_slice() {
+                            return Ok(None);
+                        }
+                        let value =
+                            Header::decode_bytes(&mut &value[..], false).map_err(invalid)?;
+                        return Ok(Some(value.to_vec()));
+                    }
+                    let Some(rest) = path.strip_prefix(node_path.as_slice()) else {
+                        return Ok(None);
+                    };
+                    path = rest;
+                    *value
+                }
+                [children @ .., _] if children.len() == 16 => {
+                    // keys of secure tries have the same length, branches have no values.
+                    let Some((nibble, rest)) = path.split_first() else {
+                        return Err(WitnessError::InvalidNode(hash));
+                    };
+                    path = rest;
+                    children[*nibble as usize]
+                }
+                _ => return Err(WitnessError::InvalidNode(hash)),
+            };
+            match child {
+                [EMPTY_STRING_CODE] => return Ok(None),
+                // hash reference.
+                [0xa0, child_hash @ ..] if child_hash.len() == 32 => {
+                    hash = B256::from_slice(child_hash);
+                    node = self.node(&hash)?;
+                }
+                // inlined node shorter than 32 bytes.
+                _ => node = child,
+            }
+        }
+    }
+
+    fn node(&self, hash: &B256) -> Result<&[u8], WitnessError> {
+        self.nodes
+            .get(hash)
+            .map(|node| node.as_ref())
+            .ok_or(WitnessError::MissingNode(*hash))
+    }
+
+    /// Extends the witness with another witness, already present values are kept.
+    pub fn extend(&mut self, other: Witness) {
+        for (address, account) in other.accounts {
+            self.accounts.entry(address).or_insert(account);
+        }
+        for (address, storage) in other.storage {
+            let slots = self.storage.entry(address).or_default();
+            for (key, value) in storage {
+                slots.entry(key).or_insert(value);
+            }
+        }
+        for (hash, bytecode) in other.bytecodes {
+            self.bytecodes.entry(hash).or_insert(bytecode);
+        }
+        for (number, hash) in other.block_hashes {
+            self.block_hashes.entry(number).or_insert(hash);
+        }
+        for (hash, node) in other.nodes {
+            self.nodes.entry(hash).or_insert(node);
+        }
+    }
+
+    fn record_account(&mut self, address: Address, info: Option<&AccountInfo>) {
+        self.accounts
+            .entry(address)
+            .or_insert_with(|| info.map(WitnessAccount::from));
+        // databases can return the code together with the account.
+        if let Some(code) = info
+            .filter(|info| info.code_hash != KECCAK_EMPTY)
+            .and_then(|info| info.code.as_ref().map(|code| (info.code_hash, code)))
+        {
+            self.bytecodes
+                .entry(code.0)
+                .or_insert_with(|| code.1.clone());
+        }
+    }
+}
+
+/// Splits the RLP list of the trie node into its raw items.
+fn decode_node(mut node: &[u8]) -> alloy_rlp::Result<Vec<&[u8]>> {
+    let header = Header::decode(&mut node)?;
+    if !header.list || header.payload_length != node.len() {
+        return Err(alloy_rlp::Error::UnexpectedString);
+    }
+    let mut items = Vec::with_capacity(17);
+    while !node.is_empty() {
+        let mut rest = node;
+        let item = Header::decode(&mut rest)?;
+        let length = node.len() - rest.len() + item.payload_length;
+        if length > node.len() {
+            return Err(alloy_rlp::Error::InputTooShort);
+        }
+        let (item, rest) = node.split_at(length);
+        items.push(item);
+        node = rest;
+    }
+    Ok(items)
+}
+
+/// Decodes the hex prefix encoded path, returns `true` if the node is a leaf.
+fn decode_path(encoded: &[u8]) -> (bool, Vec<u8>) {
+    let Some((first, rest)) = encoded.split_first() else {
+        return (false, Vec::new());
+    };
+    let flag = first >> 4;
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(rest.iter().flat_map(|b| [b >> 4, b & 0x0f]));
+    (flag & 2 == 2, path)
+}
+
+/// Decodes the account of the state trie, returns it with its storage root.
+fn decode_account(mut leaf: &[u8]) -> alloy_rlp::Result<(WitnessAccount, B256)> {
+    let header = Header::decode(&mut leaf)?;
+    if !header.list {
+        return Err(alloy_rlp::Error::UnexpectedString);
+    }
+    let nonce = u64::decode(&mut leaf)?;
+    let balance = U256::decode(&mut leaf)?;
+    let storage_root = B256::decode(&mut leaf)?;
+    let code_hash = B256::decode(&mut leaf)?;
+    let account = WitnessAccount {
+        balance,
+        nonce,
+        code_hash,
+    };
+    Ok((account, storage_root))
+}
+
+/// Witness that does not match the state root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessError {
+    /// Trie node is not in the witness.
+    MissingNode(B256),
+    /// Trie node can't be decoded.
+    InvalidNode(B256),
+    /// Account leaf can't be decoded.
+    InvalidAccount(Address),
+    /// Account is different from the proven one.
+    AccountMismatch(Address),
+    /// Storage slot is different from the proven one.
+    StorageMismatch(Address, StorageKey),
+    /// Bytecode does not match its hash.
+    BytecodeMismatch(B256),
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingNode(hash) => write!(f, "trie node {hash} is not in the witness"),
+            Self::InvalidNode(hash) => write!(f, "invalid trie node {hash}"),
+            Self::InvalidAccount(address) => write!(f, "invalid trie account {address}"),
+            Self::AccountMismatch(address) => {
+                write!(f, "account {address} does not match the state root")
+            }
+            Self::StorageMismatch(address, key) => {
+                write!(
+                    f,
+                    "storage slot {key:#x} of {address} does not match the state root"
+                )
+            }
+            Self::BytecodeMismatch(hash) => write!(f, "bytecode does not match its hash {hash}"),
+        }
+    }
+}
+
+impl Error for WitnessError {}
+
+/// Database wrapper that records all values read from the inner database into a [`Witness`].
+///
+/// Only the first read of every key is recorded, so committing changes to the inner database
+/// does not change the recorded pre-state.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingDb<DB> {
+    /// Inner database.
+    pub db: DB,
+    /// Recorded witness.
+    pub witness: Witness,
+}
+
+impl<DB> RecordingDb<DB> {
+    /// Creates new recording database.
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            witness: Witness::default(),
+        }
+    }
+
+    /// Returns the recorded witness.
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+
+    /// Takes the recorded witness and starts recording a new one.
+    pub fn take_witness(&mut self) -> Witness {
+        core::mem::take(&mut self.witness)
+    }
+
+    /// Consumes the database and returns the inner database and the recorded witness.
+    pub fn into_parts(self) -> (DB, Witness) {
+        (self.db, self.witness)
+    }
+}
+
+impl<DB: Database> Database for RecordingDb<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic(address)?;
+        self.witness.record_account(address, info.as_ref());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.db.code_by_hash(code_hash)?;
+        self.witness
+            .bytecodes
+            .entry(code_hash)
+            .or_insert_with(|| code.clone());
+        Ok(code)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        let value = self.db.storage(address, index)?;
+        self.witness
+            .storage
+            .entry(address)
+            .or_default()
+            .entry(index)
+            .or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        let hash = self.db.block_hash(number)?;
+        self.witness.block_hashes.entry(number).or_insert(hash);
+        Ok(hash)
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for RecordingDb<DB> {
+    fn commit(&mut self, changes: HashMap<Address, Account>) {
+        self.db.commit(changes)
+    }
+}
+
+/// Key that is missing from the witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessDbError {
+    /// Account is not in the witness.
+    MissingAccount(Address),
+    /// Storage slot is not in the witness.
+    MissingStorage(Address, StorageKey),
+    /// Bytecode is not in the witness.
+    MissingBytecode(B256),
+    /// Block hash is not in the witness.
+    MissingBlockHash(u64),
+}
+
+impl DBErrorMarker for WitnessDbError {}
+
+impl fmt::Display for WitnessDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAccount(address) => write!(f, "account {address} is not in the witness"),
+            Self::MissingStorage(address, key) => {
+                write!(
+                    f,
+                    "storage slot {key:#x} of {address} is not in the witness"
+                )
+            }
+            Self::MissingBytecode(hash) => write!(f, "bytecode {hash} is not in the witness"),
+            Self::MissingBlockHash(number) => {
+                write!(f, "block hash {number} is not in the witness")
+            }
+        }
+    }
+}
+
+impl Error for WitnessDbError {}
+
+/// Database that serves execution only from the [`Witness`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WitnessDb {
+    /// Witness used for execution.
+    pub witness: Witness,
+}
+
+impl WitnessDb {
+    /// Creates new database from the witness.
+    pub fn new(witness: Witness) -> Self {
+        Self { witness }
+    }
+}
+
+impl From<Witness> for WitnessDb {
+    fn from(witness: Witness) -> Self {
+        Self::new(witness)
+    }
+}
+
+impl DatabaseRef for WitnessDb {
+    type Error = WitnessDbError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let account = self
+            .witness
+            .accounts
+            .get(&address)
+            .ok_or(WitnessDbError::MissingAccount(address))?;
+        Ok(account.map(|account| {
+            let code = if account.code_hash == KECCAK_EMPTY {
+                Some(Bytecode::default())
+            } else {
+                // missing code is reported by `code_by_hash` if execution needs it.
+                self.witness.bytecodes.get(&account.code_hash).cloned()
+            };
+            AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce,
+                code_hash: account.code_hash,
+                code,
+                ..Default::default()
+            }
+        }))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+        self.witness
+            .bytecodes
+            .get(&code_hash)
+            .cloned()
+            .ok_or(WitnessDbError::MissingBytecode(code_hash))
+    }
+
+    fn storage_ref(
+        &self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        self.witness
+            .storage
+            .get(&address)
+            .and_then(|storage| storage.get(&index))
+            .copied()
+            .ok_or(WitnessDbError::MissingStorage(address, index))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.witness
+            .block_hashes
+            .get(&number)
+            .copied()
+            .ok_or(WitnessDbError::MissingBlockHash(number))
+    }
+}
+
+impl Database for WitnessDb {
+    type Error = WitnessDbError;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<StorageValue, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.block_hash_ref(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CacheDB, EmptyDB};
+    use primitives::{address, keccak256, Bytes};
+
+    const A: Address = address!("0x1000000000000000000000000000000000000001");
+    const B: Address = address!("0x2000000000000000000000000000000000000002");
+
+    fn db() -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            A,
+            AccountInfo::default()
+                .with_balance(U256::from(10))
+                .with_code(Bytecode::new_raw(Bytes::from_static(&[0x00]))),
+        );
+        db.insert_account_storage(A, U256::from(1), U256::from(2))
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut recording = RecordingDb::new(db());
+        let info = recording.basic(A).unwrap();
+        assert_eq!(recording.basic(B).unwrap(), None);
+        let value = recording.storage(A, U256::from(1)).unwrap();
+        let hash = recording.block_hash(1).unwrap();
+
+        // values read after commit are not recorded.
+        let mut changes = HashMap::default();
+        let mut account = Account::from(AccountInfo::default().with_balance(U256::from(1)));
+        account.mark_touch();
+        changes.insert(A, account);
+        recording.commit(changes);
+        assert_eq!(recording.basic(A).unwrap().unwrap().balance, U256::from(1));
+
+        let witness = recording.take_witness();
+        assert_eq!(witness.accounts.len(), 2);
+        assert_eq!(witness.accounts[&A].unwrap().balance, U256::from(10));
+
+        let mut witness_db = WitnessDb::new(witness);
+        assert_eq!(witness_db.basic(A).unwrap(), info);
+        assert_eq!(witness_db.basic(B).unwrap(), None);
+        assert_eq!(witness_db.storage(A, U256::from(1)).unwrap(), value);
+        assert_eq!(witness_db.block_hash(1).unwrap(), hash);
+        assert_eq!(hash, keccak256(b"1"));
+    }
+
+    #[test]
+    fn missing_keys() {
+        let mut witness_db = WitnessDb::default();
+        assert_eq!(witness_db.basic(A), Err(WitnessDbError::MissingAccount(A)));
+        assert_eq!(
+            witness_db.storage(A, U256::ZERO),
+            Err(WitnessDbError::MissingStorage(A, U256::ZERO))
+        );
+        assert_eq!(
+            witness_db.code_by_hash(B256::ZERO),
+            Err(WitnessDbError::MissingBytecode(B256::ZERO))
+        );
+        assert_eq!(
+            witness_db.block_hash(1),
+            Err(WitnessDbError::MissingBlockHash(1))
+        );
+    }
+
+    #[test]
+    fn verify_empty_state() {
+        let mut witness = Witness::default();
+        witness.accounts.insert(A, None);
+        witness
+            .storage
+            .entry(A)
+            .or_default()
+            .insert(U256::ZERO, U256::ZERO);
+        assert_eq!(witness.verify(EMPTY_ROOT_HASH), Ok(()));
+
+        witness.accounts.insert(B, Some(WitnessAccount::default()));
+        assert_eq!(
+            witness.verify(EMPTY_ROOT_HASH),
+            Err(WitnessError::AccountMismatch(B))
+        );
+
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x00]));
+        let witness = Witness {
+            bytecodes: BTreeMap::from([(B256::ZERO, code)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            witness.verify(EMPTY_ROOT_HASH),
+            Err(WitnessError::BytecodeMismatch(B256::ZERO))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let mut recording = RecordingDb::new(db());
+        recording.basic(A).unwrap();
+        recording.storage(A, U256::from(1)).unwrap();
+        let witness = recording.take_witness();
+
+        let serialized = serde_json::to_string(&witness).unwrap();
+        let deserialized: Witness = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, witness);
+    }
+}
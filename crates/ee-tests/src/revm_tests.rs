@@ -3,7 +3,10 @@
 use crate::TestdataConfig;
 use revm::{
     bytecode::opcode,
-    context::{CfgEnv, ContextTr, TxEnv},
+    context::{
+        result::{EVMError, HaltReason},
+        CfgEnv, ContextTr, TxEnv,
+    },
     database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET},
     primitives::{address, b256, hardfork::SpecId, Bytes, TxKind, KECCAK_EMPTY, U256},
     state::{AccountStatus, Bytecode},
@@ -283,3 +286,235 @@ fn test_disable_balance_check() {
     let expected_balance = U256::ZERO;
     assert_eq!(returned_balance, expected_balance);
 }
+
+#[test]
+fn test_estimate_gas() {
+    use revm::{handler::EstimateGasError, EstimateGasEvm};
+
+    // reverts if the remaining gas is less than 100_000.
+    let threshold = [
+        opcode::PUSH3,
+        0x01,
+        0x86,
+        0xa0,
+        opcode::GAS,
+        opcode::LT,
+        opcode::PUSH1,
+        10,
+        opcode::JUMPI,
+        opcode::STOP,
+        opcode::JUMPDEST,
+        opcode::PUSH1,
+        0,
+        opcode::PUSH1,
+        0,
+        opcode::REVERT,
+    ];
+    let tx = || TxEnv::builder_for_bench().gas_limit(1_000_000).build_fill();
+    let evm = |code: &[u8], cfg: CfgEnv| {
+        Context::mainnet()
+            .with_cfg(cfg)
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::new_legacy(
+                Bytes::copy_from_slice(code),
+            )))
+            .build_mainnet()
+    };
+
+    // intrinsic gas, PUSH3 and GAS are spent before the remaining gas is checked.
+    let estimate = evm(&threshold, CfgEnv::default())
+        .estimate_gas(tx())
+        .unwrap();
+    assert_eq!(estimate.gas_limit, 21_000 + 3 + 2 + 100_000);
+    assert!(estimate.result.is_success());
+
+    // EIP-7623 floor gas is the lower bound.
+    let mut evm_empty = evm(&[], CfgEnv::new_with_spec(SpecId::PRAGUE));
+    let data = Bytes::from(vec![1u8; 1000]);
+    let estimate = evm_empty
+        .estimate_gas(TxEnv::builder_for_bench().data(data).build_fill())
+        .unwrap();
+    assert_eq!(estimate.gas_limit, 21_000 + 4 * 1000 * 10);
+
+    // EIP-7825 cap limits the upper bound.
+    let mut cfg = CfgEnv::default();
+    cfg.tx_gas_limit_cap = Some(100_000);
+    assert_eq!(
+        evm(&threshold, cfg).estimate_gas(tx()),
+        Err(EstimateGasError::Reverted {
+            output: Bytes::new(),
+            gas_used: 21_000 + 3 + 2 + 3 + 3 + 10 + 1 + 3 + 3,
+        })
+    );
+
+    let halt = [opcode::INVALID];
+    assert!(matches!(
+        evm(&halt, CfgEnv::default()).estimate_gas(tx()),
+        Err(EstimateGasError::Halted {
+            reason: HaltReason::InvalidFEOpcode,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_estimate_gas_balance_cap() {
+    use revm::{
+        database::InMemoryDB, handler::EstimateGasError, state::AccountInfo, EstimateGasEvm,
+    };
+
+    let evm = |balance: u64| {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            BENCH_CALLER,
+            AccountInfo::default().with_balance(U256::from(balance)),
+        );
+        Context::mainnet().with_db(db).build_mainnet()
+    };
+    let tx = TxEnv::builder()
+        .caller(BENCH_CALLER)
+        .kind(TxKind::Call(BENCH_TARGET))
+        .value(U256::from(100_000))
+        .gas_price(10)
+        .gas_limit(1_000_000)
+        .build()
+        .unwrap();
+
+    // gas limit of the transaction is more than the caller can pay for.
+    let estimate = evm(500_000).estimate_gas(tx.clone()).unwrap();
+    assert_eq!(estimate.gas_limit, 21_000);
+    assert!(estimate.result.is_success());
+
+    // balance left after the value does not cover the intrinsic gas.
+    assert!(matches!(
+        evm(300_000).estimate_gas(tx),
+        Err(EstimateGasError::Evm(_))
+    ));
+}
+
+#[test]
+fn test_estimate_gas_database_error() {
+    use revm::{
+        database::{Database, ErasedError},
+        handler::EstimateGasError,
+        primitives::{Address, StorageKey, StorageValue, B256},
+        state::AccountInfo,
+        EstimateGasEvm,
+    };
+
+    /// Database that fails the storage reads after the first one.
+    struct FailingDb {
+        inner: BenchmarkDB,
+        storage_reads: usize,
+    }
+
+    impl Database for FailingDb {
+        type Error = ErasedError;
+
+        fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+            Ok(self.inner.basic(address).unwrap())
+        }
+
+        fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+            Ok(self.inner.code_by_hash(code_hash).unwrap())
+        }
+
+        fn storage(
+            &mut self,
+            _address: Address,
+            _index: StorageKey,
+        ) -> Result<StorageValue, Self::Error> {
+            self.storage_reads += 1;
+            if self.storage_reads > 1 {
+                return Err(ErasedError::new(std::io::Error::other("connection lost")));
+            }
+            Ok(StorageValue::ZERO)
+        }
+
+        fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+            Ok(self.inner.block_hash(number).unwrap())
+        }
+    }
+
+    let code = [opcode::PUSH1, 0, opcode::SLOAD, opcode::POP, opcode::STOP];
+    let db = FailingDb {
+        inner: BenchmarkDB::new_bytecode(Bytecode::new_legacy(code.into())),
+        storage_reads: 0,
+    };
+    let mut evm = Context::mainnet().with_db(db).build_mainnet();
+
+    // failed read of the probe is not taken as a too low gas limit.
+    let error = evm
+        .estimate_gas(TxEnv::builder_for_bench().gas_limit(1_000_000).build_fill())
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        EstimateGasError::Evm(EVMError::Database(error)) if error.to_string() == "connection lost"
+    ));
+}
+
+#[test]
+fn test_genesis_alloc_with_cache_budget() {
+    use revm::{
+        database::{
+            genesis::Genesis, states::bundle_state::BundleRetention, Database, EvictionPolicy,
+            State,
+        },
+        ExecuteCommitEvm,
+    };
+
+    // increments the storage slot one of the contract.
+    const GENESIS: &str = r#"{
+        "alloc": {
+            "0x1000000000000000000000000000000000000001": {
+                "balance": "1000000000000000000"
+            },
+            "0x2000000000000000000000000000000000000002": {
+                "balance": "0x0",
+                "code": "0x60015460010160015500",
+                "storage": {
+                    "0x0000000000000000000000000000000000000000000000000000000000000001": "0x2a"
+                }
+            }
+        }
+    }"#;
+    let caller = address!("0x1000000000000000000000000000000000000001");
+    let contract = address!("0x2000000000000000000000000000000000000002");
+    let genesis: Genesis = serde_json::from_str(GENESIS).unwrap();
+
+    for policy in [EvictionPolicy::Lru, EvictionPolicy::BundleReferenced] {
+        let mut state = State::builder()
+            .with_bundle_update()
+            .with_cache_budget(0, policy)
+            .build();
+        state.insert_genesis_alloc(&genesis.alloc);
+        // genesis accounts are not in the database and are kept over the budget.
+        state.evict_cache();
+        assert_eq!(state.cache.accounts.len(), 2);
+
+        let mut evm = Context::mainnet().with_db(&mut state).build_mainnet();
+        for nonce in 0..2 {
+            let result = evm
+                .transact_commit(
+                    TxEnv::builder()
+                        .caller(caller)
+                        .kind(TxKind::Call(contract))
+                        .nonce(nonce)
+                        .gas_limit(100_000)
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+            assert!(result.is_success(), "{policy:?}: {result:?}");
+        }
+        drop(evm);
+
+        state.evict_cache();
+        assert_eq!(
+            state.storage(contract, U256::from(1)).unwrap(),
+            U256::from(44)
+        );
+        assert_eq!(state.basic(caller).unwrap().unwrap().nonce, 2);
+        state.merge_transitions(BundleRetention::Reverts);
+        assert!(state.bundle_state.state.contains_key(&contract));
+    }
+}
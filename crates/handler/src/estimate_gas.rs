@@ -0,0 +1,254 @@
+//! Gas estimation with `eth_estimateGas` semantics.
+use crate::{ContextTrDbError, EvmTr, ExecuteEvm};
+use context::TxEnv;
+use context_interface::{
+    result::{EVMError, ExecutionResult, InvalidTransaction},
+    Block, Cfg, ContextTr, Database, Transaction,
+};
+use core::fmt;
+use interpreter::{gas::CALL_STIPEND, instructions::calculate_initial_tx_gas_for_tx};
+use primitives::{hardfork::SpecId, Bytes, U256};
+
+/// Estimation of the gas limit of the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GasEstimate<H> {
+    /// Lowest gas limit with which the transaction succeeds.
+    pub gas_limit: u64,
+    /// Result of the transaction executed with the estimated gas limit.
+    pub result: ExecutionResult<H>,
+}
+
+/// Type alias for the result of estimate_gas to reduce type complexity.
+type EstimateGasResult<H, E> = Result<GasEstimate<H>, EstimateGasError<H, E>>;
+
+/// Error of the gas estimation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EstimateGasError<H, E> {
+    /// Transaction reverted with the highest allowed gas limit.
+    Reverted {
+        /// Revert data.
+        output: Bytes,
+        /// Gas used by the transaction.
+        gas_used: u64,
+    },
+    /// Transaction halted with the highest allowed gas limit.
+    Halted {
+        /// Reason for the halt.
+        reason: H,
+        /// Gas used by the transaction.
+        gas_used: u64,
+    },
+    /// Transaction could not be executed.
+    Evm(E),
+}
+
+impl<H: fmt::Display, E: fmt::Display> fmt::Display for EstimateGasError<H, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reverted { output, .. } if output.is_empty() => write!(f, "execution reverted"),
+            Self::Reverted { output, .. } => write!(f, "execution reverted: {output}"),
+            Self::Halted { reason, .. } => write!(f, "execution halted: {reason}"),
+            Self::Evm(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<H, E> core::error::Error for EstimateGasError<H, E>
+where
+    H: fmt::Debug + fmt::Display,
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Evm(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Error that is returned because the gas limit of the transaction is too low.
+///
+/// [`EstimateGasEvm::estimate_gas`] considers the gas limits that fail with such error too low,
+/// any other error stops the estimation.
+pub trait GasLimitError {
+    /// Returns `true` if a higher gas limit could make the transaction valid.
+    fn is_gas_limit_error(&self) -> bool;
+}
+
+impl GasLimitError for InvalidTransaction {
+    fn is_gas_limit_error(&self) -> bool {
+        matches!(
+            self,
+            Self::CallGasCostMoreThanGasLimit { .. }
+                | Self::GasFloorMoreThanGasLimit { .. }
+                | Self::CallerGasLimitMoreThanBlock
+                | Self::TxGasLimitGreaterThanCap { .. }
+                | Self::LackOfFundForMaxFee { .. }
+        )
+    }
+}
+
+impl<DB, TX: GasLimitError> GasLimitError for EVMError<DB, TX> {
+    fn is_gas_limit_error(&self) -> bool {
+        match self {
+            Self::Transaction(error) => error.is_gas_limit_error(),
+            _ => false,
+        }
+    }
+}
+
+/// Gas estimation of the transaction.
+///
+/// Implemented for every EVM that executes [`TxEnv`] transactions.
+pub trait EstimateGasEvm: ExecuteEvm {
+    /// Halt reason of the execution result.
+    type HaltReason;
+
+    /// Estimates the lowest gas limit with which the transaction succeeds.
+    ///
+    /// Gas limit of the transaction is used as the upper bound, block gas limit is used if
+    /// the gas limit does not cover the intrinsic gas. Upper bound is capped by the block gas
+    /// limit, the transaction gas limit cap (EIP-7825) and the gas the caller can pay for with
+    /// its balance left after the value and the blob fee. The lower bound is the intrinsic
+    /// gas or the EIP-7623 floor gas.
+    ///
+    /// Transaction is first executed with the upper bound and revert data or halt reason is
+    /// returned if it fails. Otherwise an optimistic guess derived from the used and refunded
+    /// gas and the 63/64 rule of EIP-150 is tried before the binary search. Gas limits with
+    /// which the execution fails or which are rejected by a [`GasLimitError`] are considered
+    /// too low, other EVM errors are returned.
+    ///
+    /// Transactions are executed with [`ExecuteEvm::transact`], state is not committed.
+    fn estimate_gas(&mut self, tx: Self::Tx) -> EstimateGasResult<Self::HaltReason, Self::Error>;
+}
+
+impl<EVM, H> EstimateGasEvm for EVM
+where
+    EVM: ExecuteEvm<
+            Tx = TxEnv,
+            ExecutionResult = ExecutionResult<H>,
+            Error: From<ContextTrDbError<EVM::Context>> + GasLimitError,
+        > + EvmTr,
+{
+    type HaltReason = H;
+
+    fn estimate_gas(&mut self, mut tx: TxEnv) -> EstimateGasResult<H, EVM::Error> {
+        let ctx = self.ctx_ref();
+        let spec = ctx.cfg().spec().into();
+        let mut gas = calculate_initial_tx_gas_for_tx(&tx, spec);
+        if ctx.cfg().is_eip7623_disabled() || !spec.is_enabled_in(SpecId::PRAGUE) {
+            gas.floor_gas = 0;
+        }
+        let min_gas = gas.initial_gas.max(gas.floor_gas);
+
+        let block_gas_limit = ctx.block().gas_limit();
+        let mut hi = if tx.gas_limit >= min_gas {
+            tx.gas_limit
+        } else {
+            block_gas_limit
+        };
+        if !ctx.cfg().is_block_gas_limit_disabled() {
+            hi = hi.min(block_gas_limit);
+        }
+        hi = hi.min(ctx.cfg().tx_gas_limit_cap());
+
+        if tx.gas_price != 0 && !ctx.cfg().is_balance_check_disabled() {
+            let balance = self
+                .ctx()
+                .db_mut()
+                .basic(tx.caller)
+                .map_err(|error| EstimateGasError::Evm(error.into()))?
+                .map(|account| account.balance)
+                .unwrap_or_default();
+            let blob_fee =
+                U256::from(tx.max_fee_per_blob_gas).saturating_mul(U256::from(tx.total_blob_gas()));
+            let allowance = balance.saturating_sub(tx.value).saturating_sub(blob_fee)
+                / U256::from(tx.gas_price);
+            hi = hi.min(allowance.saturating_to());
+        }
+
+        let mut result = transact_with_gas_limit(self, &mut tx, hi)?;
+        let (gas_used, gas_refunded) = match result {
+            ExecutionResult::Success {
+                gas_used,
+                gas_refunded,
+                ..
+            } => (gas_used, gas_refunded),
+            ExecutionResult::Revert { output, gas_used } => {
+                return Err(EstimateGasError::Reverted { output, gas_used })
+            }
+            ExecutionResult::Halt { reason, gas_used } => {
+                return Err(EstimateGasError::Halted { reason, gas_used })
+            }
+        };
+
+        // Gas limit below the intrinsic or floor gas is invalid.
+        let mut lo = min_gas.saturating_sub(1);
+
+        // Gas spent before the refund with the stipend of a value transfer, increased so that
+        // the 63/64 of it that is passed to the calls covers the spent gas.
+        let optimistic = gas_used
+            .saturating_add(gas_refunded)
+            .saturating_add(CALL_STIPEND)
+            .saturating_mul(64)
+            / 63;
+        if optimistic < hi {
+            match probe(self, &mut tx, optimistic)? {
+                Some(optimistic_result) => {
+                    hi = optimistic;
+                    result = optimistic_result;
+                }
+                None => lo = optimistic,
+            }
+        }
+
+        while lo + 1 < hi {
+            // Most transactions need gas close to the lower bound, so the search is biased
+            // towards it.
+            let mid = (lo + (hi - lo) / 2).min(lo.saturating_mul(2));
+            match probe(self, &mut tx, mid)? {
+                Some(mid_result) => {
+                    hi = mid;
+                    result = mid_result;
+                }
+                None => lo = mid,
+            }
+        }
+
+        Ok(GasEstimate {
+            gas_limit: hi,
+            result,
+        })
+    }
+}
+
+/// Executes the transaction with the given gas limit without committing the state.
+fn transact_with_gas_limit<EVM, H>(
+    evm: &mut EVM,
+    tx: &mut TxEnv,
+    gas_limit: u64,
+) -> Result<ExecutionResult<H>, EstimateGasError<H, EVM::Error>>
+where
+    EVM: ExecuteEvm<Tx = TxEnv, ExecutionResult = ExecutionResult<H>>,
+{
+    tx.gas_limit = gas_limit;
+    evm.transact(tx.clone())
+        .map(|output| output.result)
+        .map_err(EstimateGasError::Evm)
+}
+
+/// Executes the transaction with the given gas limit, returns `None` if the gas limit is too low.
+fn probe<EVM, H>(
+    evm: &mut EVM,
+    tx: &mut TxEnv,
+    gas_limit: u64,
+) -> Result<Option<ExecutionResult<H>>, EstimateGasError<H, EVM::Error>>
+where
+    EVM: ExecuteEvm<Tx = TxEnv, ExecutionResult = ExecutionResult<H>, Error: GasLimitError>,
+{
+    match transact_with_gas_limit(evm, tx, gas_limit) {
+        Ok(result) => Ok(result.is_success().then_some(result)),
+        Err(EstimateGasError::Evm(error)) if error.is_gas_limit_error() => Ok(None),
+        Err(error) => Err(error),
+    }
+}
@@ -9,6 +9,7 @@ extern crate alloc as std;
 
 /// EVM execution API traits and implementations.
 pub mod api;
+mod estimate_gas;
 /// Core EVM traits for execution and frame management.
 pub mod evm;
 /// EVM execution logic and utilities.
@@ -25,7 +26,10 @@ mod mainnet_handler;
 /// Post-execution operations including gas refunds and state finalization.
 pub mod post_execution;
 pub mod pre_execution;
+mod precompile_cache;
 mod precompile_provider;
+mod precompile_registry;
+mod stateful_precompile;
 /// System call implementations for special EVM operations.
 pub mod system_call;
 /// Transaction and environment validation utilities.
@@ -33,6 +37,7 @@ pub mod validation;
 
 // Public exports
 pub use api::{ExecuteCommitEvm, ExecuteEvm};
+pub use estimate_gas::{EstimateGasError, EstimateGasEvm, GasEstimate, GasLimitError};
 pub use evm::{EvmTr, FrameTr};
 pub use frame::{return_create, ContextTrDbError, EthFrame};
 pub use frame_data::{CallFrame, CreateFrame, FrameData, FrameResult};
@@ -40,5 +45,8 @@ pub use handler::{EvmTrError, Handler};
 pub use item_or_result::{FrameInitOrResult, ItemOrResult};
 pub use mainnet_builder::{MainBuilder, MainContext, MainnetContext, MainnetEvm};
 pub use mainnet_handler::MainnetHandler;
+pub use precompile_cache::{CachedPrecompiles, PrecompileCache, PrecompileCacheStats};
 pub use precompile_provider::{EthPrecompiles, PrecompileProvider};
+pub use precompile_registry::{PrecompileRegistry, PrecompileScope};
+pub use stateful_precompile::{StatefulPrecompile, StatefulPrecompileInput, StatefulPrecompiles};
 pub use system_call::{SystemCallCommitEvm, SystemCallEvm, SystemCallTx, SYSTEM_ADDRESS};
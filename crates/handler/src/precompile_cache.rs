@@ -0,0 +1,339 @@
+//! Memoizing precompile provider.
+use crate::{
+    precompile_provider::precompile_interpreter_result, EthPrecompiles, PrecompileProvider,
+};
+use context::Cfg;
+use context_interface::ContextTr;
+use interpreter::{CallInputs, InstructionResult, InterpreterResult};
+use precompile::PrecompileOutput;
+use primitives::{keccak256, Address, HashMap, HashSet, B256};
+use std::{boxed::Box, collections::VecDeque, string::String};
+
+/// Approximate memory used by one cache entry, without the output bytes.
+const ENTRY_OVERHEAD: usize = 2 * core::mem::size_of::<CacheKey>()
+    + core::mem::size_of::<PrecompileOutput>()
+    + core::mem::size_of::<usize>();
+
+/// Cache key: precompile address, hash of the input and the gas limit bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    address: Address,
+    input_hash: B256,
+    gas_bucket: u32,
+}
+
+/// Returns the gas limit bucket, gas limits are grouped by the powers of two.
+fn gas_bucket(gas_limit: u64) -> u32 {
+    gas_limit.checked_ilog2().map_or(0, |log| log + 1)
+}
+
+/// Statistics of the [`PrecompileCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrecompileCacheStats {
+    /// Number of calls answered from the cache.
+    pub hits: u64,
+    /// Number of calls that executed the precompile.
+    pub misses: u64,
+    /// Number of entries evicted to stay under the memory limit.
+    pub evictions: u64,
+    /// Number of cached entries.
+    pub entries: usize,
+    /// Approximate memory used by the cached entries, in bytes.
+    pub bytes: usize,
+}
+
+/// Cache of the precompile outputs with bounded memory.
+///
+/// When the memory limit is reached, oldest entries are evicted first.
+#[derive(Clone, Debug)]
+pub struct PrecompileCache {
+    entries: HashMap<CacheKey, PrecompileOutput>,
+    /// Insertion order of the entries, used for eviction.
+    order: VecDeque<CacheKey>,
+    /// Maximum memory used by the entries, in bytes.
+    max_bytes: usize,
+    stats: PrecompileCacheStats,
+}
+
+impl PrecompileCache {
+    /// Creates new cache that uses at most `max_bytes` of memory.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::default(),
+            order: VecDeque::new(),
+            max_bytes,
+            stats: PrecompileCacheStats::default(),
+        }
+    }
+
+    /// Returns the cache statistics.
+    pub fn stats(&self) -> PrecompileCacheStats {
+        self.stats
+    }
+
+    /// Removes all entries. Hit and miss counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.stats.entries = 0;
+        self.stats.bytes = 0;
+    }
+
+    /// Returns the cached output if its gas fits into the gas limit.
+    fn get(&mut self, key: &CacheKey, gas_limit: u64) -> Option<PrecompileOutput> {
+        let output = self
+            .entries
+            .get(key)
+            .filter(|output| output.gas_used <= gas_limit)
+            .cloned();
+        if output.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        output
+    }
+
+    fn insert(&mut self, key: CacheKey, output: PrecompileOutput) {
+        let size = ENTRY_OVERHEAD + output.bytes.len();
+        if size > self.max_bytes || self.entries.contains_key(&key) {
+            return;
+        }
+        while self.stats.bytes + size > self.max_bytes {
+            let Some(evicted) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(output) = self.entries.remove(&evicted) {
+                self.stats.bytes -= ENTRY_OVERHEAD + output.bytes.len();
+                self.stats.entries -= 1;
+                self.stats.evictions += 1;
+            }
+        }
+        self.entries.insert(key, output);
+        self.order.push_back(key);
+        self.stats.entries += 1;
+        self.stats.bytes += size;
+    }
+}
+
+/// [`PrecompileProvider`] that memoizes outputs of the inner provider.
+///
+/// Outputs are cached by the precompile address, the input hash and the gas limit bucket.
+/// Cached output is only used if its gas fits into the gas limit of the call, so results are
+/// identical to the uncached execution. Errors are not cached and the cache is cleared when
+/// the spec or the precompile set of the inner provider changes.
+///
+/// Only precompiles that don't access the state can be cached, so nothing is cached until the
+/// addresses of such precompiles are added with [`CachedPrecompiles::with_addresses`].
+/// [Stateful precompiles](crate::StatefulPrecompiles) must not be added.
+#[derive(Clone, Debug)]
+pub struct CachedPrecompiles<P = EthPrecompiles> {
+    /// Inner precompiles.
+    pub inner: P,
+    /// Output cache.
+    pub cache: PrecompileCache,
+    /// Addresses of the cached precompiles.
+    addresses: HashSet<Address>,
+}
+
+impl<P> CachedPrecompiles<P> {
+    /// Creates new caching provider that uses at most `max_bytes` of memory for outputs.
+    ///
+    /// No precompile is cached until it is added with [`CachedPrecompiles::with_addresses`].
+    pub fn new(inner: P, max_bytes: usize) -> Self {
+        Self {
+            inner,
+            cache: PrecompileCache::new(max_bytes),
+            addresses: HashSet::default(),
+        }
+    }
+
+    /// Caches the precompiles at the given addresses, their outputs must depend only on the
+    /// input and the gas limit.
+    pub fn with_addresses(mut self, addresses: impl IntoIterator<Item = Address>) -> Self {
+        self.addresses.extend(addresses);
+        self
+    }
+
+    /// Returns the cache statistics.
+    pub fn stats(&self) -> PrecompileCacheStats {
+        self.cache.stats()
+    }
+
+    fn is_cached(&self, address: &Address) -> bool {
+        self.addresses.contains(address)
+    }
+}
+
+impl<CTX, P> PrecompileProvider<CTX> for CachedPrecompiles<P>
+where
+    CTX: ContextTr,
+    P: PrecompileProvider<CTX, Output = InterpreterResult>,
+{
+    type Output = InterpreterResult;
+
+    fn set_spec(&mut self, spec: <CTX::Cfg as Cfg>::Spec) -> bool {
+        let changed = self.inner.set_spec(spec);
+        if changed {
+            // precompiles and their gas costs can differ between specs.
+            self.cache.clear();
+        }
+        changed
+    }
+
+    fn run(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CallInputs,
+    ) -> Result<Option<InterpreterResult>, String> {
+        if !self.is_cached(&inputs.bytecode_address)
+            || !self.inner.contains(&inputs.bytecode_address)
+        {
+            return self.inner.run(context, inputs);
+        }
+
+        let key = CacheKey {
+            address: inputs.bytecode_address,
+            input_hash: keccak256(inputs.input.bytes(context)),
+            gas_bucket: gas_bucket(inputs.gas_limit),
+        };
+        if let Some(output) = self.cache.get(&key, inputs.gas_limit) {
+            return precompile_interpreter_result(context, Ok(output), inputs.gas_limit).map(Some);
+        }
+        let Some(result) = self.inner.run(context, inputs)? else {
+            return Ok(None);
+        };
+        let reverted = match result.result {
+            InstructionResult::Return => false,
+            InstructionResult::Revert => true,
+            // halts are not cached.
+            _ => return Ok(Some(result)),
+        };
+        self.cache.insert(
+            key,
+            PrecompileOutput {
+                gas_used: result.gas.spent(),
+                gas_refunded: result.gas.refunded(),
+                bytes: result.output.clone(),
+                reverted,
+            },
+        );
+        Ok(Some(result))
+    }
+
+    fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
+        self.inner.warm_addresses()
+    }
+
+    fn contains(&self, address: &Address) -> bool {
+        self.inner.contains(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecuteEvm, MainBuilder, MainContext, PrecompileRegistry, PrecompileScope};
+    use context::{result::ExecutionResult, Context, TxEnv};
+    use database::BenchmarkDB;
+    use precompile::hash;
+    use primitives::{address, Bytes};
+    use state::Bytecode;
+    use std::vec::Vec;
+
+    const INPUTS: [&[u8]; 5] = [b"first", b"second", b"first", b"first", b"second"];
+
+    fn call(to: Address, input: &[u8], nonce: usize) -> TxEnv {
+        TxEnv::builder_for_bench()
+            .to(to)
+            .data(Bytes::copy_from_slice(input))
+            .nonce(nonce as u64)
+            .build_fill()
+    }
+
+    fn transact_all(
+        to: Address,
+        evm: &mut dyn FnMut(TxEnv) -> ExecutionResult,
+    ) -> Vec<ExecutionResult> {
+        INPUTS
+            .iter()
+            .enumerate()
+            .map(|(nonce, input)| evm(call(to, input, nonce)))
+            .collect()
+    }
+
+    #[test]
+    fn cached_precompiles() {
+        let sha256 = *hash::SHA256.address();
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet();
+        let expected = transact_all(sha256, &mut |tx| evm.transact_one(tx).unwrap());
+
+        // nothing is cached by default.
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(CachedPrecompiles::new(EthPrecompiles::default(), 1 << 20));
+        let results = transact_all(sha256, &mut |tx| evm.transact_one(tx).unwrap());
+        assert_eq!(results, expected);
+        assert_eq!(evm.precompiles.stats(), PrecompileCacheStats::default());
+
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                CachedPrecompiles::new(EthPrecompiles::default(), 1 << 20).with_addresses([sha256]),
+            );
+        let results = transact_all(sha256, &mut |tx| evm.transact_one(tx).unwrap());
+        assert_eq!(results, expected);
+        let stats = evm.precompiles.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (3, 2, 2));
+
+        // memory for one entry only.
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                CachedPrecompiles::new(EthPrecompiles::default(), 300).with_addresses([sha256]),
+            );
+        let results = transact_all(sha256, &mut |tx| evm.transact_one(tx).unwrap());
+        assert_eq!(results, expected);
+        let stats = evm.precompiles.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 4, 1));
+        assert_eq!(stats.evictions, 3);
+        assert!(stats.bytes <= 300);
+    }
+
+    #[test]
+    fn cached_registry() {
+        let sha256 = *hash::SHA256.address();
+        let moved = address!("0x0000000000000000000000000000000000000b00");
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet();
+        let expected = transact_all(sha256, &mut |tx| evm.transact_one(tx).unwrap());
+
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                CachedPrecompiles::new(PrecompileRegistry::default(), 1 << 20)
+                    .with_addresses([moved]),
+            );
+        evm.precompiles
+            .inner
+            .move_precompile(PrecompileScope::Block, sha256, moved);
+        let results = transact_all(moved, &mut |tx| evm.transact_one(tx).unwrap());
+        assert_eq!(results, expected);
+        let stats = evm.precompiles.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (3, 2, 2));
+
+        // cache is cleared with the registry change, moved address is not a precompile anymore.
+        evm.precompiles.inner.clear_block();
+        let result = evm.transact_one(call(moved, INPUTS[0], 5)).unwrap();
+        assert!(result.output().unwrap().is_empty());
+        assert_eq!(evm.precompiles.stats().entries, 0);
+    }
+}
@@ -2,7 +2,7 @@ use auto_impl::auto_impl;
 use context::{Cfg, LocalContextTr};
 use context_interface::{ContextTr, JournalTr};
 use interpreter::{CallInput, CallInputs, Gas, InstructionResult, InterpreterResult};
-use precompile::{PrecompileError, PrecompileSpecId, Precompiles};
+use precompile::{PrecompileError, PrecompileResult, PrecompileSpecId, Precompiles};
 use primitives::{hardfork::SpecId, Address, Bytes};
 use std::{
     boxed::Box,
@@ -34,6 +34,53 @@ pub trait PrecompileProvider<CTX: ContextTr> {
     fn contains(&self, address: &Address) -> bool;
 }
 
+/// Converts the precompile result into the [`InterpreterResult`].
+///
+/// Fatal errors are returned as `Err`, other errors halt the call. Error message of the
+/// top-level call is stored in the local context so it can be returned in the final result.
+pub(crate) fn precompile_interpreter_result<CTX: ContextTr>(
+    context: &mut CTX,
+    exec_result: PrecompileResult,
+    gas_limit: u64,
+) -> Result<InterpreterResult, String> {
+    let mut result = InterpreterResult {
+        result: InstructionResult::Return,
+        gas: Gas::new(gas_limit),
+        output: Bytes::new(),
+    };
+
+    match exec_result {
+        Ok(output) => {
+            result.gas.record_refund(output.gas_refunded);
+            let underflow = result.gas.record_cost(output.gas_used);
+            assert!(underflow, "Gas underflow is not possible");
+            result.result = if output.reverted {
+                InstructionResult::Revert
+            } else {
+                InstructionResult::Return
+            };
+            result.output = output.bytes;
+        }
+        Err(PrecompileError::Fatal(e)) => return Err(e),
+        Err(e) => {
+            result.result = if e.is_oog() {
+                InstructionResult::PrecompileOOG
+            } else {
+                InstructionResult::PrecompileError
+            };
+            // If this is a top-level precompile call (depth == 1), persist the error message
+            // into the local context so it can be returned as output in the final result.
+            // Only do this for non-OOG errors (OOG is a distinct halt reason without output).
+            if !e.is_oog() && context.journal().depth() == 1 {
+                context
+                    .local_mut()
+                    .set_precompile_error_context(e.to_string());
+            }
+        }
+    }
+    Ok(result)
+}
+
 /// The [`PrecompileProvider`] for ethereum precompiles.
 #[derive(Debug)]
 pub struct EthPrecompiles {
@@ -105,12 +152,6 @@ impl<CTX: ContextTr> PrecompileProvider<CTX> for EthPrecompiles {
             return Ok(None);
         };
 
-        let mut result = InterpreterResult {
-            result: InstructionResult::Return,
-            gas: Gas::new(inputs.gas_limit),
-            output: Bytes::new(),
-        };
-
         let exec_result = {
             let r;
             let input_bytes = match &inputs.input {
@@ -127,36 +168,7 @@ impl<CTX: ContextTr> PrecompileProvider<CTX> for EthPrecompiles {
             precompile.execute(input_bytes, inputs.gas_limit)
         };
 
-        match exec_result {
-            Ok(output) => {
-                result.gas.record_refund(output.gas_refunded);
-                let underflow = result.gas.record_cost(output.gas_used);
-                assert!(underflow, "Gas underflow is not possible");
-                result.result = if output.reverted {
-                    InstructionResult::Revert
-                } else {
-                    InstructionResult::Return
-                };
-                result.output = output.bytes;
-            }
-            Err(PrecompileError::Fatal(e)) => return Err(e),
-            Err(e) => {
-                result.result = if e.is_oog() {
-                    InstructionResult::PrecompileOOG
-                } else {
-                    InstructionResult::PrecompileError
-                };
-                // If this is a top-level precompile call (depth == 1), persist the error message
-                // into the local context so it can be returned as output in the final result.
-                // Only do this for non-OOG errors (OOG is a distinct halt reason without output).
-                if !e.is_oog() && context.journal().depth() == 1 {
-                    context
-                        .local_mut()
-                        .set_precompile_error_context(e.to_string());
-                }
-            }
-        }
-        Ok(Some(result))
+        precompile_interpreter_result(context, exec_result, inputs.gas_limit).map(Some)
     }
 
     fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
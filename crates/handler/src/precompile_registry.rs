@@ -0,0 +1,305 @@
+//! Precompile registry that can change precompiles at runtime.
+use crate::{
+    precompile_provider::precompile_interpreter_result, EthPrecompiles, PrecompileProvider,
+};
+use context::Cfg;
+use context_interface::ContextTr;
+use interpreter::{CallInputs, InterpreterResult};
+use precompile::Precompile;
+use primitives::{Address, HashMap, HashSet};
+use std::{boxed::Box, string::String};
+
+/// Scope of the precompile change in the [`PrecompileRegistry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrecompileScope {
+    /// Change is kept until [`PrecompileRegistry::clear_block`] is called.
+    Block,
+    /// Change applies to the next transaction only, it is cleared when the transaction after
+    /// it starts or when [`PrecompileRegistry::clear_transaction`] is called.
+    ///
+    /// Transaction changes take precedence over block changes.
+    Transaction,
+}
+
+/// Precompile changes of one scope. `None` means that the precompile is removed.
+type Overrides = HashMap<Address, Option<Precompile>>;
+
+/// [`PrecompileProvider`] with precompiles that can be added, removed or moved at runtime.
+///
+/// Changes are layered on top of the spec precompiles of [`EthPrecompiles`] and are scoped to
+/// the block or to the transaction, see [`PrecompileScope`]. Transaction changes are cleared at
+/// the start of the next transaction, block changes are cleared by the caller after the block is
+/// executed.
+///
+/// Any change makes the next [`PrecompileProvider::set_spec`] return `true`, so the journal
+/// warms the addresses of the new precompile set.
+///
+/// Registry is the base of the other providers: wrap it with
+/// [`StatefulPrecompiles`](crate::StatefulPrecompiles) or
+/// [`CachedPrecompiles`](crate::CachedPrecompiles) to combine them.
+#[derive(Clone, Debug, Default)]
+pub struct PrecompileRegistry {
+    /// Spec precompiles.
+    pub base: EthPrecompiles,
+    /// Block scoped changes.
+    block: Overrides,
+    /// Transaction scoped changes.
+    transaction: Overrides,
+    /// Precompile set changed since the last `set_spec` call.
+    changed: bool,
+    /// Transaction changes were used by a transaction and are cleared before the next
+    /// transaction or the next change.
+    transaction_started: bool,
+}
+
+impl PrecompileRegistry {
+    /// Creates new registry on top of the spec precompiles.
+    pub fn new(base: EthPrecompiles) -> Self {
+        Self {
+            base,
+            block: Overrides::default(),
+            transaction: Overrides::default(),
+            changed: false,
+            transaction_started: false,
+        }
+    }
+
+    /// Returns the precompile registered at the address.
+    pub fn get(&self, address: &Address) -> Option<&Precompile> {
+        if let Some(precompile) = self.transaction.get(address) {
+            return precompile.as_ref();
+        }
+        if let Some(precompile) = self.block.get(address) {
+            return precompile.as_ref();
+        }
+        self.base.precompiles.get(address)
+    }
+
+    /// Returns whether the precompile is registered at the address.
+    pub fn contains(&self, address: &Address) -> bool {
+        self.get(address).is_some()
+    }
+
+    /// Returns addresses of the registered precompiles.
+    pub fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
+        let addresses: HashSet<Address> = self
+            .base
+            .precompiles
+            .addresses()
+            .chain(self.block.keys())
+            .chain(self.transaction.keys())
+            .filter(|address| self.contains(address))
+            .copied()
+            .collect();
+        Box::new(addresses.into_iter())
+    }
+
+    /// Registers the precompile at its address.
+    pub fn insert(&mut self, scope: PrecompileScope, precompile: Precompile) {
+        self.end_transaction();
+        let address = *precompile.address();
+        self.overrides(scope).insert(address, Some(precompile));
+    }
+
+    /// Removes the precompile from the address. Returns the removed precompile.
+    pub fn remove(&mut self, scope: PrecompileScope, address: Address) -> Option<Precompile> {
+        self.end_transaction();
+        let precompile = self.get(&address).cloned();
+        self.overrides(scope).insert(address, None);
+        precompile
+    }
+
+    /// Moves the precompile to another address. Returns `false` if there is no precompile at
+    /// the `from` address.
+    pub fn move_precompile(&mut self, scope: PrecompileScope, from: Address, to: Address) -> bool {
+        let Some(precompile) = self.remove(scope, from) else {
+            return false;
+        };
+        let (id, _, fn_) = precompile.into();
+        self.insert(scope, Precompile::new(id, to, fn_));
+        true
+    }
+
+    /// Clears transaction scoped changes.
+    ///
+    /// Called automatically when the next transaction starts.
+    pub fn clear_transaction(&mut self) {
+        self.transaction_started = false;
+        if !self.transaction.is_empty() {
+            self.transaction.clear();
+            self.changed = true;
+        }
+    }
+
+    /// Clears block and transaction scoped changes.
+    pub fn clear_block(&mut self) {
+        self.clear_transaction();
+        if !self.block.is_empty() {
+            self.block.clear();
+            self.changed = true;
+        }
+    }
+
+    /// Clears changes of the executed transaction, the registry is changed for the next one.
+    fn end_transaction(&mut self) {
+        if self.transaction_started {
+            self.clear_transaction();
+        }
+    }
+
+    fn overrides(&mut self, scope: PrecompileScope) -> &mut Overrides {
+        self.changed = true;
+        match scope {
+            PrecompileScope::Block => &mut self.block,
+            PrecompileScope::Transaction => &mut self.transaction,
+        }
+    }
+}
+
+impl<CTX: ContextTr> PrecompileProvider<CTX> for PrecompileRegistry {
+    type Output = InterpreterResult;
+
+    fn set_spec(&mut self, spec: <CTX::Cfg as Cfg>::Spec) -> bool {
+        // spec is set at the start of every transaction.
+        self.end_transaction();
+        self.transaction_started = true;
+        let spec_changed =
+            <EthPrecompiles as PrecompileProvider<CTX>>::set_spec(&mut self.base, spec);
+        core::mem::take(&mut self.changed) || spec_changed
+    }
+
+    fn run(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CallInputs,
+    ) -> Result<Option<InterpreterResult>, String> {
+        let Some(precompile) = self.get(&inputs.bytecode_address) else {
+            return Ok(None);
+        };
+        let input = inputs.input.bytes(context);
+        let exec_result = precompile.execute(&input, inputs.gas_limit);
+        precompile_interpreter_result(context, exec_result, inputs.gas_limit).map(Some)
+    }
+
+    fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
+        Self::warm_addresses(self)
+    }
+
+    fn contains(&self, address: &Address) -> bool {
+        Self::contains(self, address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ExecuteEvm, MainBuilder, MainContext, StatefulPrecompileInput, StatefulPrecompiles,
+    };
+    use context::{result::ExecutionResult, Context, TxEnv};
+    use database::BenchmarkDB;
+    use precompile::{identity, PrecompileId, PrecompileOutput, PrecompileResult};
+    use primitives::{address, Bytes};
+    use state::Bytecode;
+
+    const MOVED_IDENTITY: Address = address!("0x0000000000000000000000000000000000000b00");
+    const MOCK: Address = address!("0x0000000000000000000000000000000000000c00");
+    const STATEFUL: Address = address!("0x0000000000000000000000000000000000000d00");
+
+    fn mock(_input: &[u8], _gas_limit: u64) -> PrecompileResult {
+        Ok(PrecompileOutput::new(100, Bytes::from_static(b"mock")))
+    }
+
+    fn call(to: Address, nonce: u64) -> TxEnv {
+        TxEnv::builder_for_bench()
+            .to(to)
+            .data(Bytes::from_static(b"input"))
+            .nonce(nonce)
+            .build_fill()
+    }
+
+    fn output(result: ExecutionResult) -> Bytes {
+        result.output().cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn registry_scopes() {
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(PrecompileRegistry::default());
+
+        assert!(evm.precompiles.move_precompile(
+            PrecompileScope::Transaction,
+            *identity::FUN.address(),
+            MOVED_IDENTITY
+        ));
+        evm.precompiles.insert(
+            PrecompileScope::Block,
+            Precompile::new(PrecompileId::Custom("mock".into()), MOCK, mock),
+        );
+        let warm: Vec<Address> = evm.precompiles.warm_addresses().collect();
+        assert!(warm.contains(&MOVED_IDENTITY) && warm.contains(&MOCK));
+        assert!(!warm.contains(identity::FUN.address()));
+        assert!(!evm.precompiles.contains(identity::FUN.address()));
+
+        assert_eq!(
+            output(evm.transact_one(call(MOVED_IDENTITY, 0)).unwrap()),
+            b"input"[..]
+        );
+        // identity is back at its address in the next transaction.
+        assert!(output(evm.transact_one(call(MOVED_IDENTITY, 1)).unwrap()).is_empty());
+        assert_eq!(
+            output(evm.transact_one(call(*identity::FUN.address(), 2)).unwrap()),
+            b"input"[..]
+        );
+        assert_eq!(
+            output(evm.transact_one(call(MOCK, 3)).unwrap()),
+            b"mock"[..]
+        );
+
+        // transaction change made between transactions applies to the next one only.
+        evm.precompiles.remove(PrecompileScope::Transaction, MOCK);
+        assert!(output(evm.transact_one(call(MOCK, 4)).unwrap()).is_empty());
+        assert_eq!(
+            output(evm.transact_one(call(MOCK, 5)).unwrap()),
+            b"mock"[..]
+        );
+
+        evm.precompiles.clear_block();
+        assert!(output(evm.transact_one(call(MOCK, 6)).unwrap()).is_empty());
+        assert!(evm.precompiles.contains(identity::FUN.address()));
+    }
+
+    #[test]
+    fn registry_with_stateful_precompiles() {
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                StatefulPrecompiles::new(PrecompileRegistry::default()).with_precompile(
+                    STATEFUL,
+                    |input: &mut StatefulPrecompileInput<'_, _>| {
+                        Ok(PrecompileOutput::new(100, input.caller().to_vec().into()))
+                    },
+                ),
+            );
+        evm.precompiles.inner.insert(
+            PrecompileScope::Transaction,
+            Precompile::new(PrecompileId::Custom("mock".into()), MOCK, mock),
+        );
+        assert!(evm.precompiles.contains(&MOCK) && evm.precompiles.contains(&STATEFUL));
+
+        assert_eq!(
+            output(evm.transact_one(call(MOCK, 0)).unwrap()),
+            b"mock"[..]
+        );
+        assert_eq!(
+            output(evm.transact_one(call(STATEFUL, 1)).unwrap()),
+            database::BENCH_CALLER[..]
+        );
+        // transaction change is cleared through the stateful provider.
+        assert!(output(evm.transact_one(call(MOCK, 2)).unwrap()).is_empty());
+        assert!(!evm.precompiles.contains(&MOCK));
+    }
+}
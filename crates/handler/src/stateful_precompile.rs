@@ -0,0 +1,383 @@
+//! Stateful precompiles with access to the call inputs and the journal.
+use crate::{
+    precompile_provider::precompile_interpreter_result, EthPrecompiles, PrecompileProvider,
+};
+use context::Cfg;
+use context_interface::{journaled_state::TransferError, ContextTr, JournalTr};
+use core::fmt;
+use interpreter::{CallInputs, InterpreterResult};
+use precompile::{PrecompileError, PrecompileResult};
+use primitives::{Address, Bytes, HashMap, Log, StorageKey, StorageValue, B256, U256};
+use std::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Precompile that can read and write state.
+///
+/// Unlike [`PrecompileFn`](precompile::PrecompileFn), it gets the [`CallInputs`] and the
+/// context, so it can access storage, emit logs, transfer balance and see the caller.
+///
+/// Precompile runs inside the journal checkpoint of the call frame: if it halts or reverts,
+/// all its state changes are reverted.
+///
+/// Implemented for closures that take [`StatefulPrecompileInput`].
+pub trait StatefulPrecompile<CTX: ContextTr> {
+    /// Executes the precompile.
+    ///
+    /// Gas used by the precompile, including state accesses, is reported in the output. If it is
+    /// over the gas limit of the call, the call halts with out of gas and its changes are
+    /// reverted.
+    fn call(&self, input: &mut StatefulPrecompileInput<'_, CTX>) -> PrecompileResult;
+}
+
+impl<CTX, F> StatefulPrecompile<CTX> for F
+where
+    CTX: ContextTr,
+    F: Fn(&mut StatefulPrecompileInput<'_, CTX>) -> PrecompileResult,
+{
+    fn call(&self, input: &mut StatefulPrecompileInput<'_, CTX>) -> PrecompileResult {
+        self(input)
+    }
+}
+
+/// Input of the [`StatefulPrecompile`].
+///
+/// Storage accesses and logs are done on the behalf of the target address, same as for the
+/// bytecode, so precompile called with `DELEGATECALL` uses the caller storage.
+#[derive(Debug)]
+pub struct StatefulPrecompileInput<'a, CTX> {
+    /// Call input data.
+    pub data: Bytes,
+    /// Call inputs.
+    pub inputs: &'a CallInputs,
+    /// EVM context.
+    pub context: &'a mut CTX,
+}
+
+impl<'a, CTX: ContextTr> StatefulPrecompileInput<'a, CTX> {
+    /// Creates new input, call input data is copied out of the shared memory.
+    pub fn new(context: &'a mut CTX, inputs: &'a CallInputs) -> Self {
+        Self {
+            data: inputs.input.bytes(context),
+            inputs,
+            context,
+        }
+    }
+
+    /// Returns the caller of the precompile.
+    pub fn caller(&self) -> Address {
+        self.inputs.caller
+    }
+
+    /// Returns the address whose state is accessed.
+    pub fn target_address(&self) -> Address {
+        self.inputs.target_address
+    }
+
+    /// Returns the call value.
+    pub fn value(&self) -> U256 {
+        self.inputs.value.get()
+    }
+
+    /// Returns the gas limit of the call.
+    pub fn gas_limit(&self) -> u64 {
+        self.inputs.gas_limit
+    }
+
+    /// Returns `true` if the call is static and state can't be changed.
+    pub fn is_static(&self) -> bool {
+        self.inputs.is_static
+    }
+
+    /// Loads the storage value of the target address.
+    pub fn sload(&mut self, key: StorageKey) -> Result<StorageValue, PrecompileError> {
+        let target = self.target_address();
+        self.context
+            .journal_mut()
+            .sload(target, key)
+            .map(|load| load.data)
+            .map_err(|e| PrecompileError::Fatal(e.to_string()))
+    }
+
+    /// Stores the storage value of the target address.
+    pub fn sstore(&mut self, key: StorageKey, value: StorageValue) -> Result<(), PrecompileError> {
+        self.check_not_static()?;
+        let target = self.target_address();
+        self.context
+            .journal_mut()
+            .sstore(target, key, value)
+            .map(|_| ())
+            .map_err(|e| PrecompileError::Fatal(e.to_string()))
+    }
+
+    /// Emits the log from the target address.
+    pub fn log(&mut self, topics: Vec<B256>, data: Bytes) -> Result<(), PrecompileError> {
+        self.check_not_static()?;
+        let target = self.target_address();
+        self.context
+            .journal_mut()
+            .log(Log::new_unchecked(target, topics, data));
+        Ok(())
+    }
+
+    /// Transfers balance from the target address.
+    pub fn transfer(&mut self, to: Address, value: U256) -> Result<(), PrecompileError> {
+        self.check_not_static()?;
+        let target = self.target_address();
+        match self
+            .context
+            .journal_mut()
+            .transfer(target, to, value)
+            .map_err(|e| PrecompileError::Fatal(e.to_string()))?
+        {
+            None => Ok(()),
+            Some(TransferError::OutOfFunds) => {
+                Err(PrecompileError::other_static("transfer out of funds"))
+            }
+            Some(TransferError::OverflowPayment) => {
+                Err(PrecompileError::other_static("transfer balance overflow"))
+            }
+            Some(TransferError::CreateCollision) => {
+                Err(PrecompileError::other_static("transfer create collision"))
+            }
+        }
+    }
+
+    fn check_not_static(&self) -> Result<(), PrecompileError> {
+        if self.is_static() {
+            return Err(PrecompileError::other_static(
+                "state change during static call",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// [`PrecompileProvider`] that runs registered [`StatefulPrecompile`]s and delegates other
+/// calls to the inner provider.
+pub struct StatefulPrecompiles<CTX: ContextTr, P = EthPrecompiles> {
+    /// Inner precompile provider.
+    pub inner: P,
+    /// Stateful precompiles by address.
+    precompiles: HashMap<Address, Box<dyn StatefulPrecompile<CTX> + Send + Sync>>,
+}
+
+impl<CTX: ContextTr, P> StatefulPrecompiles<CTX, P> {
+    /// Creates new provider on top of the inner provider.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            precompiles: HashMap::default(),
+        }
+    }
+
+    /// Registers the stateful precompile, it overrides inner precompile at the same address.
+    pub fn with_precompile(
+        mut self,
+        address: Address,
+        precompile: impl StatefulPrecompile<CTX> + Send + Sync + 'static,
+    ) -> Self {
+        self.insert(address, precompile);
+        self
+    }
+
+    /// Registers the stateful precompile, it overrides inner precompile at the same address.
+    pub fn insert(
+        &mut self,
+        address: Address,
+        precompile: impl StatefulPrecompile<CTX> + Send + Sync + 'static,
+    ) {
+        self.precompiles.insert(address, Box::new(precompile));
+    }
+
+    /// Removes the stateful precompile. Returns `true` if it was registered.
+    pub fn remove(&mut self, address: &Address) -> bool {
+        self.precompiles.remove(address).is_some()
+    }
+}
+
+impl<CTX: ContextTr, P: Default> Default for StatefulPrecompiles<CTX, P> {
+    fn default() -> Self {
+        Self::new(P::default())
+    }
+}
+
+impl<CTX: ContextTr, P: fmt::Debug> fmt::Debug for StatefulPrecompiles<CTX, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatefulPrecompiles")
+            .field("inner", &self.inner)
+            .field("precompiles", &self.precompiles.keys())
+            .finish()
+    }
+}
+
+impl<CTX, P> PrecompileProvider<CTX> for StatefulPrecompiles<CTX, P>
+where
+    CTX: ContextTr,
+    P: PrecompileProvider<CTX, Output = InterpreterResult>,
+{
+    type Output = InterpreterResult;
+
+    fn set_spec(&mut self, spec: <CTX::Cfg as Cfg>::Spec) -> bool {
+        self.inner.set_spec(spec)
+    }
+
+    fn run(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CallInputs,
+    ) -> Result<Option<InterpreterResult>, String> {
+        let Some(precompile) = self.precompiles.get(&inputs.bytecode_address) else {
+            return self.inner.run(context, inputs);
+        };
+        let exec_result = precompile
+            .call(&mut StatefulPrecompileInput::new(context, inputs))
+            .and_then(|output| {
+                // gas is charged after the state accesses, it is not checked by the precompile.
+                if output.gas_used > inputs.gas_limit {
+                    return Err(PrecompileError::OutOfGas);
+                }
+                Ok(output)
+            });
+        precompile_interpreter_result(context, exec_result, inputs.gas_limit).map(Some)
+    }
+
+    fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
+        Box::new(
+            self.precompiles
+                .keys()
+                .copied()
+                .chain(*self.inner.warm_addresses()),
+        )
+    }
+
+    fn contains(&self, address: &Address) -> bool {
+        self.precompiles.contains_key(address) || self.inner.contains(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecuteEvm, MainBuilder, MainContext};
+    use context::{
+        result::{ExecResultAndState, ExecutionResult, HaltReason, OutOfGasError},
+        Context, TxEnv,
+    };
+    use database::BenchmarkDB;
+    use precompile::PrecompileOutput;
+    use primitives::address;
+    use state::Bytecode;
+
+    const COUNTER: Address = address!("0x0000000000000000000000000000000000000a00");
+
+    /// Increments storage slot zero and logs the new value. Fails after the changes if called
+    /// with non-empty input.
+    fn counter<CTX: ContextTr>(input: &mut StatefulPrecompileInput<'_, CTX>) -> PrecompileResult {
+        let value = input.sload(U256::ZERO)? + U256::from(1);
+        input.sstore(U256::ZERO, value)?;
+        input.log(Vec::new(), value.to_be_bytes_vec().into())?;
+        if !input.data.is_empty() {
+            return Err(PrecompileError::other_static("counter failed"));
+        }
+        Ok(PrecompileOutput::new(
+            30_000,
+            value.to_be_bytes_vec().into(),
+        ))
+    }
+
+    fn transact_counter(input: Bytes) -> ExecResultAndState<ExecutionResult> {
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                StatefulPrecompiles::new(EthPrecompiles::default())
+                    .with_precompile(COUNTER, counter),
+            );
+        evm.transact(
+            TxEnv::builder_for_bench()
+                .to(COUNTER)
+                .data(input)
+                .build_fill(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn stateful_precompile() {
+        let output = transact_counter(Bytes::new());
+        assert!(output.result.is_success());
+        assert_eq!(
+            output.result.output().unwrap()[..],
+            U256::from(1).to_be_bytes::<32>()
+        );
+        assert_eq!(output.result.logs().len(), 1);
+        assert_eq!(output.result.logs()[0].address, COUNTER);
+
+        let account = output.state.get(&COUNTER).unwrap();
+        assert_eq!(
+            account.storage.get(&U256::ZERO).unwrap().present_value,
+            U256::from(1)
+        );
+    }
+
+    #[test]
+    fn error_reverts_state() {
+        let output = transact_counter(Bytes::from_static(&[1]));
+        assert_eq!(
+            output.result,
+            ExecutionResult::Halt {
+                reason: HaltReason::PrecompileErrorWithContext("counter failed".into()),
+                gas_used: output.result.gas_used(),
+            }
+        );
+        assert!(output.result.logs().is_empty());
+
+        // storage write is reverted.
+        let account = output.state.get(&COUNTER).unwrap();
+        assert!(account
+            .storage
+            .get(&U256::ZERO)
+            .is_none_or(|slot| !slot.is_changed()));
+    }
+
+    #[test]
+    fn gas_over_limit_halts() {
+        let mut evm = Context::mainnet()
+            .with_db(BenchmarkDB::new_bytecode(Bytecode::default()))
+            .build_mainnet()
+            .with_precompiles(
+                StatefulPrecompiles::new(EthPrecompiles::default()).with_precompile(
+                    COUNTER,
+                    |input: &mut StatefulPrecompileInput<'_, _>| {
+                        counter(input).map(|output| PrecompileOutput {
+                            gas_used: input.gas_limit() + 1,
+                            ..output
+                        })
+                    },
+                ),
+            );
+        let output = evm
+            .transact(
+                TxEnv::builder_for_bench()
+                    .to(COUNTER)
+                    .gas_limit(100_000)
+                    .build_fill(),
+            )
+            .unwrap();
+        assert_eq!(
+            output.result,
+            ExecutionResult::Halt {
+                reason: HaltReason::OutOfGas(OutOfGasError::Precompile),
+                gas_used: 100_000,
+            }
+        );
+        let account = output.state.get(&COUNTER).unwrap();
+        assert!(account
+            .storage
+            .get(&U256::ZERO)
+            .is_none_or(|slot| !slot.is_changed()));
+    }
+}
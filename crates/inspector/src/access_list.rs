@@ -0,0 +1,297 @@
+//! Access list generation with `eth_createAccessList` semantics.
+extern crate alloc;
+
+use crate::{InspectEvm, Inspector, InspectorEvmTr};
+use alloc::{collections::BTreeMap, collections::BTreeSet, vec::Vec};
+use context::{
+    result::ExecutionResult,
+    transaction::{AccessList, AccessListItem, AccessListItemTr, TransactionType},
+    TxEnv,
+};
+use handler::PrecompileProvider;
+use interpreter::{
+    interpreter_types::{InputsTr, Jumps, StackTr},
+    Interpreter, InterpreterTypes,
+};
+use primitives::{Address, HashSet, TxKind, B256};
+use state::bytecode::opcode;
+
+/// Inspector that collects addresses and storage keys accessed by the executed opcodes.
+///
+/// Accessed addresses are collected from `BALANCE`, `EXTCODESIZE`, `EXTCODECOPY`,
+/// `EXTCODEHASH`, `SELFDESTRUCT` and the call opcodes, storage keys from `SLOAD` and `SSTORE`.
+/// Excluded addresses are not collected on their own, their storage keys are, so the list keeps
+/// the address with the accessed keys.
+#[derive(Clone, Debug, Default)]
+pub struct AccessListInspector {
+    /// Addresses that are not added to the access list.
+    excluded: HashSet<Address>,
+    /// Collected addresses and storage keys.
+    access_list: BTreeMap<Address, BTreeSet<B256>>,
+}
+
+impl AccessListInspector {
+    /// Creates new inspector that starts from the given access list.
+    pub fn new(
+        access_list: impl IntoIterator<Item = impl AccessListItemTr>,
+        excluded: impl IntoIterator<Item = Address>,
+    ) -> Self {
+        let mut inspector = Self {
+            excluded: excluded.into_iter().collect(),
+            access_list: BTreeMap::new(),
+        };
+        for item in access_list {
+            let address = *item.address();
+            inspector.add_address(address);
+            for key in item.storage_slots() {
+                inspector.add_storage(address, *key);
+            }
+        }
+        inspector
+    }
+
+    /// Excludes the addresses and removes the collected addresses without storage keys.
+    pub fn exclude(&mut self, addresses: impl IntoIterator<Item = Address>) {
+        for address in addresses {
+            if self
+                .access_list
+                .get(&address)
+                .is_some_and(BTreeSet::is_empty)
+            {
+                self.access_list.remove(&address);
+            }
+            self.excluded.insert(address);
+        }
+    }
+
+    /// Returns the collected access list, sorted by address and storage key.
+    pub fn access_list(&self) -> AccessList {
+        AccessList(
+            self.access_list
+                .iter()
+                .map(|(address, keys)| AccessListItem {
+                    address: *address,
+                    storage_keys: keys.iter().copied().collect(),
+                })
+                .collect(),
+        )
+    }
+
+    fn add_address(&mut self, address: Address) {
+        if !self.excluded.contains(&address) {
+            self.access_list.entry(address).or_default();
+        }
+    }
+
+    fn add_storage(&mut self, address: Address, key: B256) {
+        self.access_list.entry(address).or_default().insert(key);
+    }
+}
+
+impl<CTX, INTR: InterpreterTypes> Inspector<CTX, INTR> for AccessListInspector {
+    fn step(&mut self, interp: &mut Interpreter<INTR>, _context: &mut CTX) {
+        let stack = interp.stack.data();
+        let peek = |n: usize| stack.len().checked_sub(n + 1).map(|i| stack[i]);
+        match interp.bytecode.opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                if let Some(key) = peek(0) {
+                    let address = interp.input.target_address();
+                    self.add_storage(address, B256::from(key));
+                }
+            }
+            opcode::BALANCE
+            | opcode::EXTCODESIZE
+            | opcode::EXTCODECOPY
+            | opcode::EXTCODEHASH
+            | opcode::SELFDESTRUCT => {
+                if let Some(address) = peek(0) {
+                    self.add_address(Address::from_word(address.into()));
+                }
+            }
+            opcode::CALL | opcode::CALLCODE | opcode::DELEGATECALL | opcode::STATICCALL => {
+                if let Some(address) = peek(1) {
+                    self.add_address(Address::from_word(address.into()));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Access list created by [`create_access_list`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessListResult<H> {
+    /// Access list of the transaction.
+    pub access_list: AccessList,
+    /// Gas used by the transaction with the access list.
+    pub gas_used: u64,
+    /// Result of the transaction with the access list.
+    pub result: ExecutionResult<H>,
+}
+
+/// Creates the access list of the transaction with `eth_createAccessList` semantics.
+///
+/// Transaction is executed with [`AccessListInspector`] and re-executed with the collected
+/// access list until the list does not change. The sender, the target or the created contract
+/// and the precompiles are excluded from the list, unless their storage keys are accessed. Collected entries are kept between the runs,
+/// including the entries of the access list of the transaction, so the list only grows.
+///
+/// State is not committed, legacy transactions are executed as EIP-2930 transactions. The
+/// inspector of the EVM is restored before returning, also on error.
+pub fn create_access_list<EVM, H>(
+    evm: &mut EVM,
+    mut tx: TxEnv,
+) -> Result<AccessListResult<H>, EVM::Error>
+where
+    EVM: InspectEvm<Tx = TxEnv, ExecutionResult = ExecutionResult<H>>
+        + InspectorEvmTr<Inspector = AccessListInspector>,
+{
+    if tx.tx_type == TransactionType::Legacy as u8 {
+        tx.tx_type = TransactionType::Eip2930 as u8;
+    }
+    let target = match tx.kind {
+        TxKind::Call(address) => address,
+        TxKind::Create => tx.caller.create(tx.nonce),
+    };
+    let inspector = AccessListInspector::new(&tx.access_list.0, [tx.caller, target]);
+
+    let original = core::mem::replace(evm.inspector(), inspector);
+    let result = access_list_fixpoint(evm, tx);
+    *evm.inspector() = original;
+    result
+}
+
+/// Re-executes the transaction with the collected access list until the list does not change.
+fn access_list_fixpoint<EVM, H>(
+    evm: &mut EVM,
+    mut tx: TxEnv,
+) -> Result<AccessListResult<H>, EVM::Error>
+where
+    EVM: InspectEvm<Tx = TxEnv, ExecutionResult = ExecutionResult<H>>
+        + InspectorEvmTr<Inspector = AccessListInspector>,
+{
+    loop {
+        let access_list = evm.inspector().access_list();
+        tx.access_list = access_list.clone();
+        let output = evm.inspect_tx(tx.clone())?;
+
+        // precompiles are known only after the spec is set by the execution.
+        let (_, precompiles) = evm.ctx_precompiles();
+        let precompiles = precompiles.warm_addresses().collect::<Vec<_>>();
+        let inspector = evm.inspector();
+        inspector.exclude(precompiles);
+
+        if inspector.access_list() == access_list {
+            return Ok(AccessListResult {
+                access_list,
+                gas_used: output.result.gas_used(),
+                result: output.result,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::Context;
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::address;
+    use state::bytecode::Bytecode;
+
+    #[test]
+    fn access_list_fixpoint() {
+        const OTHER: Address = address!("0x00000000000000000000000000000000000000aa");
+        let mut code = vec![opcode::PUSH20];
+        code.extend_from_slice(OTHER.as_slice());
+        code.extend_from_slice(&[
+            opcode::BALANCE,
+            opcode::POP,
+            // storage keys of the target are kept.
+            opcode::PUSH1,
+            1,
+            opcode::SLOAD,
+            opcode::POP,
+            // precompiles are excluded.
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::STOP,
+        ]);
+
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let mut evm = ctx.build_mainnet_with_inspector(AccessListInspector::default());
+        let tx = TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .gas_limit(100_000)
+            .build()
+            .unwrap();
+
+        let without_list = evm.inspect_tx(tx.clone()).unwrap().result.gas_used();
+        let result = create_access_list(&mut evm, tx).unwrap();
+        assert!(result.result.is_success());
+        assert_eq!(
+            result.access_list,
+            AccessList(vec![
+                AccessListItem {
+                    address: OTHER,
+                    storage_keys: vec![],
+                },
+                AccessListItem {
+                    address: BENCH_TARGET,
+                    storage_keys: vec![B256::with_last_byte(1)],
+                },
+            ])
+        );
+        // access list address costs 2400 gas and storage key 1900 gas, cold account access is
+        // 2500 gas and cold storage access 2000 gas more than warm.
+        assert_eq!(
+            result.gas_used,
+            without_list + 2400 - 2500 + 2400 + 1900 - 2000
+        );
+    }
+
+    #[test]
+    fn inspector_is_restored() {
+        const OTHER: Address = address!("0x00000000000000000000000000000000000000aa");
+        let mut code = vec![opcode::PUSH20];
+        code.extend_from_slice(OTHER.as_slice());
+        code.extend_from_slice(&[opcode::BALANCE, opcode::POP, opcode::STOP]);
+
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let list = [AccessListItem {
+            address: BENCH_TARGET,
+            storage_keys: vec![B256::ZERO],
+        }];
+        let inspector = AccessListInspector::new(&list, []);
+        let mut evm = ctx.build_mainnet_with_inspector(inspector.clone());
+        let tx = TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .gas_limit(100_000)
+            .build()
+            .unwrap();
+
+        let result = create_access_list(&mut evm, tx.clone()).unwrap();
+        assert_eq!(result.access_list.0.len(), 1);
+        assert_eq!(evm.inspector().access_list(), inspector.access_list());
+
+        // execution error.
+        let tx = TxEnv { nonce: 1, ..tx };
+        assert!(create_access_list(&mut evm, tx).is_err());
+        assert_eq!(evm.inspector().access_list(), inspector.access_list());
+    }
+}
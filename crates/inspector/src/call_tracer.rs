@@ -0,0 +1,558 @@
+//! CallTracer. Inspector that builds the call tree of the transaction.
+extern crate alloc;
+
+use crate::Inspector;
+use alloc::{format, string::String, vec::Vec};
+use context::{result::ExecutionResult, ContextTr, Transaction};
+use interpreter::{
+    CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, CreateScheme,
+    InstructionResult, InterpreterResult, InterpreterTypes,
+};
+use primitives::{Address, Bytes, Log, B256, U256};
+
+/// Selector of the `Error(string)` revert.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Type of the call frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum CallKind {
+    /// `CALL`.
+    Call,
+    /// `CALLCODE`.
+    CallCode,
+    /// `DELEGATECALL`.
+    DelegateCall,
+    /// `STATICCALL`.
+    StaticCall,
+    /// `CREATE`.
+    Create,
+    /// `CREATE2`.
+    Create2,
+    /// `SELFDESTRUCT`.
+    SelfDestruct,
+}
+
+impl CallKind {
+    /// Returns the opcode name of the frame type.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Call => "CALL",
+            Self::CallCode => "CALLCODE",
+            Self::DelegateCall => "DELEGATECALL",
+            Self::StaticCall => "STATICCALL",
+            Self::Create => "CREATE",
+            Self::Create2 => "CREATE2",
+            Self::SelfDestruct => "SELFDESTRUCT",
+        }
+    }
+}
+
+impl From<CallScheme> for CallKind {
+    fn from(scheme: CallScheme) -> Self {
+        match scheme {
+            CallScheme::Call => Self::Call,
+            CallScheme::CallCode => Self::CallCode,
+            CallScheme::DelegateCall => Self::DelegateCall,
+            CallScheme::StaticCall => Self::StaticCall,
+        }
+    }
+}
+
+impl From<CreateScheme> for CallKind {
+    fn from(scheme: CreateScheme) -> Self {
+        match scheme {
+            CreateScheme::Create2 { .. } => Self::Create2,
+            _ => Self::Create,
+        }
+    }
+}
+
+/// Log emitted inside the call frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CallLog {
+    /// Address of the contract that emitted the log.
+    pub address: Address,
+    /// Log topics.
+    pub topics: Vec<B256>,
+    /// Log data.
+    pub data: Bytes,
+    /// Number of the sub calls of the frame made before the log was emitted.
+    #[cfg_attr(feature = "serde", serde(with = "quantity"))]
+    pub position: u64,
+}
+
+/// Call frame in the format of the geth `callTracer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CallFrame {
+    /// Frame type.
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: CallKind,
+    /// Address of the caller.
+    pub from: Address,
+    /// Address of the callee or of the created contract.
+    ///
+    /// `None` if the creation failed before the address was derived.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub to: Option<Address>,
+    /// Transferred value, `None` for `STATICCALL` and `DELEGATECALL`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub value: Option<U256>,
+    /// Gas available to the frame.
+    #[cfg_attr(feature = "serde", serde(with = "quantity"))]
+    pub gas: u64,
+    /// Gas used by the frame, including its sub calls.
+    #[cfg_attr(feature = "serde", serde(with = "quantity"))]
+    pub gas_used: u64,
+    /// Call data or init code.
+    pub input: Bytes,
+    /// Return data or the deployed code.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub output: Option<Bytes>,
+    /// Error if the frame reverted or halted.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub error: Option<String>,
+    /// Decoded `Error(string)` revert reason.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub revert_reason: Option<String>,
+    /// Logs emitted by this frame, in emission order.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub logs: Vec<CallLog>,
+    /// Sub calls of this frame, in execution order.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    fn new(kind: CallKind, from: Address, to: Option<Address>, gas: u64, input: Bytes) -> Self {
+        Self {
+            kind,
+            from,
+            to,
+            value: None,
+            gas,
+            gas_used: 0,
+            input,
+            output: None,
+            error: None,
+            revert_reason: None,
+            logs: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if the frame did not revert or halt.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Sets output, gas used and error from the frame result.
+    fn fill_result(&mut self, result: &InterpreterResult) {
+        self.gas_used = result.gas.spent();
+        if !result.output.is_empty() {
+            self.output = Some(result.output.clone());
+        }
+        if result.result.is_ok() {
+            return;
+        }
+        self.error = Some(error_message(result.result));
+        if result.result.is_revert() {
+            self.revert_reason = decode_revert_reason(&result.output);
+        }
+        // logs of the failed frames are reverted.
+        self.clear_logs();
+    }
+
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        for call in &mut self.calls {
+            call.clear_logs();
+        }
+    }
+}
+
+/// Inspector that records the call tree of the transaction.
+///
+/// Every `CALL`, `CALLCODE`, `DELEGATECALL`, `STATICCALL`, `CREATE`, `CREATE2` and
+/// `SELFDESTRUCT` is recorded as a [`CallFrame`] with its inputs, outputs, gas, value, revert
+/// reason and logs. With the `serde` feature frames serialize to the JSON of the geth
+/// `callTracer` with `withLog` enabled.
+///
+/// Gas of the root frame is the transaction gas limit and its gas used includes the intrinsic
+/// gas. Refunds are not known to the inspector, use [`CallTracer::apply_result`] to set the
+/// gas used to the one of the transaction.
+#[derive(Clone, Debug, Default)]
+pub struct CallTracer {
+    /// Frames that are executing, last one is the current frame.
+    stack: Vec<CallFrame>,
+    /// Root frame of the last transaction.
+    root: Option<CallFrame>,
+    /// Intrinsic gas of the transaction, added to the gas used by the root frame.
+    intrinsic_gas: u64,
+}
+
+impl CallTracer {
+    /// Creates new call tracer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the root frame of the last traced transaction.
+    pub fn root(&self) -> Option<&CallFrame> {
+        self.root.as_ref()
+    }
+
+    /// Takes the root frame of the last traced transaction.
+    pub fn take_root(&mut self) -> Option<CallFrame> {
+        self.root.take()
+    }
+
+    /// Sets the gas used by the root frame to the gas used by the transaction, refunds included.
+    pub fn apply_result<H>(&mut self, result: &ExecutionResult<H>) {
+        if let Some(root) = &mut self.root {
+            root.gas_used = result.gas_used();
+        }
+    }
+
+    /// Clears the recorded frames.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+        self.root = None;
+        self.intrinsic_gas = 0;
+    }
+
+    /// Starts the new frame. For the root frame the transaction gas limit is used.
+    fn start_frame<CTX: ContextTr>(&mut self, context: &mut CTX, mut frame: CallFrame) {
+        if self.stack.is_empty() {
+            self.root = None;
+            let gas_limit = context.tx().gas_limit();
+            self.intrinsic_gas = gas_limit.saturating_sub(frame.gas);
+            frame.gas = gas_limit;
+        }
+        self.stack.push(frame);
+    }
+
+    /// Ends the current frame and attaches it to its parent.
+    fn end_frame(&mut self, result: &InterpreterResult, to: Option<Address>) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+        if to.is_some() {
+            frame.to = to;
+        }
+        frame.fill_result(result);
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => {
+                frame.gas_used += self.intrinsic_gas;
+                self.root = Some(frame);
+            }
+        }
+    }
+}
+
+impl<CTX: ContextTr, INTR: InterpreterTypes> Inspector<CTX, INTR> for CallTracer {
+    fn log(&mut self, _context: &mut CTX, log: Log) {
+        let Some(frame) = self.stack.last_mut() else {
+            return;
+        };
+        frame.logs.push(CallLog {
+            address: log.address,
+            topics: log.data.topics().to_vec(),
+            data: log.data.data,
+            position: frame.calls.len() as u64,
+        });
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let kind = CallKind::from(inputs.scheme);
+        // delegate calls and call codes execute the code of the callee in the caller context.
+        let (from, to) = match kind {
+            CallKind::DelegateCall => (inputs.target_address, inputs.bytecode_address),
+            CallKind::CallCode => (inputs.caller, inputs.bytecode_address),
+            _ => (inputs.caller, inputs.target_address),
+        };
+        let input = inputs.input.bytes(context);
+        let mut frame = CallFrame::new(kind, from, Some(to), inputs.gas_limit, input);
+        if !matches!(kind, CallKind::DelegateCall | CallKind::StaticCall) {
+            frame.value = Some(inputs.value.get());
+        }
+        self.start_frame(context, frame);
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.end_frame(&outcome.result, None);
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let mut frame = CallFrame::new(
+            inputs.scheme().into(),
+            inputs.caller(),
+            None,
+            inputs.gas_limit(),
+            inputs.init_code().clone(),
+        );
+        frame.value = Some(inputs.value());
+        self.start_frame(context, frame);
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        self.end_frame(&outcome.result, outcome.address);
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        let Some(parent) = self.stack.last_mut() else {
+            return;
+        };
+        let mut frame = CallFrame::new(
+            CallKind::SelfDestruct,
+            contract,
+            Some(target),
+            0,
+            Bytes::new(),
+        );
+        frame.value = Some(value);
+        parent.calls.push(frame);
+    }
+}
+
+/// Returns the geth error message of the failed frame.
+fn error_message(result: InstructionResult) -> String {
+    let message = match result {
+        InstructionResult::Revert => "execution reverted",
+        InstructionResult::OutOfGas
+        | InstructionResult::MemoryOOG
+        | InstructionResult::MemoryLimitOOG
+        | InstructionResult::PrecompileOOG
+        | InstructionResult::InvalidOperandOOG
+        | InstructionResult::ReentrancySentryOOG => "out of gas",
+        InstructionResult::OpcodeNotFound | InstructionResult::InvalidFEOpcode => "invalid opcode",
+        InstructionResult::InvalidJump => "invalid jump destination",
+        InstructionResult::StackUnderflow => "stack underflow",
+        InstructionResult::StackOverflow => "stack limit reached",
+        InstructionResult::CallTooDeep => "max call depth exceeded",
+        InstructionResult::OutOfFunds => "insufficient balance for transfer",
+        InstructionResult::CallNotAllowedInsideStatic
+        | InstructionResult::StateChangeDuringStaticCall => "write protection",
+        InstructionResult::CreateCollision => "contract address collision",
+        InstructionResult::CreateContractSizeLimit => "max code size exceeded",
+        InstructionResult::CreateContractStartingWithEF => "invalid code: must not begin with 0xef",
+        InstructionResult::NonceOverflow => "nonce uint64 overflow",
+        other => return format!("{other:?}"),
+    };
+    message.into()
+}
+
+/// Decodes the ABI encoded `Error(string)` revert reason.
+fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    let data = output.strip_prefix(&ERROR_SELECTOR)?;
+    let word = |offset: usize| -> Option<usize> {
+        let word = data.get(offset..offset.checked_add(32)?)?;
+        // offsets and lengths that do not fit into the last 8 bytes are invalid.
+        if word[..24].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        usize::try_from(u64::from_be_bytes(word[24..].try_into().ok()?)).ok()
+    };
+    let offset = word(0)?;
+    let len = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let reason = data.get(start..start.checked_add(len)?)?;
+    String::from_utf8(reason.to_vec()).ok()
+}
+
+/// Serializes `u64` as the hex quantity, e.g. `0x1a`.
+#[cfg(feature = "serde")]
+mod quantity {
+    use super::alloc::{format, string::String};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{value:#x}"))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let digits = value
+            .strip_prefix("0x")
+            .ok_or_else(|| D::Error::custom("missing 0x prefix"))?;
+        u64::from_str_radix(digits, 16).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::{bytes, TxKind};
+    use state::bytecode::{opcode, Bytecode};
+
+    #[test]
+    fn records_call_tree() {
+        // LOG0 with empty data, STATICCALL to the identity precompile, then REVERT with
+        // `Error("no")`.
+        let mut code = vec![
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::LOG0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::POP,
+        ];
+        let revert_data = bytes!(
+            "08c379a0"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000002"
+            "6e6f000000000000000000000000000000000000000000000000000000000000"
+        );
+        for (i, chunk) in revert_data.chunks(32).enumerate() {
+            code.push(opcode::PUSH32);
+            let mut word = [0u8; 32];
+            word[..chunk.len()].copy_from_slice(chunk);
+            code.extend_from_slice(&word);
+            code.extend_from_slice(&[opcode::PUSH1, (i * 32) as u8, opcode::MSTORE]);
+        }
+        code.extend_from_slice(&[
+            opcode::PUSH1,
+            revert_data.len() as u8,
+            opcode::PUSH1,
+            0,
+            opcode::REVERT,
+        ]);
+
+        let bytecode = Bytecode::new_raw(code.into());
+        let ctx = Context::mainnet().with_db(BenchmarkDB::new_bytecode(bytecode));
+        let mut evm = ctx.build_mainnet_with_inspector(CallTracer::new());
+        let result = evm
+            .inspect_one_tx(
+                TxEnv::builder()
+                    .caller(BENCH_CALLER)
+                    .kind(TxKind::Call(BENCH_TARGET))
+                    .gas_limit(100_000)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        evm.inspector.apply_result(&result);
+
+        let root = evm.inspector.root().unwrap();
+        assert_eq!(root.kind, CallKind::Call);
+        assert_eq!(root.from, BENCH_CALLER);
+        assert_eq!(root.to, Some(BENCH_TARGET));
+        assert_eq!(root.gas, 100_000);
+        assert_eq!(root.gas_used, result.gas_used());
+        assert_eq!(root.error.as_deref(), Some("execution reverted"));
+        assert_eq!(root.revert_reason.as_deref(), Some("no"));
+        assert_eq!(root.output.as_ref(), Some(&revert_data));
+        // log of the reverted frame is dropped.
+        assert!(root.logs.is_empty());
+
+        assert_eq!(root.calls.len(), 1);
+        let call = &root.calls[0];
+        assert_eq!(call.kind, CallKind::StaticCall);
+        assert_eq!(call.from, BENCH_TARGET);
+        assert_eq!(call.to, Some(Address::with_last_byte(4)));
+        assert_eq!(call.value, None);
+        assert!(call.is_success());
+    }
+
+    #[test]
+    fn logs_keep_position() {
+        let mut tracer = CallTracer::new();
+        tracer.stack.push(CallFrame::new(
+            CallKind::Call,
+            BENCH_CALLER,
+            Some(BENCH_TARGET),
+            0,
+            Bytes::new(),
+        ));
+        let mut ctx = Context::mainnet().with_db(BenchmarkDB::default());
+        let log = Log::new_unchecked(BENCH_TARGET, vec![B256::ZERO], Bytes::new());
+        Inspector::<_, interpreter::interpreter::EthInterpreter>::log(
+            &mut tracer,
+            &mut ctx,
+            log.clone(),
+        );
+        tracer.stack[0].calls.push(CallFrame::new(
+            CallKind::Call,
+            BENCH_TARGET,
+            Some(BENCH_CALLER),
+            0,
+            Bytes::new(),
+        ));
+        Inspector::<_, interpreter::interpreter::EthInterpreter>::log(&mut tracer, &mut ctx, log);
+
+        let positions: Vec<_> = tracer.stack[0].logs.iter().map(|l| l.position).collect();
+        assert_eq!(positions, vec![0, 1]);
+    }
+
+    #[cfg(feature = "tracer")]
+    #[test]
+    fn serializes_geth_shape() {
+        let mut frame = CallFrame::new(
+            CallKind::DelegateCall,
+            BENCH_CALLER,
+            Some(BENCH_TARGET),
+            0x100,
+            bytes!("01"),
+        );
+        frame.gas_used = 0x10;
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "DELEGATECALL");
+        assert_eq!(json["gas"], "0x100");
+        assert_eq!(json["gasUsed"], "0x10");
+        assert_eq!(json["input"], "0x01");
+        assert!(json.get("value").is_none());
+        assert!(json.get("calls").is_none());
+
+        let decoded: CallFrame = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, frame);
+    }
+}
@@ -0,0 +1,392 @@
+//! Chrome trace event writer with gas as the time axis.
+use crate::{
+    exclusive_gas::{frame_gas_spent, PendingStep},
+    inspectors::CallKind,
+    Inspector,
+};
+use interpreter::{
+    interpreter::EthInterpreter, interpreter_types::Jumps, CallInputs, CallOutcome, CreateInputs,
+    CreateOutcome, Interpreter, InterpreterResult,
+};
+use primitives::{Address, U256};
+use serde::Serialize;
+use state::bytecode::opcode::OpCode;
+use std::io::Write;
+
+/// Inspector that writes the call frames and optionally the opcodes as
+/// [Chrome trace events](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU)
+/// with gas as the time axis.
+///
+/// Events are complete (`"ph": "X"`) events in the JSON array format, written as soon as the
+/// frame or the opcode ends, so the output can be opened in Perfetto or `chrome://tracing`.
+/// Gas is accumulated over all inspected transactions, intrinsic gas is not traced.
+///
+/// Opcode spans do not include the gas spent by the calls they made, call and create opcodes
+/// span the frames they created.
+///
+/// Writing stops at the first error of the output, the error is returned by
+/// [`ChromeTracer::finish`].
+pub struct ChromeTracer {
+    output: Box<dyn Write>,
+    include_opcodes: bool,
+    /// Gas spent since the start of the trace.
+    clock: u64,
+    /// Whether an event was written and the next one needs a separator.
+    started: bool,
+    frames: Vec<TraceFrame>,
+    /// First error of the output.
+    error: Option<std::io::Error>,
+}
+
+impl std::fmt::Debug for ChromeTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChromeTracer")
+            .field("include_opcodes", &self.include_opcodes)
+            .field("clock", &self.clock)
+            .field("started", &self.started)
+            .field("frames", &self.frames)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct TraceFrame {
+    kind: CallKind,
+    from: Address,
+    to: Address,
+    value: U256,
+    start: u64,
+    pending: PendingStep<TraceStep>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TraceStep {
+    start: u64,
+    pc: usize,
+    opcode: u8,
+}
+
+#[derive(Serialize)]
+struct TraceEvent<'a, A> {
+    name: &'a str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u32,
+    args: A,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameArgs {
+    from: Address,
+    to: Address,
+    value: U256,
+    gas_limit: u64,
+    gas_used: u64,
+    result: String,
+}
+
+#[derive(Serialize)]
+struct OpcodeArgs {
+    pc: usize,
+}
+
+impl ChromeTracer {
+    /// Creates a new Chrome trace tracer with the given output writer, by first wrapping it in a
+    /// [`BufWriter`](std::io::BufWriter).
+    pub fn buffered(output: impl Write + 'static) -> Self {
+        Self::new(Box::new(std::io::BufWriter::new(output)))
+    }
+
+    /// Creates a new Chrome trace tracer with the given output writer.
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            include_opcodes: false,
+            clock: 0,
+            started: false,
+            frames: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Include a span for each opcode. This significantly increases the output size.
+    pub fn with_opcodes(mut self) -> Self {
+        self.include_opcodes = true;
+        self
+    }
+
+    /// Closes the JSON array and flushes the output.
+    ///
+    /// Returns the first error of the output if writing of an event failed. Trace viewers accept
+    /// the output without the closing bracket.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        if !self.started {
+            self.output.write_all(b"[")?;
+        }
+        self.output.write_all(b"]\n")?;
+        self.output.flush()
+    }
+
+    fn write_event<A: Serialize>(&mut self, event: &TraceEvent<'_, A>) {
+        if self.error.is_some() {
+            return;
+        }
+        let separator: &[u8] = if self.started { b",\n" } else { b"[\n" };
+        self.started = true;
+        let result = self
+            .output
+            .write_all(separator)
+            .and_then(|()| Ok(serde_json::to_writer(&mut *self.output, event)?));
+        self.error = result.err();
+    }
+
+    /// Advances the clock by the gas of the finished opcode and writes it.
+    fn write_step(&mut self, (step, gas): (TraceStep, u64)) {
+        self.clock += gas;
+        if !self.include_opcodes {
+            return;
+        }
+        self.write_event(&TraceEvent {
+            name: OpCode::name_by_op(step.opcode),
+            cat: "opcode",
+            ph: "X",
+            ts: step.start,
+            dur: self.clock - step.start,
+            pid: 0,
+            tid: 0,
+            args: OpcodeArgs { pc: step.pc },
+        });
+    }
+
+    fn push_frame(&mut self, kind: CallKind, from: Address, to: Address, value: U256) {
+        self.frames.push(TraceFrame {
+            kind,
+            from,
+            to,
+            value,
+            start: self.clock,
+            pending: PendingStep::default(),
+        });
+    }
+
+    fn end_frame(&mut self, result: &InterpreterResult, address: Option<Address>) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        if let Some(step) = frame.pending.finish_frame(result) {
+            self.write_step(step);
+        }
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        let gas_used = frame_gas_spent(result);
+        self.clock = self.clock.max(frame.start + gas_used);
+
+        let to = address.unwrap_or(frame.to);
+        let name = format!("{} {to}", frame.kind.as_str());
+        self.write_event(&TraceEvent {
+            name: &name,
+            cat: "call",
+            ph: "X",
+            ts: frame.start,
+            dur: self.clock - frame.start,
+            pid: 0,
+            tid: 0,
+            args: FrameArgs {
+                from: frame.from,
+                to,
+                value: frame.value,
+                gas_limit: result.gas.limit(),
+                gas_used,
+                result: format!("{:?}", result.result),
+            },
+        });
+
+        match self.frames.last_mut() {
+            Some(parent) => parent.pending.add_calls_gas(gas_used),
+            None if self.error.is_none() => self.error = self.output.flush().err(),
+            None => {}
+        }
+    }
+}
+
+impl<CTX> Inspector<CTX, EthInterpreter> for ChromeTracer {
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        let gas_remaining = interp.gas.remaining();
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        if let Some(step) = frame.pending.finish(gas_remaining) {
+            self.write_step(step);
+        }
+        let start = self.clock;
+        if let Some(frame) = self.frames.last_mut() {
+            frame.pending.start(
+                TraceStep {
+                    start,
+                    pc: interp.bytecode.pc(),
+                    opcode: interp.bytecode.opcode(),
+                },
+                gas_remaining,
+            );
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.push_frame(
+            inputs.scheme.into(),
+            inputs.caller,
+            inputs.target_address,
+            inputs.value.get(),
+        );
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.end_frame(&outcome.result, None);
+    }
+
+    fn create(&mut self, _context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.push_frame(
+            inputs.scheme().into(),
+            inputs.caller(),
+            Address::ZERO,
+            inputs.value(),
+        );
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        self.end_frame(&outcome.result, outcome.address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::{address, TxKind};
+    use serde_json::Value;
+    use state::bytecode::{opcode, Bytecode};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_gas_spans() {
+        const IDENTITY: Address = address!("0x0000000000000000000000000000000000000004");
+        let code = [
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::POP,
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ];
+        let buffer = SharedBuffer::default();
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let mut evm = ctx.build_mainnet_with_inspector(
+            ChromeTracer::new(Box::new(buffer.clone())).with_opcodes(),
+        );
+        let tx = TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .gas_limit(100_000)
+            .build()
+            .unwrap();
+        assert!(evm.inspect_tx(tx).unwrap().result.is_success());
+        evm.inspector.finish().unwrap();
+
+        let events: Vec<Value> = serde_json::from_slice(&buffer.0.borrow()).unwrap();
+        let span = |name: &str| {
+            let event = events.iter().find(|e| e["name"] == name).unwrap();
+            (
+                event["ts"].as_u64().unwrap(),
+                event["dur"].as_u64().unwrap(),
+            )
+        };
+        assert_eq!(events.len(), 14);
+        assert_eq!(span(&format!("CALL {BENCH_TARGET}")), (0, 22_240));
+        // identity precompile is called after five pushes and `GAS`.
+        assert_eq!(span(&format!("STATICCALL {IDENTITY}")), (17, 15));
+        assert_eq!(span("STATICCALL"), (17, 115));
+        assert_eq!(span("SSTORE"), (140, 22_100));
+        assert_eq!(span("STOP"), (22_240, 0));
+
+        let root = events.last().unwrap();
+        assert_eq!(root["cat"], "call");
+        assert_eq!(root["args"]["gasUsed"], 22_240);
+        assert_eq!(root["args"]["result"], "Stop");
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("closed"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_error_is_returned() {
+        let ctx = Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(
+            [opcode::STOP].into(),
+        )));
+        let mut evm = ctx.build_mainnet_with_inspector(ChromeTracer::new(Box::new(FailingWriter)));
+        let tx = TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .gas_limit(100_000)
+            .build()
+            .unwrap();
+        assert!(evm.inspect_tx(tx).unwrap().result.is_success());
+
+        let error = evm.inspector.finish().unwrap_err();
+        assert_eq!(error.to_string(), "closed");
+    }
+}
@@ -0,0 +1,411 @@
+//! Coverage of the executed bytecode with lcov output.
+extern crate alloc;
+
+use crate::Inspector;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::{fmt, fmt::Write};
+use interpreter::{
+    interpreter::EthInterpreter, interpreter_types::Jumps, CallInputs, CallOutcome, CreateInputs,
+    CreateOutcome, Interpreter,
+};
+use primitives::{Bytes, HashMap, B256};
+use state::bytecode::opcode;
+
+/// Number of times a `JUMPI` jumped and continued to the next opcode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// Number of times the jump was taken.
+    pub taken: u64,
+    /// Number of times the jump was skipped.
+    pub skipped: u64,
+}
+
+/// Coverage of a bytecode.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeCoverage {
+    /// Number of executions keyed by the program counter.
+    pub hits: BTreeMap<usize, u64>,
+    /// Branches of the executed `JUMPI` opcodes keyed by the program counter.
+    pub branches: BTreeMap<usize, BranchCoverage>,
+}
+
+/// Inspector that records the executed bytecode offsets and `JUMPI` branches per code hash.
+///
+/// Coverage is accumulated over all inspected transactions and can be mapped to the source
+/// files with solc source maps in the lcov format, see [`CoverageInspector::lcov`].
+#[derive(Clone, Debug, Default)]
+pub struct CoverageInspector {
+    coverage: HashMap<B256, CodeCoverage>,
+    /// Code hashes of the frames, `None` for frames without code.
+    frames: Vec<Option<B256>>,
+}
+
+impl CoverageInspector {
+    /// Create a new CoverageInspector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the coverage keyed by the code hash.
+    pub fn coverage(&self) -> &HashMap<B256, CodeCoverage> {
+        &self.coverage
+    }
+
+    /// Clear the recorded coverage.
+    pub fn clear(&mut self) {
+        self.coverage.clear();
+        self.frames.clear();
+    }
+
+    /// Returns the coverage of the source files in the lcov format.
+    ///
+    /// Opcodes are mapped to the lines of the source files with the source maps of the contracts,
+    /// a line is hit as many times as its most executed opcode. Every executed `JUMPI` is reported
+    /// as a branch with the taken and skipped blocks. Opcodes that are not mapped to any of the
+    /// files are ignored.
+    pub fn lcov(&self, contracts: &[ContractSources], files: &BTreeMap<u32, SourceFile>) -> String {
+        let mut records: BTreeMap<u32, FileRecord> = BTreeMap::new();
+        for contract in contracts {
+            let coverage = self.coverage.get(&contract.code_hash);
+            let pcs = instruction_offsets(&contract.bytecode);
+            for (entry, pc) in contract.source_map.entries.iter().zip(pcs) {
+                let Some(file) = entry.file.and_then(|id| files.get(&id).map(|f| (id, f))) else {
+                    continue;
+                };
+                let line = file.1.line(entry.offset);
+                let record = records.entry(file.0).or_default();
+                let hits = coverage.and_then(|c| c.hits.get(&pc)).copied();
+                let line_hits = record.lines.entry(line).or_default();
+                *line_hits = (*line_hits).max(hits.unwrap_or_default());
+                if let Some(branch) = coverage.and_then(|c| c.branches.get(&pc)) {
+                    record.branches.insert((line, pc), *branch);
+                }
+            }
+        }
+
+        let mut output = String::new();
+        for (id, record) in records {
+            let _ = record.write(&mut output, &files[&id].path);
+        }
+        output
+    }
+}
+
+impl<CTX> Inspector<CTX, EthInterpreter> for CoverageInspector {
+    fn initialize_interp(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        if let Some(frame) = self.frames.last_mut() {
+            *frame = Some(interp.bytecode.get_or_calculate_hash());
+        }
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        let Some(Some(code_hash)) = self.frames.last() else {
+            return;
+        };
+        let coverage = self.coverage.entry(*code_hash).or_default();
+        let pc = interp.bytecode.pc();
+        *coverage.hits.entry(pc).or_default() += 1;
+
+        if interp.bytecode.opcode() == opcode::JUMPI {
+            // condition is the second stack item.
+            let stack = interp.stack.data();
+            if let Some(condition) = stack.len().checked_sub(2).map(|i| stack[i]) {
+                let branch = coverage.branches.entry(pc).or_default();
+                if condition.is_zero() {
+                    branch.skipped += 1;
+                } else {
+                    branch.taken += 1;
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.frames.push(None);
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, _outcome: &mut CallOutcome) {
+        self.frames.pop();
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.frames.push(None);
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        _outcome: &mut CreateOutcome,
+    ) {
+        self.frames.pop();
+    }
+}
+
+/// Coverage of a source file.
+#[derive(Debug, Default)]
+struct FileRecord {
+    /// Hits keyed by the line number.
+    lines: BTreeMap<usize, u64>,
+    /// Branches keyed by the line number and the program counter of the `JUMPI`.
+    branches: BTreeMap<(usize, usize), BranchCoverage>,
+}
+
+impl FileRecord {
+    fn write(&self, output: &mut String, path: &str) -> fmt::Result {
+        writeln!(output, "TN:")?;
+        writeln!(output, "SF:{path}")?;
+        let mut branches_hit = 0;
+        for ((line, pc), branch) in &self.branches {
+            writeln!(output, "BRDA:{line},{pc},0,{}", branch.taken)?;
+            writeln!(output, "BRDA:{line},{pc},1,{}", branch.skipped)?;
+            branches_hit += (branch.taken > 0) as usize + (branch.skipped > 0) as usize;
+        }
+        writeln!(output, "BRF:{}", self.branches.len() * 2)?;
+        writeln!(output, "BRH:{branches_hit}")?;
+        for (line, hits) in &self.lines {
+            writeln!(output, "DA:{line},{hits}")?;
+        }
+        writeln!(output, "LF:{}", self.lines.len())?;
+        let lines_hit = self.lines.values().filter(|hits| **hits > 0).count();
+        writeln!(output, "LH:{lines_hit}")?;
+        writeln!(output, "end_of_record")
+    }
+}
+
+/// Returns the program counters of the instructions of the bytecode.
+fn instruction_offsets(bytecode: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut pc = 0;
+    core::iter::from_fn(move || {
+        let opcode = *bytecode.get(pc)?;
+        let offset = pc;
+        pc += 1;
+        if (opcode::PUSH1..=opcode::PUSH32).contains(&opcode) {
+            pc += (opcode - opcode::PUSH0) as usize;
+        }
+        Some(offset)
+    })
+}
+
+/// Source file referenced by the source maps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Path of the file reported in the lcov output.
+    pub path: String,
+    /// Byte offsets of the line starts.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    /// Creates the source file from its path and content.
+    pub fn new(path: impl Into<String>, content: &str) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            path: path.into(),
+            line_starts,
+        }
+    }
+
+    /// Returns the line number of the byte offset, starting at one.
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+}
+
+/// Bytecode of a contract with its solc source map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractSources {
+    /// Hash of the executed code, it differs from the hash of the compiled bytecode
+    /// if the contract has immutables.
+    pub code_hash: B256,
+    /// Bytecode the source map was generated for.
+    pub bytecode: Bytes,
+    /// Source map of the bytecode.
+    pub source_map: SourceMap,
+}
+
+/// Jump type of the source map entry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceJump {
+    /// Jump into a function.
+    In,
+    /// Return from a function.
+    Out,
+    /// Regular jump or any other instruction.
+    #[default]
+    Regular,
+}
+
+/// Entry of a solc source map for one instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceMapEntry {
+    /// Byte offset of the source range.
+    pub offset: usize,
+    /// Length of the source range.
+    pub length: usize,
+    /// Index of the source file, `None` if the instruction is not mapped to a file.
+    pub file: Option<u32>,
+    /// Jump type.
+    pub jump: SourceJump,
+    /// Modifier depth.
+    pub modifier_depth: u32,
+}
+
+/// Solc source map, one entry per instruction.
+///
+/// See <https://docs.soliditylang.org/en/latest/internals/source_mappings.html>.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Entries of the instructions.
+    pub entries: Vec<SourceMapEntry>,
+}
+
+/// Error of the source map parsing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceMapError {
+    /// Index of the invalid entry.
+    pub entry: usize,
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid source map entry {}", self.entry)
+    }
+}
+
+impl core::error::Error for SourceMapError {}
+
+impl SourceMap {
+    /// Parses the compressed source map in the `s:l:f:j:m` format.
+    ///
+    /// Empty fields and missing fields repeat the value of the previous entry.
+    pub fn parse(source_map: &str) -> Result<Self, SourceMapError> {
+        let mut entries = Vec::new();
+        let mut last = SourceMapEntry::default();
+        if source_map.is_empty() {
+            return Ok(Self { entries });
+        }
+        for (index, entry) in source_map.split(';').enumerate() {
+            let error = SourceMapError { entry: index };
+            for (field, value) in entry.split(':').enumerate() {
+                if value.is_empty() {
+                    continue;
+                }
+                match field {
+                    0 => last.offset = value.parse().map_err(|_| error.clone())?,
+                    1 => last.length = value.parse().map_err(|_| error.clone())?,
+                    2 => {
+                        let file: i64 = value.parse().map_err(|_| error.clone())?;
+                        last.file = u32::try_from(file).ok();
+                    }
+                    3 => {
+                        last.jump = match value {
+                            "i" => SourceJump::In,
+                            "o" => SourceJump::Out,
+                            "-" => SourceJump::Regular,
+                            _ => return Err(error),
+                        }
+                    }
+                    4 => last.modifier_depth = value.parse().map_err(|_| error.clone())?,
+                    _ => return Err(error),
+                }
+            }
+            entries.push(last);
+        }
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::TxKind;
+    use state::bytecode::Bytecode;
+
+    #[test]
+    fn source_map_parse() {
+        let map = SourceMap::parse("0:5:0:-;;6:5:1:i;:::o:1;0:0:-1").unwrap();
+        let entry = |offset, length, file, jump, modifier_depth| SourceMapEntry {
+            offset,
+            length,
+            file,
+            jump,
+            modifier_depth,
+        };
+        assert_eq!(
+            map.entries,
+            vec![
+                entry(0, 5, Some(0), SourceJump::Regular, 0),
+                entry(0, 5, Some(0), SourceJump::Regular, 0),
+                entry(6, 5, Some(1), SourceJump::In, 0),
+                entry(6, 5, Some(1), SourceJump::Out, 1),
+                entry(0, 0, None, SourceJump::Out, 1),
+            ]
+        );
+        assert_eq!(SourceMap::parse("0:1;x"), Err(SourceMapError { entry: 1 }));
+    }
+
+    #[test]
+    fn coverage_lcov() {
+        // jumps over the second line if calldata is not empty.
+        let code = [
+            opcode::CALLDATASIZE,
+            opcode::PUSH1,
+            7,
+            opcode::JUMPI,
+            opcode::PUSH1,
+            0,
+            opcode::POP,
+            opcode::JUMPDEST,
+            opcode::STOP,
+        ];
+        let bytecode = Bytecode::new_raw(code.into());
+        let ctx = Context::mainnet().with_db(BenchmarkDB::new_bytecode(bytecode.clone()));
+        let mut evm = ctx.build_mainnet_with_inspector(CoverageInspector::new());
+        let tx = TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .data(Bytes::from_static(&[1]))
+            .gas_limit(100_000)
+            .build()
+            .unwrap();
+        assert!(evm.inspect_tx(tx).unwrap().result.is_success());
+
+        let inspector = &evm.inspector;
+        let coverage = &inspector.coverage()[&bytecode.hash_slow()];
+        assert_eq!(
+            coverage.hits,
+            BTreeMap::from([(0, 1), (1, 1), (3, 1), (7, 1), (8, 1)])
+        );
+        assert_eq!(
+            coverage.branches,
+            BTreeMap::from([(
+                3,
+                BranchCoverage {
+                    taken: 1,
+                    skipped: 0
+                }
+            )])
+        );
+
+        let contracts = [ContractSources {
+            code_hash: bytecode.hash_slow(),
+            bytecode: code.into(),
+            source_map: SourceMap::parse("0:5:0:-;;;6:5;;12:5;0:0:-1").unwrap(),
+        }];
+        let files = BTreeMap::from([(0, SourceFile::new("test.sol", "line1\nline2\nline3\n"))]);
+        assert_eq!(
+            inspector.lcov(&contracts, &files),
+            "TN:\nSF:test.sol\nBRDA:1,3,0,1\nBRDA:1,3,1,0\nBRF:2\nBRH:1\n\
+             DA:1,1\nDA:2,0\nDA:3,1\nLF:3\nLH:2\nend_of_record\n"
+        );
+    }
+}
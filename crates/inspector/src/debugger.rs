@@ -0,0 +1,565 @@
+//! Step debugger that pauses the execution at breakpoints.
+extern crate alloc;
+
+use crate::{Inspector, JournalCheckpointExt, JournalExt};
+use alloc::{collections::VecDeque, vec::Vec};
+use context::{journaled_state::JournalCheckpoint, ContextTr, JournalEntry};
+use core::cell::Ref;
+use interpreter::{
+    interpreter::EthInterpreter,
+    interpreter_types::{InputsTr, Jumps, ReturnData},
+    CallInputs, CallOutcome, CreateInputs, CreateOutcome, Gas, Interpreter, Stack,
+};
+use primitives::{Address, Bytes, U256};
+use state::bytecode::opcode;
+
+/// Condition on which the [`Debugger`] pauses before executing an opcode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Program counter in the code of the address, in any code if the address is not set.
+    Pc {
+        /// Address of the executed code.
+        address: Option<Address>,
+        /// Program counter.
+        pc: usize,
+    },
+    /// Every execution of the opcode.
+    Opcode(u8),
+    /// First opcode of the frames executing the code of the address.
+    Address(Address),
+    /// First opcode of the frames at the call depth, top level frame is at depth zero.
+    Depth(usize),
+    /// `SSTORE` to the storage of the address and to the key, any if not set.
+    StorageWrite {
+        /// Address of the written storage.
+        address: Option<Address>,
+        /// Written storage key.
+        key: Option<U256>,
+    },
+}
+
+impl Breakpoint {
+    fn matches(&self, interp: &Interpreter<EthInterpreter>, depth: usize, first: bool) -> bool {
+        match *self {
+            Self::Pc { address, pc } => {
+                interp.bytecode.pc() == pc && address.is_none_or(|a| a == code_address(interp))
+            }
+            Self::Opcode(opcode) => interp.bytecode.opcode() == opcode,
+            Self::Address(address) => first && code_address(interp) == address,
+            Self::Depth(at) => first && depth == at,
+            Self::StorageWrite { address, key } => {
+                interp.bytecode.opcode() == opcode::SSTORE
+                    && address.is_none_or(|a| a == interp.input.target_address())
+                    && key.is_none_or(|k| interp.stack.data().last() == Some(&k))
+            }
+        }
+    }
+}
+
+/// Reason of the pause.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseReason {
+    /// Breakpoint at the index was hit.
+    Breakpoint(usize),
+    /// Step requested by the previous command finished.
+    Step,
+    /// Execution was rewound.
+    Rewind,
+}
+
+/// Command that resumes the paused [`Debugger`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Pauses at the next opcode, including opcodes of the called contracts.
+    Step,
+    /// Pauses at the next opcode of the current or a parent frame.
+    StepOver,
+    /// Pauses at the next opcode of a parent frame.
+    StepOut,
+    /// Pauses at the next breakpoint.
+    Continue,
+    /// Rewinds the current frame by the number of executed opcodes and pauses.
+    ///
+    /// Ignored if the history of the frame is shorter.
+    Rewind(usize),
+}
+
+/// State of the paused execution passed to the handler of the [`Debugger`].
+#[derive(Debug)]
+pub struct Paused<'a, CTX> {
+    /// Reason of the pause.
+    pub reason: PauseReason,
+    /// Call depth, top level frame is at depth zero.
+    pub depth: usize,
+    /// Number of opcodes the current frame can be rewound by.
+    pub history: usize,
+    /// Paused interpreter.
+    pub interpreter: &'a Interpreter<EthInterpreter>,
+    /// Context of the execution.
+    pub context: &'a CTX,
+}
+
+impl<CTX: ContextTr<Journal: JournalExt>> Paused<'_, CTX> {
+    /// Returns the program counter.
+    pub fn pc(&self) -> usize {
+        self.interpreter.bytecode.pc()
+    }
+
+    /// Returns the opcode that is executed next.
+    pub fn opcode(&self) -> u8 {
+        self.interpreter.bytecode.opcode()
+    }
+
+    /// Returns the address of the executed code.
+    pub fn address(&self) -> Address {
+        code_address(self.interpreter)
+    }
+
+    /// Returns the stack, top of the stack is the last element.
+    pub fn stack(&self) -> &[U256] {
+        self.interpreter.stack.data()
+    }
+
+    /// Returns the memory of the current frame.
+    pub fn memory(&self) -> Ref<'_, [u8]> {
+        self.interpreter.memory.context_memory()
+    }
+
+    /// Returns the return data of the last call.
+    pub fn return_data(&self) -> &Bytes {
+        self.interpreter.return_data.buffer()
+    }
+
+    /// Returns the remaining gas.
+    pub fn gas_remaining(&self) -> u64 {
+        self.interpreter.gas.remaining()
+    }
+
+    /// Returns the journal entries of the transaction.
+    pub fn journal(&self) -> &[JournalEntry] {
+        self.context.journal_ref().journal()
+    }
+}
+
+/// State of the interpreter before an executed opcode.
+#[derive(Clone, Debug)]
+struct Snapshot {
+    pc: usize,
+    gas: Gas,
+    stack: Stack,
+    memory: Vec<u8>,
+    return_data: Bytes,
+    checkpoint: JournalCheckpoint,
+}
+
+#[derive(Clone, Debug, Default)]
+struct DebugFrame {
+    started: bool,
+    history: VecDeque<Snapshot>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Step,
+    StepOver(usize),
+    StepOut(usize),
+    Continue,
+}
+
+/// Inspector that pauses the execution at breakpoints and steps through it.
+///
+/// On every pause the handler is called with the [`Paused`] state and returns the
+/// [`DebugCommand`] that resumes the execution. Execution starts in the
+/// [`DebugCommand::Continue`] mode, [`Breakpoint::Depth`] at zero pauses on the first opcode.
+///
+/// If history is enabled, the state of the interpreter and a journal checkpoint are recorded
+/// before every opcode, so the current frame can be rewound, including the state changes of
+/// the frames it called. Every recorded opcode clones the stack and the memory of the frame, so
+/// the number of recorded opcodes is bounded by [`Debugger::with_history`].
+#[derive(Clone, Debug)]
+pub struct Debugger<F> {
+    handler: F,
+    breakpoints: Vec<Breakpoint>,
+    history_limit: usize,
+    /// Number of snapshots recorded in all frames.
+    history_len: usize,
+    mode: Mode,
+    frames: Vec<DebugFrame>,
+}
+
+impl<F> Debugger<F> {
+    /// Create a new Debugger that calls the handler on every pause.
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            breakpoints: Vec::new(),
+            history_limit: 0,
+            history_len: 0,
+            mode: Mode::Continue,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Adds the breakpoint.
+    pub fn with_breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+
+    /// Keeps the state of the last `limit` executed opcodes for rewinding, history is disabled by
+    /// default.
+    ///
+    /// Limit is shared by all frames, the oldest snapshots of the outer frames are dropped first.
+    pub fn with_history(mut self, limit: usize) -> Self {
+        self.history_limit = limit;
+        self
+    }
+
+    /// Get the breakpoints.
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Get the mutable breakpoints, index of the breakpoint is reported in [`PauseReason`].
+    pub fn breakpoints_mut(&mut self) -> &mut Vec<Breakpoint> {
+        &mut self.breakpoints
+    }
+
+    /// Get the handler.
+    pub fn handler(&self) -> &F {
+        &self.handler
+    }
+
+    /// Get the mutable handler.
+    pub fn handler_mut(&mut self) -> &mut F {
+        &mut self.handler
+    }
+
+    /// Consumes the debugger and returns the handler.
+    pub fn into_handler(self) -> F {
+        self.handler
+    }
+
+    /// Restores the state of the current frame before the `steps` last executed opcodes.
+    fn rewind<CTX: ContextTr<Journal: JournalCheckpointExt>>(
+        &mut self,
+        steps: usize,
+        interp: &mut Interpreter<EthInterpreter>,
+        context: &mut CTX,
+    ) -> bool {
+        let Some(frame) = self.frames.last_mut() else {
+            return false;
+        };
+        if steps == 0 || steps > frame.history.len() {
+            return false;
+        }
+        frame.history.truncate(frame.history.len() - steps + 1);
+        let Some(snapshot) = frame.history.pop_back() else {
+            return false;
+        };
+        self.history_len -= steps;
+
+        context
+            .journal_mut()
+            .revert_to_checkpoint(snapshot.checkpoint);
+
+        interp.bytecode.absolute_jump(snapshot.pc);
+        interp.gas = snapshot.gas;
+        interp.stack = snapshot.stack;
+        interp.memory.resize(snapshot.memory.len());
+        interp.memory.set(0, &snapshot.memory);
+        interp.return_data.set_buffer(snapshot.return_data);
+        true
+    }
+
+    /// Pops the finished frame and drops its history.
+    fn pop_frame(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.history_len -= frame.history.len();
+        }
+    }
+}
+
+impl<CTX, F> Inspector<CTX, EthInterpreter> for Debugger<F>
+where
+    CTX: ContextTr<Journal: JournalExt + JournalCheckpointExt>,
+    F: FnMut(&Paused<'_, CTX>) -> DebugCommand,
+{
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, context: &mut CTX) {
+        let depth = self.frames.len().saturating_sub(1);
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        let first = !core::mem::replace(&mut frame.started, true);
+
+        let mut reason = self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.matches(interp, depth, first))
+            .map(PauseReason::Breakpoint);
+        if reason.is_none() {
+            reason = match self.mode {
+                Mode::Step => Some(PauseReason::Step),
+                Mode::StepOver(at) if depth <= at => Some(PauseReason::Step),
+                Mode::StepOut(at) if depth < at => Some(PauseReason::Step),
+                _ => None,
+            };
+        }
+
+        while let Some(pause) = reason.take() {
+            let paused = Paused {
+                reason: pause,
+                depth,
+                history: self.frames.last().map_or(0, |f| f.history.len()),
+                interpreter: interp,
+                context,
+            };
+            match (self.handler)(&paused) {
+                DebugCommand::Step => self.mode = Mode::Step,
+                DebugCommand::StepOver => self.mode = Mode::StepOver(depth),
+                DebugCommand::StepOut => self.mode = Mode::StepOut(depth),
+                DebugCommand::Continue => self.mode = Mode::Continue,
+                DebugCommand::Rewind(steps) => {
+                    reason = Some(match self.rewind(steps, interp, context) {
+                        true => PauseReason::Rewind,
+                        false => pause,
+                    });
+                }
+            }
+        }
+
+        if self.history_limit == 0 {
+            return;
+        }
+        if self.history_len == self.history_limit {
+            if let Some(frame) = self.frames.iter_mut().find(|f| !f.history.is_empty()) {
+                frame.history.pop_front();
+                self.history_len -= 1;
+            }
+        }
+        let snapshot = Snapshot {
+            pc: interp.bytecode.pc(),
+            gas: interp.gas,
+            stack: interp.stack.clone(),
+            memory: interp.memory.context_memory().to_vec(),
+            return_data: interp.return_data.buffer().clone(),
+            checkpoint: context.journal_ref().current_checkpoint(),
+        };
+        if let Some(frame) = self.frames.last_mut() {
+            frame.history.push_back(snapshot);
+            self.history_len += 1;
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.frames.push(DebugFrame::default());
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, _outcome: &mut CallOutcome) {
+        self.pop_frame();
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.frames.push(DebugFrame::default());
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        _outcome: &mut CreateOutcome,
+    ) {
+        self.pop_frame();
+    }
+}
+
+/// Returns the address of the code executed by the interpreter.
+fn code_address(interp: &Interpreter<EthInterpreter>) -> Address {
+    interp
+        .input
+        .bytecode_address()
+        .copied()
+        .unwrap_or_else(|| interp.input.target_address())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::TxKind;
+    use state::bytecode::Bytecode;
+
+    fn tx() -> TxEnv {
+        TxEnv::builder()
+            .caller(BENCH_CALLER)
+            .kind(TxKind::Call(BENCH_TARGET))
+            .gas_limit(100_000)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn step_over_and_out() {
+        // calls itself with one byte of calldata, the nested call stops.
+        let code = [
+            opcode::CALLDATASIZE,
+            opcode::PUSH1,
+            17,
+            opcode::JUMPI,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            0,
+            opcode::ADDRESS,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::POP,
+            opcode::STOP,
+            opcode::JUMPDEST,
+            opcode::STOP,
+        ];
+        let run = |commands: &[DebugCommand]| {
+            let ctx = Context::mainnet()
+                .with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+            let mut commands = commands.iter().copied();
+            let mut pauses = Vec::new();
+            let debugger = Debugger::new(|paused: &Paused<'_, _>| {
+                pauses.push((paused.pc(), paused.depth, paused.reason));
+                commands.next().unwrap_or(DebugCommand::Continue)
+            })
+            .with_breakpoint(Breakpoint::Opcode(opcode::STATICCALL));
+            let mut evm = ctx.build_mainnet_with_inspector(debugger);
+            assert!(evm.inspect_tx(tx()).unwrap().result.is_success());
+            drop(evm);
+            pauses
+        };
+
+        assert_eq!(
+            run(&[DebugCommand::Step, DebugCommand::StepOut]),
+            vec![
+                (14, 0, PauseReason::Breakpoint(0)),
+                (0, 1, PauseReason::Step),
+                (15, 0, PauseReason::Step),
+            ]
+        );
+        assert_eq!(
+            run(&[DebugCommand::StepOver, DebugCommand::Step]),
+            vec![
+                (14, 0, PauseReason::Breakpoint(0)),
+                (15, 0, PauseReason::Step),
+                (16, 0, PauseReason::Step),
+            ]
+        );
+    }
+
+    #[test]
+    fn rewind_storage_write() {
+        let code = [
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::PUSH1,
+            2,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ];
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let expected = ctx
+            .clone()
+            .build_mainnet_with_inspector(crate::NoOpInspector)
+            .inspect_tx(tx())
+            .unwrap();
+
+        let mut pauses = Vec::new();
+        let mut rewound = false;
+        let debugger = Debugger::new(|paused: &Paused<'_, _>| {
+            pauses.push((paused.pc(), paused.reason, paused.journal().len()));
+            if paused.pc() == 9 && !rewound {
+                rewound = true;
+                return DebugCommand::Rewind(4);
+            }
+            DebugCommand::Continue
+        })
+        .with_breakpoint(Breakpoint::StorageWrite {
+            address: Some(BENCH_TARGET),
+            key: Some(U256::ZERO),
+        })
+        .with_history(16);
+        let mut evm = ctx.build_mainnet_with_inspector(debugger);
+        let output = evm.inspect_tx(tx()).unwrap();
+        drop(evm);
+
+        // first write is rewound before it is executed again.
+        let journal_len = pauses[0].2;
+        assert_eq!(
+            pauses,
+            vec![
+                (4, PauseReason::Breakpoint(0), journal_len),
+                (9, PauseReason::Breakpoint(0), journal_len + 2),
+                (2, PauseReason::Rewind, journal_len),
+                (4, PauseReason::Breakpoint(0), journal_len),
+                (9, PauseReason::Breakpoint(0), journal_len + 2),
+            ]
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn history_limit() {
+        let code = [
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::PUSH1,
+            2,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ];
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+
+        let mut pauses = Vec::new();
+        let debugger = Debugger::new(|paused: &Paused<'_, _>| {
+            pauses.push((paused.pc(), paused.reason, paused.history));
+            match pauses.len() {
+                1 => DebugCommand::Rewind(4),
+                _ => DebugCommand::Continue,
+            }
+        })
+        .with_breakpoint(Breakpoint::Pc {
+            address: None,
+            pc: 9,
+        })
+        .with_history(3);
+        let mut evm = ctx.build_mainnet_with_inspector(debugger);
+        assert!(evm.inspect_tx(tx()).unwrap().result.is_success());
+        drop(evm);
+
+        // only three opcodes are kept, rewinding by four is ignored.
+        assert_eq!(
+            pauses,
+            vec![
+                (9, PauseReason::Breakpoint(0), 3),
+                (9, PauseReason::Breakpoint(0), 3),
+            ]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Gas of the opcodes without the gas spent by the frames they created.
+use interpreter::InterpreterResult;
+
+/// Returns the gas spent by the frame.
+pub(crate) fn frame_gas_spent(result: &InterpreterResult) -> u64 {
+    // Remaining gas of the failed frames is not returned to the caller.
+    if result.result.is_ok_or_revert() {
+        result.gas.spent()
+    } else {
+        result.gas.limit()
+    }
+}
+
+/// Opcode whose gas is known only when the next opcode of its frame starts or the frame ends.
+#[derive(Clone, Debug)]
+pub(crate) struct PendingStep<T> {
+    /// Pending opcode with the remaining gas before it.
+    step: Option<(T, u64)>,
+    /// Gas spent by the frames created by the pending opcode.
+    calls_gas: u64,
+}
+
+impl<T> Default for PendingStep<T> {
+    fn default() -> Self {
+        Self {
+            step: None,
+            calls_gas: 0,
+        }
+    }
+}
+
+impl<T> PendingStep<T> {
+    /// Starts the opcode with the remaining gas before it.
+    pub(crate) fn start(&mut self, step: T, gas_remaining: u64) {
+        self.step = Some((step, gas_remaining));
+        self.calls_gas = 0;
+    }
+
+    /// Returns the pending opcode.
+    #[cfg(feature = "std")]
+    pub(crate) fn get_mut(&mut self) -> Option<&mut T> {
+        self.step.as_mut().map(|(step, _)| step)
+    }
+
+    /// Records the gas spent by a frame created by the pending opcode.
+    pub(crate) fn add_calls_gas(&mut self, gas: u64) {
+        self.calls_gas += gas;
+    }
+
+    /// Finishes the pending opcode with the remaining gas after it, returns the opcode with its
+    /// gas.
+    pub(crate) fn finish(&mut self, gas_remaining: u64) -> Option<(T, u64)> {
+        let (step, gas_before) = self.step.take()?;
+        let gas = gas_before
+            .saturating_sub(gas_remaining)
+            .saturating_sub(core::mem::take(&mut self.calls_gas));
+        Some((step, gas))
+    }
+
+    /// Finishes the last opcode of the frame that ended with the result.
+    pub(crate) fn finish_frame(&mut self, result: &InterpreterResult) -> Option<(T, u64)> {
+        self.finish(result.gas.limit() - frame_gas_spent(result))
+    }
+}
@@ -0,0 +1,176 @@
+//! Collector of the 4-byte call selectors and the event signatures.
+extern crate alloc;
+
+use crate::Inspector;
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use context::{ContextTr, LocalContextTr};
+use interpreter::{CallInput, CallInputs, CallOutcome};
+use primitives::{hex, Address, FixedBytes, HashMap, Log, B256};
+
+/// Selectors and event signatures used by a contract.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractSignatures {
+    /// Number of calls keyed by the selector and the size of the calldata after it.
+    pub selectors: HashMap<(FixedBytes<4>, usize), u64>,
+    /// Number of emitted logs keyed by their first topic.
+    pub events: HashMap<B256, u64>,
+}
+
+/// Inspector that collects the 4-byte selectors of the calls and the first topics of the logs,
+/// aggregated per contract.
+///
+/// Calls are attributed to the address of the executed code and logs to the emitting contract.
+/// Calls with less than four bytes of calldata, calls to precompiles and contract creations are
+/// skipped. Calls and logs of the reverted frames are collected too.
+#[derive(Clone, Debug, Default)]
+pub struct FourByteInspector {
+    contracts: HashMap<Address, ContractSignatures>,
+    /// Selectors of the calls that are not finished yet.
+    pending: Vec<Option<(FixedBytes<4>, usize)>>,
+}
+
+impl FourByteInspector {
+    /// Create a new FourByteInspector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the collected signatures keyed by the contract address.
+    pub fn contracts(&self) -> &HashMap<Address, ContractSignatures> {
+        &self.contracts
+    }
+
+    /// Returns the number of calls keyed by `0x{selector}-{calldata size}` of geth `4byteTracer`.
+    pub fn geth_format(&self) -> BTreeMap<String, u64> {
+        let mut output = BTreeMap::new();
+        for ((selector, size), count) in self.contracts.values().flat_map(|c| &c.selectors) {
+            *output
+                .entry(format!("{}-{size}", hex::encode_prefixed(selector)))
+                .or_default() += count;
+        }
+        output
+    }
+
+    /// Clear all collected signatures.
+    pub fn clear(&mut self) {
+        self.contracts.clear();
+        self.pending.clear();
+    }
+}
+
+impl<CTX: ContextTr> Inspector<CTX> for FourByteInspector {
+    fn log(&mut self, _context: &mut CTX, log: Log) {
+        if let Some(topic) = log.topics().first() {
+            *self
+                .contracts
+                .entry(log.address)
+                .or_default()
+                .events
+                .entry(*topic)
+                .or_default() += 1;
+        }
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        // Only the selector is read, calldata is not copied.
+        let selector = match &inputs.input {
+            CallInput::Bytes(bytes) => bytes.get(..4).map(FixedBytes::from_slice),
+            CallInput::SharedBuffer(range) => {
+                let start = range.start;
+                (range.len() >= 4)
+                    .then(|| context.local().shared_memory_buffer_slice(start..start + 4))
+                    .flatten()
+                    .map(|bytes| FixedBytes::from_slice(&bytes))
+            }
+        };
+        self.pending
+            .push(selector.map(|selector| (selector, inputs.input.len() - 4)));
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        let Some(Some(key)) = self.pending.pop() else {
+            return;
+        };
+        if outcome.was_precompile_called {
+            return;
+        }
+        *self
+            .contracts
+            .entry(inputs.bytecode_address)
+            .or_default()
+            .selectors
+            .entry(key)
+            .or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::{b256, fixed_bytes, Bytes, TxKind};
+    use state::bytecode::{opcode, Bytecode};
+
+    #[test]
+    fn collect_selectors_and_events() {
+        let topic = b256!("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+        let mut code = vec![opcode::PUSH32];
+        code.extend_from_slice(topic.as_slice());
+        code.extend_from_slice(&[
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::LOG1,
+            // call to identity precompile is skipped.
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            8,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::STOP,
+        ]);
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let mut evm = ctx.build_mainnet_with_inspector(FourByteInspector::new());
+        for data in [
+            &[0xa9, 0x05, 0x9c, 0xbb, 1, 2][..],
+            &[0xa9, 0x05, 0x9c, 0xbb],
+            &[1],
+        ] {
+            let tx = TxEnv::builder()
+                .caller(BENCH_CALLER)
+                .kind(TxKind::Call(BENCH_TARGET))
+                .data(Bytes::copy_from_slice(data))
+                .gas_limit(100_000)
+                .build()
+                .unwrap();
+            assert!(evm.inspect_tx(tx).unwrap().result.is_success());
+        }
+
+        let inspector = &evm.inspector;
+        let target = &inspector.contracts()[&BENCH_TARGET];
+        assert_eq!(inspector.contracts().len(), 1);
+        assert_eq!(target.selectors.len(), 2);
+        assert_eq!(target.selectors[&(fixed_bytes!("0xa9059cbb"), 2)], 1);
+        assert_eq!(target.events[&topic], 3);
+        assert_eq!(
+            inspector.geth_format(),
+            BTreeMap::from([
+                (String::from("0xa9059cbb-0"), 1),
+                (String::from("0xa9059cbb-2"), 1)
+            ])
+        );
+    }
+}
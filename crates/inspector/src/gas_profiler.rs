@@ -0,0 +1,435 @@
+//! Gas profiler that attributes gas to opcodes, contracts and call stacks.
+extern crate alloc;
+
+use crate::{
+    exclusive_gas::{frame_gas_spent, PendingStep},
+    Inspector,
+};
+use alloc::{format, string::String, vec::Vec};
+use core::{fmt::Write, time::Duration};
+use interpreter::{
+    interpreter::EthInterpreter,
+    interpreter_types::{InputsTr, Jumps},
+    CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter, InterpreterResult,
+};
+use primitives::{Address, HashMap, B256, KECCAK_EMPTY};
+use state::bytecode::opcode::OpCode;
+
+/// Gas and time attributed to an opcode, location, contract or call stack.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProfileStats {
+    /// Number of executed opcodes, or calls for contracts without code.
+    pub count: u64,
+    /// Gas spent, without the gas spent by the nested calls.
+    pub gas: u64,
+    /// Wall-clock time spent, recorded only if timing is enabled.
+    pub time: Duration,
+}
+
+impl ProfileStats {
+    fn record(&mut self, gas: u64, time: Duration) {
+        self.count += 1;
+        self.gas += gas;
+        self.time += time;
+    }
+}
+
+/// Location of the executed opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProfileKey {
+    /// Address of the executed code.
+    pub address: Address,
+    /// Hash of the executed code.
+    pub code_hash: B256,
+    /// Program counter.
+    pub pc: usize,
+    /// Executed opcode.
+    pub opcode: u8,
+}
+
+/// Profiled opcode.
+#[derive(Clone, Debug)]
+struct ProfiledStep {
+    pc: usize,
+    opcode: u8,
+    time: Duration,
+}
+
+#[derive(Clone, Debug)]
+struct ProfiledFrame {
+    address: Address,
+    code_hash: B256,
+    /// Index of the call stack, resolved on the first use.
+    stack: Option<usize>,
+    pending: PendingStep<ProfiledStep>,
+}
+
+impl ProfiledFrame {
+    fn new(address: Address) -> Self {
+        Self {
+            address,
+            code_hash: KECCAK_EMPTY,
+            stack: None,
+            pending: PendingStep::default(),
+        }
+    }
+}
+
+/// Inspector that profiles gas spent by opcodes, contracts and call stacks.
+///
+/// Gas of an opcode is the gas it spent without the gas spent by the calls it made,
+/// so that gas of all profiled opcodes and calls to contracts without code sums to the
+/// execution gas. Intrinsic gas and refunds are not attributed.
+///
+/// Profiles are accumulated over all inspected transactions until [`GasProfiler::clear`].
+#[derive(Clone, Debug)]
+pub struct GasProfiler {
+    #[cfg(feature = "std")]
+    timing: bool,
+    #[cfg(feature = "std")]
+    step_start: Option<std::time::Instant>,
+    frames: Vec<ProfiledFrame>,
+    opcodes: [ProfileStats; 256],
+    locations: HashMap<ProfileKey, ProfileStats>,
+    contracts: HashMap<Address, ProfileStats>,
+    /// Call stacks in collapsed format, indexed by their id.
+    stacks: Vec<String>,
+    stack_ids: HashMap<String, usize>,
+    /// Stats of the opcodes keyed by the call stack id, `None` for contracts without code.
+    stack_stats: HashMap<(usize, Option<u8>), ProfileStats>,
+}
+
+impl Default for GasProfiler {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "std")]
+            timing: false,
+            #[cfg(feature = "std")]
+            step_start: None,
+            frames: Vec::new(),
+            opcodes: [ProfileStats::default(); 256],
+            locations: HashMap::default(),
+            contracts: HashMap::default(),
+            stacks: Vec::new(),
+            stack_ids: HashMap::default(),
+            stack_stats: HashMap::default(),
+        }
+    }
+}
+
+impl GasProfiler {
+    /// Create a new GasProfiler.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables recording of the wall-clock time spent by the opcodes.
+    #[cfg(feature = "std")]
+    pub fn with_timing(mut self) -> Self {
+        self.timing = true;
+        self
+    }
+
+    /// Get the stats of a specific opcode.
+    pub fn opcode(&self, opcode: u8) -> &ProfileStats {
+        &self.opcodes[opcode as usize]
+    }
+
+    /// Returns the executed opcodes with their stats, sorted by gas in descending order.
+    pub fn opcode_histogram(&self) -> Vec<(u8, ProfileStats)> {
+        let mut histogram = (0..=u8::MAX)
+            .map(|opcode| (opcode, self.opcodes[opcode as usize]))
+            .filter(|(_, stats)| stats.count > 0)
+            .collect::<Vec<_>>();
+        histogram.sort_by(|(a_op, a), (b_op, b)| b.gas.cmp(&a.gas).then(a_op.cmp(b_op)));
+        histogram
+    }
+
+    /// Get the stats of the executed opcodes keyed by their location.
+    pub fn locations(&self) -> &HashMap<ProfileKey, ProfileStats> {
+        &self.locations
+    }
+
+    /// Get the stats of the contracts keyed by the address of the executed code.
+    pub fn contracts(&self) -> &HashMap<Address, ProfileStats> {
+        &self.contracts
+    }
+
+    /// Returns the gas of the call stacks in collapsed format used by flamegraph tools.
+    ///
+    /// Each line contains the addresses of the executed code separated by `;`, ending with
+    /// the opcode name, followed by the spent gas. Lines are sorted.
+    pub fn collapsed_stacks(&self) -> String {
+        self.collapse(|stats| stats.gas as u128)
+    }
+
+    /// Returns the wall-clock time of the call stacks in nanoseconds in collapsed format.
+    ///
+    /// Time is recorded only if timing is enabled.
+    pub fn collapsed_stacks_by_time(&self) -> String {
+        self.collapse(|stats| stats.time.as_nanos())
+    }
+
+    /// Clear all profiles.
+    pub fn clear(&mut self) {
+        *self = Self {
+            #[cfg(feature = "std")]
+            timing: self.timing,
+            ..Self::default()
+        };
+    }
+
+    fn collapse(&self, weight: impl Fn(&ProfileStats) -> u128) -> String {
+        let mut lines = self
+            .stack_stats
+            .iter()
+            .map(|((stack, opcode), stats)| {
+                let stack = &self.stacks[*stack];
+                let line = match opcode {
+                    Some(opcode) => format!("{stack};{}", OpCode::name_by_op(*opcode)),
+                    None => stack.clone(),
+                };
+                (line, weight(stats))
+            })
+            .filter(|(_, weight)| *weight > 0)
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        let mut output = String::new();
+        for (line, weight) in lines {
+            let _ = writeln!(output, "{line} {weight}");
+        }
+        output
+    }
+
+    /// Returns the id of the call stack of the frame.
+    fn stack_id(&mut self, index: usize) -> usize {
+        if let Some(id) = self.frames[index].stack {
+            return id;
+        }
+        let address = self.frames[index].address;
+        let stack = match index {
+            0 => format!("{address}"),
+            _ => {
+                let parent = self.stack_id(index - 1);
+                format!("{};{address}", self.stacks[parent])
+            }
+        };
+        let id = match self.stack_ids.get(&stack) {
+            Some(id) => *id,
+            None => {
+                self.stack_ids.insert(stack.clone(), self.stacks.len());
+                self.stacks.push(stack);
+                self.stacks.len() - 1
+            }
+        };
+        self.frames[index].stack = Some(id);
+        id
+    }
+
+    /// Records the stats of the current frame.
+    fn record(&mut self, step: Option<(usize, u8)>, gas: u64, time: Duration) {
+        let Some(index) = self.frames.len().checked_sub(1) else {
+            return;
+        };
+        let stack = self.stack_id(index);
+        let frame = &self.frames[index];
+        self.contracts
+            .entry(frame.address)
+            .or_default()
+            .record(gas, time);
+        if let Some((pc, opcode)) = step {
+            self.opcodes[opcode as usize].record(gas, time);
+            self.locations
+                .entry(ProfileKey {
+                    address: frame.address,
+                    code_hash: frame.code_hash,
+                    pc,
+                    opcode,
+                })
+                .or_default()
+                .record(gas, time);
+        }
+        self.stack_stats
+            .entry((stack, step.map(|(_, opcode)| opcode)))
+            .or_default()
+            .record(gas, time);
+    }
+
+    /// Records the finished step of the current frame.
+    fn record_step(&mut self, (step, gas): (ProfiledStep, u64)) {
+        self.record(Some((step.pc, step.opcode)), gas, step.time);
+    }
+
+    fn end_frame(&mut self, result: &InterpreterResult) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        let spent = frame_gas_spent(result);
+        match frame.pending.finish_frame(result) {
+            Some(step) => self.record_step(step),
+            None => self.record(None, spent, Duration::ZERO),
+        }
+        self.frames.pop();
+        if let Some(parent) = self.frames.last_mut() {
+            parent.pending.add_calls_gas(spent);
+        }
+    }
+}
+
+impl<CTX> Inspector<CTX, EthInterpreter> for GasProfiler {
+    fn initialize_interp(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.address = interp
+                .input
+                .bytecode_address()
+                .copied()
+                .unwrap_or_else(|| interp.input.target_address());
+            frame.code_hash = interp.bytecode.get_or_calculate_hash();
+        }
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        let gas_remaining = interp.gas.remaining();
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        if let Some(step) = frame.pending.finish(gas_remaining) {
+            self.record_step(step);
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.pending.start(
+                ProfiledStep {
+                    pc: interp.bytecode.pc(),
+                    opcode: interp.bytecode.opcode(),
+                    time: Duration::ZERO,
+                },
+                gas_remaining,
+            );
+        }
+        #[cfg(feature = "std")]
+        if self.timing {
+            self.step_start = Some(std::time::Instant::now());
+        }
+    }
+
+    fn step_end(&mut self, _interp: &mut Interpreter<EthInterpreter>, _context: &mut CTX) {
+        #[cfg(feature = "std")]
+        if let Some(start) = self.step_start.take() {
+            if let Some(step) = self.frames.last_mut().and_then(|f| f.pending.get_mut()) {
+                step.time += start.elapsed();
+            }
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.frames
+            .push(ProfiledFrame::new(inputs.bytecode_address));
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut CTX, _inputs: &CallInputs, outcome: &mut CallOutcome) {
+        self.end_frame(&outcome.result);
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        // Created address is set when the interpreter is initialized.
+        self.frames.push(ProfiledFrame::new(Address::ZERO));
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut CTX,
+        _inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        if let (Some(frame), Some(address)) = (self.frames.last_mut(), outcome.address) {
+            if frame.stack.is_none() {
+                frame.address = address;
+            }
+        }
+        self.end_frame(&outcome.result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::{address, TxKind};
+    use state::bytecode::{opcode, Bytecode};
+
+    #[test]
+    fn profile_gas() {
+        const IDENTITY: Address = address!("0x0000000000000000000000000000000000000004");
+        let code = [
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::POP,
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ];
+        let ctx =
+            Context::mainnet().with_db(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+        let mut evm = ctx.build_mainnet_with_inspector(GasProfiler::new().with_timing());
+        let result = evm
+            .inspect_one_tx(
+                TxEnv::builder()
+                    .caller(BENCH_CALLER)
+                    .kind(TxKind::Call(BENCH_TARGET))
+                    .gas_limit(100_000)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        let profiler = &evm.inspector;
+
+        assert_eq!(result.gas_used(), 21_000 + 22_240);
+        assert_eq!(
+            profiler.collapsed_stacks(),
+            format!(
+                "{BENCH_TARGET};{IDENTITY} 15\n\
+                 {BENCH_TARGET};GAS 2\n\
+                 {BENCH_TARGET};POP 2\n\
+                 {BENCH_TARGET};PUSH1 21\n\
+                 {BENCH_TARGET};SSTORE 22100\n\
+                 {BENCH_TARGET};STATICCALL 100\n"
+            )
+        );
+
+        let histogram = profiler.opcode_histogram();
+        assert_eq!(histogram[0].0, opcode::SSTORE);
+        assert_eq!(profiler.opcode(opcode::PUSH1).count, 7);
+        assert_eq!(profiler.opcode(opcode::STOP).gas, 0);
+        assert_eq!(profiler.contracts()[&IDENTITY].gas, 15);
+        assert_eq!(profiler.contracts()[&BENCH_TARGET].gas, 22_225);
+        assert_eq!(
+            profiler.locations()[&ProfileKey {
+                address: BENCH_TARGET,
+                code_hash: Bytecode::new_raw(code.into()).hash_slow(),
+                pc: 17,
+                opcode: opcode::SSTORE,
+            }]
+                .gas,
+            22_100
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use auto_impl::auto_impl;
-use context::{Database, Journal, JournalEntry};
+use context::{journaled_state::JournalCheckpoint, Database, Journal, JournalEntry};
 use interpreter::{
     interpreter::EthInterpreter, CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter,
     InterpreterTypes,
@@ -215,3 +215,27 @@ impl<DB: Database> JournalExt for Journal<DB> {
         &mut self.state
     }
 }
+
+/// Extends the journal with checkpoints that do not change its depth, used by the
+/// [`Debugger`](crate::inspectors::Debugger) to rewind the execution.
+#[auto_impl(&mut, Box)]
+pub trait JournalCheckpointExt {
+    /// Get the checkpoint of the current state, journal depth is not changed.
+    fn current_checkpoint(&self) -> JournalCheckpoint;
+
+    /// Reverts the state to the checkpoint taken with
+    /// [`JournalCheckpointExt::current_checkpoint`], journal depth is not changed.
+    fn revert_to_checkpoint(&mut self, checkpoint: JournalCheckpoint);
+}
+
+impl<DB: Database> JournalCheckpointExt for Journal<DB> {
+    #[inline]
+    fn current_checkpoint(&self) -> JournalCheckpoint {
+        self.inner.current_checkpoint()
+    }
+
+    #[inline]
+    fn revert_to_checkpoint(&mut self, checkpoint: JournalCheckpoint) {
+        self.inner.revert_to_checkpoint(checkpoint)
+    }
+}
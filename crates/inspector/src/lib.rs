@@ -5,17 +5,28 @@
 #![cfg_attr(not(test), warn(unused_crate_dependencies))]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod access_list;
+mod call_tracer;
+#[cfg(feature = "tracer")]
+mod chrome_trace;
 mod count_inspector;
+mod coverage;
+mod debugger;
 #[cfg(feature = "tracer")]
 mod eip3155;
 mod either;
+mod exclusive_gas;
+mod four_byte;
 mod gas;
+mod gas_profiler;
 /// Handler implementations for inspector integration.
 pub mod handler;
 mod inspect;
 mod inspector;
 mod mainnet_inspect;
+mod multi;
 mod noop;
+mod prestate;
 /// Test inspector for testing EVM execution.
 pub mod test_inspector;
 mod traits;
@@ -25,15 +36,29 @@ mod inspector_tests;
 
 /// Inspector implementations.
 pub mod inspectors {
+    pub use super::access_list::AccessListInspector;
+    pub use super::call_tracer::{CallFrame, CallKind, CallLog, CallTracer};
+    #[cfg(feature = "tracer")]
+    pub use super::chrome_trace::ChromeTracer;
+    pub use super::coverage::{
+        BranchCoverage, CodeCoverage, ContractSources, CoverageInspector, SourceFile, SourceJump,
+        SourceMap, SourceMapEntry, SourceMapError,
+    };
+    pub use super::debugger::{Breakpoint, DebugCommand, Debugger, PauseReason, Paused};
     #[cfg(feature = "tracer")]
     pub use super::eip3155::TracerEip3155;
+    pub use super::four_byte::{ContractSignatures, FourByteInspector};
     pub use super::gas::GasInspector;
+    pub use super::gas_profiler::{GasProfiler, ProfileKey, ProfileStats};
+    pub use super::prestate::{PrestateAccount, PrestateTracer, StateDiff};
 }
 
+pub use access_list::{create_access_list, AccessListResult};
 pub use count_inspector::CountInspector;
 pub use handler::{inspect_instructions, InspectorHandler};
 pub use inspect::{InspectCommitEvm, InspectEvm, InspectSystemCallEvm};
 pub use inspector::*;
+pub use multi::{MultiInspector, OverridePolicy};
 pub use noop::NoOpInspector;
 pub use test_inspector::{InspectorEvent, InterpreterState, StepRecord, TestInspector};
 pub use traits::*;
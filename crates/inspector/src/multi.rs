@@ -0,0 +1,284 @@
+//! Inspector that dispatches the hooks to a list of inspectors chosen at runtime.
+extern crate alloc;
+
+use crate::inspector::Inspector;
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use interpreter::{
+    interpreter::EthInterpreter, CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter,
+    InterpreterTypes,
+};
+use primitives::{Address, Log, U256};
+
+/// Policy that selects the outcome when several inspectors override a call or a create.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverridePolicy {
+    /// Outcome of the first inspector in the list is used.
+    #[default]
+    First,
+    /// Outcome of the last inspector in the list is used.
+    Last,
+}
+
+impl OverridePolicy {
+    fn merge<T>(self, current: Option<T>, outcome: Option<T>) -> Option<T> {
+        match self {
+            Self::First => current.or(outcome),
+            Self::Last => outcome.or(current),
+        }
+    }
+}
+
+/// Inspector that calls every hook on a list of boxed inspectors, in order.
+///
+/// Unlike the tuple and [`Either`](either::Either) implementations, the inspectors are chosen at
+/// runtime. Inspectors can be borrowed, so their results stay available after the execution.
+///
+/// [`Inspector::call`] and [`Inspector::create`] are called on every inspector even if one of them
+/// returned an outcome, so each inspector sees the matching `call_end`/`create_end`. The returned
+/// outcome is selected by the [`OverridePolicy`] and the ends are called with it.
+pub struct MultiInspector<'a, CTX, INTR: InterpreterTypes = EthInterpreter> {
+    inspectors: Vec<Box<dyn Inspector<CTX, INTR> + 'a>>,
+    policy: OverridePolicy,
+}
+
+impl<CTX, INTR: InterpreterTypes> fmt::Debug for MultiInspector<'_, CTX, INTR> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiInspector")
+            .field("inspectors", &self.inspectors.len())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl<CTX, INTR: InterpreterTypes> Default for MultiInspector<'_, CTX, INTR> {
+    fn default() -> Self {
+        Self {
+            inspectors: Vec::new(),
+            policy: OverridePolicy::default(),
+        }
+    }
+}
+
+impl<'a, CTX, INTR: InterpreterTypes> MultiInspector<'a, CTX, INTR> {
+    /// Create a new MultiInspector without inspectors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the policy that selects the overriding outcome.
+    pub fn with_policy(mut self, policy: OverridePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Adds the inspector to the end of the list.
+    pub fn with_inspector(mut self, inspector: impl Inspector<CTX, INTR> + 'a) -> Self {
+        self.push(inspector);
+        self
+    }
+
+    /// Adds the inspector to the end of the list.
+    pub fn push(&mut self, inspector: impl Inspector<CTX, INTR> + 'a) {
+        self.inspectors.push(Box::new(inspector));
+    }
+
+    /// Returns the policy that selects the overriding outcome.
+    pub fn policy(&self) -> OverridePolicy {
+        self.policy
+    }
+
+    /// Returns the number of inspectors.
+    pub fn len(&self) -> usize {
+        self.inspectors.len()
+    }
+
+    /// Returns `true` if there are no inspectors.
+    pub fn is_empty(&self) -> bool {
+        self.inspectors.is_empty()
+    }
+
+    /// Returns the inspectors.
+    pub fn inspectors(&self) -> &[Box<dyn Inspector<CTX, INTR> + 'a>] {
+        &self.inspectors
+    }
+
+    /// Returns the mutable list of inspectors.
+    pub fn inspectors_mut(&mut self) -> &mut Vec<Box<dyn Inspector<CTX, INTR> + 'a>> {
+        &mut self.inspectors
+    }
+
+    /// Consumes the MultiInspector and returns the inspectors.
+    pub fn into_inspectors(self) -> Vec<Box<dyn Inspector<CTX, INTR> + 'a>> {
+        self.inspectors
+    }
+}
+
+impl<'a, CTX, INTR: InterpreterTypes> FromIterator<Box<dyn Inspector<CTX, INTR> + 'a>>
+    for MultiInspector<'a, CTX, INTR>
+{
+    fn from_iter<T: IntoIterator<Item = Box<dyn Inspector<CTX, INTR> + 'a>>>(iter: T) -> Self {
+        Self {
+            inspectors: iter.into_iter().collect(),
+            policy: OverridePolicy::default(),
+        }
+    }
+}
+
+impl<CTX, INTR: InterpreterTypes> Inspector<CTX, INTR> for MultiInspector<'_, CTX, INTR> {
+    fn initialize_interp(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        for inspector in &mut self.inspectors {
+            inspector.initialize_interp(interp, context);
+        }
+    }
+
+    fn step(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        for inspector in &mut self.inspectors {
+            inspector.step(interp, context);
+        }
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX) {
+        for inspector in &mut self.inspectors {
+            inspector.step_end(interp, context);
+        }
+    }
+
+    fn log(&mut self, context: &mut CTX, log: Log) {
+        for inspector in &mut self.inspectors {
+            inspector.log(context, log.clone());
+        }
+    }
+
+    fn log_full(&mut self, interp: &mut Interpreter<INTR>, context: &mut CTX, log: Log) {
+        for inspector in &mut self.inspectors {
+            inspector.log_full(interp, context, log.clone());
+        }
+    }
+
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let mut outcome = None;
+        for inspector in &mut self.inspectors {
+            let result = inspector.call(context, inputs);
+            outcome = self.policy.merge(outcome, result);
+        }
+        outcome
+    }
+
+    fn call_end(&mut self, context: &mut CTX, inputs: &CallInputs, outcome: &mut CallOutcome) {
+        for inspector in &mut self.inspectors {
+            inspector.call_end(context, inputs, outcome);
+        }
+    }
+
+    fn create(&mut self, context: &mut CTX, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let mut outcome = None;
+        for inspector in &mut self.inspectors {
+            let result = inspector.create(context, inputs);
+            outcome = self.policy.merge(outcome, result);
+        }
+        outcome
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        inputs: &CreateInputs,
+        outcome: &mut CreateOutcome,
+    ) {
+        for inspector in &mut self.inspectors {
+            inspector.create_end(context, inputs, outcome);
+        }
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        for inspector in &mut self.inspectors {
+            inspector.selfdestruct(contract, target, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CountInspector, InspectEvm};
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use interpreter::{Gas, InstructionResult, InterpreterResult};
+    use primitives::{address, Bytes, TxKind};
+    use state::bytecode::{opcode, Bytecode};
+
+    const IDENTITY: Address = address!("0x0000000000000000000000000000000000000004");
+
+    /// Overrides the calls to the identity precompile with the given result.
+    struct Override(InstructionResult);
+
+    impl<CTX> Inspector<CTX> for Override {
+        fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+            (inputs.target_address == IDENTITY).then(|| {
+                CallOutcome::new(
+                    InterpreterResult::new(self.0, Bytes::new(), Gas::new(inputs.gas_limit)),
+                    inputs.return_memory_offset.clone(),
+                )
+            })
+        }
+    }
+
+    #[test]
+    fn merge_overrides() {
+        // returns the success flag of the call to the identity precompile.
+        let code = [
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            4,
+            opcode::GAS,
+            opcode::STATICCALL,
+            opcode::PUSH1,
+            0,
+            opcode::MSTORE,
+            opcode::PUSH1,
+            32,
+            opcode::PUSH1,
+            0,
+            opcode::RETURN,
+        ];
+        let bytecode = Bytecode::new_raw(code.into());
+
+        for (policy, success) in [(OverridePolicy::First, 0), (OverridePolicy::Last, 1)] {
+            let mut first = CountInspector::new();
+            let mut last = CountInspector::new();
+            let inspector = MultiInspector::new()
+                .with_policy(policy)
+                .with_inspector(&mut first)
+                .with_inspector(Override(InstructionResult::Revert))
+                .with_inspector(Override(InstructionResult::Return))
+                .with_inspector(&mut last);
+            let ctx = Context::mainnet().with_db(BenchmarkDB::new_bytecode(bytecode.clone()));
+            let mut evm = ctx.build_mainnet_with_inspector(inspector);
+            let tx = TxEnv::builder()
+                .caller(BENCH_CALLER)
+                .kind(TxKind::Call(BENCH_TARGET))
+                .gas_limit(100_000)
+                .build()
+                .unwrap();
+            let output = evm.inspect_tx(tx).unwrap().result.into_output().unwrap();
+            assert_eq!(output[31], success);
+            drop(evm);
+
+            // every inspector sees both calls even if an earlier one overrode it.
+            for count in [first, last] {
+                assert_eq!(count.call_count(), 2);
+                assert_eq!(count.call_end_count(), 2);
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! PrestateTracer. Inspector that records the state accessed by the transaction.
+extern crate alloc;
+
+use crate::{Inspector, JournalExt};
+use alloc::collections::BTreeMap;
+use context::{ContextTr, JournalEntry, Transaction};
+use interpreter::{CallInputs, CallOutcome, CreateInputs, CreateOutcome, InterpreterTypes};
+use primitives::{Address, Bytes, StorageKey, StorageValue, B256, U256};
+use state::{Account, EvmState};
+
+/// Account in the format of the geth `prestateTracer`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrestateAccount {
+    /// Account balance.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub balance: Option<U256>,
+    /// Account nonce, omitted if zero.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub nonce: Option<u64>,
+    /// Account code, omitted if empty.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub code: Option<Bytes>,
+    /// Storage slots.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub storage: BTreeMap<B256, B256>,
+}
+
+/// State changes of the transaction in the format of the geth `prestateTracer` with
+/// `diffMode` enabled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateDiff {
+    /// Values of the changed accounts before the transaction.
+    ///
+    /// Accounts created by the transaction are omitted.
+    pub pre: BTreeMap<Address, PrestateAccount>,
+    /// Changed values after the transaction.
+    ///
+    /// Only changed fields are present and destroyed accounts are omitted.
+    pub post: BTreeMap<Address, PrestateAccount>,
+}
+
+/// Account fields at one point of the transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct AccountFields {
+    balance: U256,
+    nonce: u64,
+    code: Bytes,
+}
+
+impl AccountFields {
+    fn new(account: &Account) -> Self {
+        Self {
+            balance: account.info.balance,
+            nonce: account.info.nonce,
+            code: account
+                .info
+                .code
+                .as_ref()
+                .map(|code| code.original_bytes())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Account accessed by the transaction with its values before and after it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct AccountRecord {
+    pre: AccountFields,
+    post: AccountFields,
+    /// Storage slots with their values before and after the transaction.
+    storage: BTreeMap<StorageKey, (StorageValue, StorageValue)>,
+    /// Account did not exist before the transaction.
+    created: bool,
+    /// Account was destroyed by the transaction.
+    destroyed: bool,
+}
+
+impl AccountRecord {
+    /// Creates record with the pre values equal to the present ones, pre values are fixed by
+    /// replaying the journal.
+    fn new(account: &Account, transaction_id: usize) -> Self {
+        let fields = AccountFields::new(account);
+        Self {
+            pre: fields.clone(),
+            post: fields,
+            storage: storage(account, transaction_id).collect(),
+            created: account.is_loaded_as_not_existing(),
+            destroyed: account.is_selfdestructed(),
+        }
+    }
+
+    /// Sets the post values from the account.
+    fn update_post(&mut self, account: &Account, transaction_id: usize) {
+        self.post = AccountFields::new(account);
+        for (key, (pre, post)) in storage(account, transaction_id) {
+            self.storage.entry(key).or_insert((pre, post)).1 = post;
+        }
+        self.destroyed = account.is_selfdestructed();
+    }
+
+    fn pre_account(&self, keys: impl Fn(&StorageValue, &StorageValue) -> bool) -> PrestateAccount {
+        PrestateAccount {
+            balance: Some(self.pre.balance),
+            nonce: Some(self.pre.nonce).filter(|nonce| *nonce != 0),
+            code: Some(self.pre.code.clone()).filter(|code| !code.is_empty()),
+            storage: self
+                .storage
+                .iter()
+                .filter(|(_, (pre, post))| keys(pre, post))
+                .map(|(key, (pre, _))| (B256::from(*key), B256::from(*pre)))
+                .collect(),
+        }
+    }
+}
+
+/// Returns storage slots accessed in the transaction with their original and present values.
+fn storage(
+    account: &Account,
+    transaction_id: usize,
+) -> impl Iterator<Item = (StorageKey, (StorageValue, StorageValue))> + '_ {
+    account
+        .storage
+        .iter()
+        .filter(move |(_, slot)| slot.transaction_id == transaction_id)
+        .map(|(key, slot)| (*key, (slot.original_value, slot.present_value)))
+}
+
+/// Inspector that records every account and storage slot read or written by the transaction.
+///
+/// State is recorded when the top level frame ends: accounts and slots accessed in the
+/// transaction are taken from the [`EvmState`] and their values before the transaction are
+/// restored by replaying the [`JournalExt::journal`] entries backwards. Accounts and slots
+/// accessed in reverted calls are included.
+///
+/// Gas refund and the beneficiary reward are applied after the last frame, use
+/// [`PrestateTracer::apply_state`] with the state returned by the execution to include them.
+///
+/// Output is the geth `prestateTracer` format, see [`PrestateTracer::prestate`] and
+/// [`PrestateTracer::diff`] for the `diffMode`.
+#[derive(Clone, Debug, Default)]
+pub struct PrestateTracer {
+    /// Accessed accounts.
+    accounts: BTreeMap<Address, AccountRecord>,
+    /// Id of the traced transaction in the journal.
+    transaction_id: usize,
+    /// Depth of the executing frames.
+    depth: usize,
+}
+
+impl PrestateTracer {
+    /// Creates new prestate tracer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clears the recorded state.
+    pub fn clear(&mut self) {
+        self.accounts.clear();
+        self.depth = 0;
+    }
+
+    /// Returns every accessed account with its values before the transaction.
+    ///
+    /// This is the default output of the geth `prestateTracer`. Account code is included
+    /// only if it was loaded by the execution.
+    pub fn prestate(&self) -> BTreeMap<Address, PrestateAccount> {
+        self.accounts
+            .iter()
+            .map(|(address, record)| (*address, record.pre_account(|_, _| true)))
+            .collect()
+    }
+
+    /// Returns values of the changed accounts and storage slots before and after the
+    /// transaction.
+    ///
+    /// This is the output of the geth `prestateTracer` with `diffMode` enabled. Zero storage
+    /// values are omitted.
+    pub fn diff(&self) -> StateDiff {
+        let mut diff = StateDiff::default();
+        for (address, record) in &self.accounts {
+            let storage_changed = record.storage.values().any(|(pre, post)| pre != post);
+            if !record.destroyed && !storage_changed && record.pre == record.post {
+                continue;
+            }
+
+            if !record.created {
+                let mut pre = record.pre_account(|pre, post| pre != post);
+                pre.storage.retain(|_, value| !value.is_zero());
+                diff.pre.insert(*address, pre);
+            }
+            if record.destroyed {
+                continue;
+            }
+
+            let (pre, post) = (&record.pre, &record.post);
+            let account = PrestateAccount {
+                balance: Some(post.balance).filter(|balance| *balance != pre.balance),
+                nonce: Some(post.nonce).filter(|nonce| *nonce != pre.nonce),
+                code: Some(post.code.clone()).filter(|code| *code != pre.code),
+                storage: record
+                    .storage
+                    .iter()
+                    .filter(|(_, (pre, post))| pre != post && !post.is_zero())
+                    .map(|(key, (_, post))| (B256::from(*key), B256::from(*post)))
+                    .collect(),
+            };
+            if account != PrestateAccount::default() {
+                diff.post.insert(*address, account);
+            }
+        }
+        diff
+    }
+
+    /// Updates the post values from the state returned by the execution.
+    ///
+    /// Includes changes made after the last frame, the gas refund and the beneficiary reward.
+    /// Values before the transaction of the accounts that were accessed only after the last
+    /// frame are taken from [`Account::original_info`].
+    pub fn apply_state(&mut self, state: &EvmState) {
+        for (address, account) in state {
+            if account.transaction_id != self.transaction_id {
+                continue;
+            }
+            self.accounts
+                .entry(*address)
+                .or_insert_with(|| {
+                    let mut record = AccountRecord::new(account, self.transaction_id);
+                    record.pre.balance = account.original_info.balance;
+                    record.pre.nonce = account.original_info.nonce;
+                    record
+                })
+                .update_post(account, self.transaction_id);
+        }
+    }
+
+    /// Records accessed accounts from the journal.
+    fn record<J: JournalExt>(&mut self, journal: &J, caller: Address) {
+        let state = journal.evm_state();
+        // caller is always loaded by the transaction.
+        let Some(transaction_id) = state.get(&caller).map(|account| account.transaction_id) else {
+            return;
+        };
+        self.transaction_id = transaction_id;
+        self.accounts = state
+            .iter()
+            .filter(|(_, account)| account.transaction_id == transaction_id)
+            .map(|(address, account)| (*address, AccountRecord::new(account, transaction_id)))
+            .collect();
+
+        // revert the account changes to get the values before the transaction.
+        for entry in journal.journal().iter().rev() {
+            match entry {
+                JournalEntry::BalanceChange {
+                    old_balance,
+                    address,
+                } => self.pre_mut(address).balance = *old_balance,
+                JournalEntry::BalanceTransfer { balance, from, to } => {
+                    let from = &mut self.pre_mut(from).balance;
+                    *from = from.wrapping_add(*balance);
+                    let to = &mut self.pre_mut(to).balance;
+                    *to = to.wrapping_sub(*balance);
+                }
+                JournalEntry::NonceChange {
+                    address,
+                    previous_nonce,
+                } => self.pre_mut(address).nonce = *previous_nonce,
+                JournalEntry::NonceBump { address } => {
+                    let nonce = &mut self.pre_mut(address).nonce;
+                    *nonce = nonce.saturating_sub(1);
+                }
+                JournalEntry::CodeChange { address } => self.pre_mut(address).code = Bytes::new(),
+                JournalEntry::AccountDestroyed {
+                    had_balance,
+                    address,
+                    target,
+                    ..
+                } => {
+                    if address != target {
+                        let target = &mut self.pre_mut(target).balance;
+                        *target = target.wrapping_sub(*had_balance);
+                    }
+                    self.pre_mut(address).balance = *had_balance;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn pre_mut(&mut self, address: &Address) -> &mut AccountFields {
+        &mut self.accounts.entry(*address).or_default().pre
+    }
+
+    /// Decreases depth and records the state when the top level frame ends.
+    fn frame_end<CTX>(&mut self, context: &CTX)
+    where
+        CTX: ContextTr<Journal: JournalExt>,
+    {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 {
+            self.record(context.journal_ref(), context.tx().caller());
+        }
+    }
+}
+
+impl<CTX, INTR> Inspector<CTX, INTR> for PrestateTracer
+where
+    CTX: ContextTr<Journal: JournalExt>,
+    INTR: InterpreterTypes,
+{
+    fn call(&mut self, _context: &mut CTX, _inputs: &mut CallInputs) -> Option<CallOutcome> {
+        self.depth += 1;
+        None
+    }
+
+    fn call_end(&mut self, context: &mut CTX, _inputs: &CallInputs, _outcome: &mut CallOutcome) {
+        self.frame_end(context);
+    }
+
+    fn create(&mut self, _context: &mut CTX, _inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        self.depth += 1;
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        context: &mut CTX,
+        _inputs: &CreateInputs,
+        _outcome: &mut CreateOutcome,
+    ) {
+        self.frame_end(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InspectEvm;
+    use context::{Context, TxEnv};
+    use database::{BenchmarkDB, BENCH_CALLER, BENCH_CALLER_BALANCE, BENCH_TARGET};
+    use handler::{MainBuilder, MainContext};
+    use primitives::TxKind;
+    use state::bytecode::{opcode, Bytecode};
+
+    #[test]
+    fn records_prestate_and_diff() {
+        // SLOAD slot 1 and SSTORE 5 into slot 2.
+        let code = [
+            opcode::PUSH1,
+            1,
+            opcode::SLOAD,
+            opcode::POP,
+            opcode::PUSH1,
+            5,
+            opcode::PUSH1,
+            2,
+            opcode::SSTORE,
+            opcode::STOP,
+        ];
+        let bytecode = Bytecode::new_raw(code.to_vec().into());
+        let ctx = Context::mainnet().with_db(BenchmarkDB::new_bytecode(bytecode.clone()));
+        let mut evm = ctx.build_mainnet_with_inspector(PrestateTracer::new());
+        let output = evm
+            .inspect_tx(
+                TxEnv::builder()
+                    .caller(BENCH_CALLER)
+                    .kind(TxKind::Call(BENCH_TARGET))
+                    .gas_limit(100_000)
+                    .gas_price(1)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(output.result.is_success());
+        evm.inspector.apply_state(&output.state);
+
+        let prestate = evm.inspector.prestate();
+        let caller = &prestate[&BENCH_CALLER];
+        assert_eq!(caller.balance, Some(BENCH_CALLER_BALANCE));
+        assert_eq!(caller.nonce, None);
+        let target = &prestate[&BENCH_TARGET];
+        assert_eq!(target.code, Some(bytecode.original_bytes()));
+        assert_eq!(
+            target.storage,
+            BTreeMap::from([
+                (B256::from(U256::from(1)), B256::ZERO),
+                (B256::from(U256::from(2)), B256::ZERO),
+            ])
+        );
+
+        let diff = evm.inspector.diff();
+        // read slot and zero pre value are omitted.
+        assert_eq!(diff.pre[&BENCH_TARGET].storage, BTreeMap::new());
+        assert_eq!(
+            diff.post[&BENCH_TARGET].storage,
+            BTreeMap::from([(B256::from(U256::from(2)), B256::from(U256::from(5)))])
+        );
+        let caller_pre = &diff.pre[&BENCH_CALLER];
+        let caller_post = &diff.post[&BENCH_CALLER];
+        assert_eq!(caller_pre.balance, Some(BENCH_CALLER_BALANCE));
+        assert_eq!(caller_post.nonce, Some(1));
+        assert_eq!(
+            caller_post.balance,
+            Some(BENCH_CALLER_BALANCE - U256::from(output.result.gas_used()))
+        );
+    }
+}
@@ -3,6 +3,9 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 // reexport dependencies
+#[cfg(feature = "block")]
+#[doc(inline)]
+pub use block;
 #[doc(inline)]
 pub use bytecode;
 #[doc(inline)]
@@ -25,6 +28,9 @@ pub use precompile;
 pub use primitives;
 #[doc(inline)]
 pub use state;
+#[cfg(feature = "trie")]
+#[doc(inline)]
+pub use trie;
 
 #[cfg(feature = "test-types")]
 #[doc(inline)]
@@ -38,8 +44,8 @@ pub use context::{
 };
 pub use database_interface::{Database, DatabaseCommit, DatabaseRef};
 pub use handler::{
-    ExecuteCommitEvm, ExecuteEvm, MainBuilder, MainContext, MainnetEvm, SystemCallCommitEvm,
-    SystemCallEvm,
+    EstimateGasEvm, ExecuteCommitEvm, ExecuteEvm, MainBuilder, MainContext, MainnetEvm,
+    SystemCallCommitEvm, SystemCallEvm,
 };
 pub use inspector::{InspectCommitEvm, InspectEvm, InspectSystemCallEvm, Inspector};
 pub use precompile::install_crypto;
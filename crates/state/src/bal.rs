@@ -11,12 +11,15 @@
 //! - **`AccountBal`**: Complete BAL structure for an account (balance, nonce, code, and storage)
 //! - **`AccountInfoBal`**: Account info BAL data (nonce, balance, code)
 //! - **`StorageBal`**: Storage-level BAL data for an account
+//! - **`BalDiff`**: Differences between two BALs returned by [`Bal::validate_against`]
 
 pub mod account;
 pub mod alloy;
+pub mod diff;
 pub mod writes;
 
 pub use account::{AccountBal, AccountInfoBal, StorageBal};
+pub use diff::{BalDiff, BalMismatch, WriteMismatch};
 pub use writes::BalWrites;
 
 use crate::{Account, AccountInfo};
@@ -0,0 +1,359 @@
+//! BAL validation and diffing.
+
+use crate::bal::{writes::BalWrites, AccountBal, Bal, BalIndex};
+use core::fmt;
+use primitives::{Address, StorageKey, StorageValue, B256, U256};
+use std::{collections::BTreeSet, vec::Vec};
+
+/// Value written at a [`BalIndex`] that differs between two BALs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteMismatch<T> {
+    /// Index of the write.
+    pub index: BalIndex,
+    /// Value written in the reference BAL, `None` if there is no write at this index.
+    pub expected: Option<T>,
+    /// Value written in the validated BAL, `None` if there is no write at this index.
+    pub actual: Option<T>,
+}
+
+impl<T: fmt::Debug> fmt::Display for WriteMismatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at index {}: expected {:?}, got {:?}",
+            self.index, self.expected, self.actual
+        )
+    }
+}
+
+/// Single difference between two BALs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BalMismatch {
+    /// Account is present in the reference BAL but missing from the validated BAL.
+    MissingAccount(Address),
+    /// Account is present in the validated BAL but not in the reference BAL.
+    ExtraAccount(Address),
+    /// Storage read is present in the reference BAL but missing from the validated BAL.
+    MissingStorageRead {
+        /// Account address.
+        address: Address,
+        /// Storage key.
+        key: StorageKey,
+    },
+    /// Storage read is present in the validated BAL but not in the reference BAL.
+    ExtraStorageRead {
+        /// Account address.
+        address: Address,
+        /// Storage key.
+        key: StorageKey,
+    },
+    /// Storage write differs.
+    StorageWrite {
+        /// Account address.
+        address: Address,
+        /// Storage key.
+        key: StorageKey,
+        /// Mismatched write.
+        mismatch: WriteMismatch<StorageValue>,
+    },
+    /// Nonce write differs.
+    Nonce {
+        /// Account address.
+        address: Address,
+        /// Mismatched write.
+        mismatch: WriteMismatch<u64>,
+    },
+    /// Balance write differs.
+    Balance {
+        /// Account address.
+        address: Address,
+        /// Mismatched write.
+        mismatch: WriteMismatch<U256>,
+    },
+    /// Code write differs. Code is compared by its hash.
+    Code {
+        /// Account address.
+        address: Address,
+        /// Mismatched write.
+        mismatch: WriteMismatch<B256>,
+    },
+}
+
+impl fmt::Display for BalMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAccount(address) => write!(f, "missing account {address}"),
+            Self::ExtraAccount(address) => write!(f, "extra account {address}"),
+            Self::MissingStorageRead { address, key } => {
+                write!(f, "missing storage read {address} {key:#x}")
+            }
+            Self::ExtraStorageRead { address, key } => {
+                write!(f, "extra storage read {address} {key:#x}")
+            }
+            Self::StorageWrite {
+                address,
+                key,
+                mismatch,
+            } => write!(f, "storage write {address} {key:#x} {mismatch}"),
+            Self::Nonce { address, mismatch } => write!(f, "nonce write {address} {mismatch}"),
+            Self::Balance { address, mismatch } => {
+                write!(f, "balance write {address} {mismatch}")
+            }
+            Self::Code { address, mismatch } => write!(f, "code write {address} {mismatch}"),
+        }
+    }
+}
+
+/// Differences between two BALs, returned by [`Bal::validate_against`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BalDiff {
+    /// Mismatches ordered by account address.
+    pub mismatches: Vec<BalMismatch>,
+}
+
+impl BalDiff {
+    /// Returns true if there are no mismatches.
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Returns number of mismatches.
+    pub fn len(&self) -> usize {
+        self.mismatches.len()
+    }
+
+    /// Returns first mismatch, it is the most precise reason to reject the block.
+    pub fn first(&self) -> Option<&BalMismatch> {
+        self.mismatches.first()
+    }
+}
+
+impl fmt::Display for BalDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BAL mismatch")?;
+        for (i, mismatch) in self.mismatches.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { ", " };
+            write!(f, "{separator}{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for BalDiff {}
+
+impl Bal {
+    /// Validates this BAL against the reference BAL.
+    ///
+    /// Reference is usually the BAL built by executing the block and `self` the BAL received
+    /// with the block. Account order is ignored, all mismatches are returned ordered by account
+    /// address.
+    pub fn validate_against(&self, other: &Bal) -> Result<(), BalDiff> {
+        let addresses: BTreeSet<&Address> =
+            self.accounts.keys().chain(other.accounts.keys()).collect();
+
+        let mut diff = BalDiff::default();
+        for address in addresses {
+            match (self.accounts.get(address), other.accounts.get(address)) {
+                (Some(actual), Some(expected)) => {
+                    diff_account(&mut diff.mismatches, *address, expected, actual)
+                }
+                (None, Some(_)) => diff.mismatches.push(BalMismatch::MissingAccount(*address)),
+                (Some(_), None) => diff.mismatches.push(BalMismatch::ExtraAccount(*address)),
+                (None, None) => unreachable!("address is taken from one of the BALs"),
+            }
+        }
+
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(diff)
+        }
+    }
+}
+
+/// Pushes differences between account BALs.
+fn diff_account(
+    mismatches: &mut Vec<BalMismatch>,
+    address: Address,
+    expected: &AccountBal,
+    actual: &AccountBal,
+) {
+    for mismatch in diff_writes(&expected.nonce, &actual.nonce, |nonce| *nonce) {
+        mismatches.push(BalMismatch::Nonce { address, mismatch });
+    }
+    for mismatch in diff_writes(&expected.balance, &actual.balance, |balance| *balance) {
+        mismatches.push(BalMismatch::Balance { address, mismatch });
+    }
+    for mismatch in diff_writes(&expected.code, &actual.code, |(code_hash, _)| *code_hash) {
+        mismatches.push(BalMismatch::Code { address, mismatch });
+    }
+
+    let keys: BTreeSet<&StorageKey> = expected
+        .storage
+        .storage
+        .keys()
+        .chain(actual.storage.storage.keys())
+        .collect();
+    let empty = BalWrites::default();
+    for key in keys {
+        let expected = expected.storage.storage.get(key);
+        let actual = actual.storage.storage.get(key);
+        // slots without writes are reads.
+        match (expected, actual) {
+            (Some(expected), None) if expected.is_empty() => {
+                mismatches.push(BalMismatch::MissingStorageRead { address, key: *key });
+                continue;
+            }
+            (None, Some(actual)) if actual.is_empty() => {
+                mismatches.push(BalMismatch::ExtraStorageRead { address, key: *key });
+                continue;
+            }
+            _ => {}
+        }
+        for mismatch in diff_writes(
+            expected.unwrap_or(&empty),
+            actual.unwrap_or(&empty),
+            |value| *value,
+        ) {
+            mismatches.push(BalMismatch::StorageWrite {
+                address,
+                key: *key,
+                mismatch,
+            });
+        }
+    }
+}
+
+/// Returns mismatched writes of two sorted write lists.
+fn diff_writes<T: PartialEq + Clone, K: PartialEq>(
+    expected: &BalWrites<T>,
+    actual: &BalWrites<T>,
+    key: impl Fn(&T) -> K,
+) -> Vec<WriteMismatch<K>> {
+    let mut mismatches = Vec::new();
+    let mut expected = expected.writes.iter().peekable();
+    let mut actual = actual.writes.iter().peekable();
+    loop {
+        let (index, expected_value, actual_value) = match (expected.peek(), actual.peek()) {
+            (None, None) => break,
+            (Some((e, _)), Some((a, _))) if e == a => {
+                let (index, e) = expected.next().unwrap();
+                let (_, a) = actual.next().unwrap();
+                (*index, Some(key(e)), Some(key(a)))
+            }
+            (Some((e, _)), Some((a, _))) if e > a => {
+                let (index, a) = actual.next().unwrap();
+                (*index, None, Some(key(a)))
+            }
+            (Some(_), _) => {
+                let (index, e) = expected.next().unwrap();
+                (*index, Some(key(e)), None)
+            }
+            (None, Some(_)) => {
+                let (index, a) = actual.next().unwrap();
+                (*index, None, Some(key(a)))
+            }
+        };
+        if expected_value != actual_value {
+            mismatches.push(WriteMismatch {
+                index,
+                expected: expected_value,
+                actual: actual_value,
+            });
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bal::{AccountInfoBal, StorageBal};
+    use primitives::address;
+
+    const A: Address = address!("0x1000000000000000000000000000000000000001");
+    const B: Address = address!("0x2000000000000000000000000000000000000002");
+
+    fn account() -> AccountBal {
+        AccountBal {
+            account_info: AccountInfoBal {
+                nonce: BalWrites::new(vec![(1, 1)]),
+                balance: BalWrites::new(vec![(1, U256::from(10)), (3, U256::from(5))]),
+                code: BalWrites::default(),
+            },
+            storage: StorageBal::from_iter([
+                (U256::from(1), BalWrites::default()),
+                (U256::from(2), BalWrites::new(vec![(2, U256::from(7))])),
+            ]),
+        }
+    }
+
+    #[test]
+    fn equal_bals_are_valid() {
+        let bal = Bal::from_iter([(A, account()), (B, AccountBal::default())]);
+        // account order does not matter.
+        let reversed = Bal::from_iter([(B, AccountBal::default()), (A, account())]);
+        assert_eq!(bal.validate_against(&reversed), Ok(()));
+    }
+
+    #[test]
+    fn structured_diff() {
+        let expected = Bal::from_iter([(A, account()), (B, AccountBal::default())]);
+
+        let mut actual = account();
+        actual.balance = BalWrites::new(vec![(1, U256::from(10)), (2, U256::from(5))]);
+        actual.storage.storage.remove(&U256::from(1));
+        actual.storage.update_reads([U256::from(3)].into_iter());
+        actual
+            .storage
+            .storage
+            .get_mut(&U256::from(2))
+            .unwrap()
+            .writes = vec![(2, U256::from(8))];
+        let actual = Bal::from_iter([(A, actual)]);
+
+        let diff = actual.validate_against(&expected).unwrap_err();
+        assert_eq!(
+            diff.mismatches,
+            vec![
+                BalMismatch::Balance {
+                    address: A,
+                    mismatch: WriteMismatch {
+                        index: 2,
+                        expected: None,
+                        actual: Some(U256::from(5)),
+                    },
+                },
+                BalMismatch::Balance {
+                    address: A,
+                    mismatch: WriteMismatch {
+                        index: 3,
+                        expected: Some(U256::from(5)),
+                        actual: None,
+                    },
+                },
+                BalMismatch::MissingStorageRead {
+                    address: A,
+                    key: U256::from(1),
+                },
+                BalMismatch::StorageWrite {
+                    address: A,
+                    key: U256::from(2),
+                    mismatch: WriteMismatch {
+                        index: 2,
+                        expected: Some(U256::from(7)),
+                        actual: Some(U256::from(8)),
+                    },
+                },
+                BalMismatch::ExtraStorageRead {
+                    address: A,
+                    key: U256::from(3),
+                },
+                BalMismatch::MissingAccount(B),
+            ]
+        );
+    }
+}
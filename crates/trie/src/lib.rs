@@ -0,0 +1,22 @@
+//! Merkle Patricia Trie root computation.
+//!
+//! Computes state root, storage roots, receipts root and logs bloom of the block.
+//! [`StateTrie`] keeps the whole state trie in memory and is updated from a
+//! [`BundleState`](database::BundleState) or [`EvmState`](state::EvmState), rehashing
+//! only the touched accounts.
+#![cfg_attr(not(test), warn(unused_crate_dependencies))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc as std;
+
+/// Receipts root and logs bloom.
+pub mod receipts;
+/// State and storage tries.
+pub mod state;
+/// In-memory Merkle Patricia Trie.
+pub mod trie;
+
+pub use receipts::{encode_receipt, logs_bloom, receipts_bloom, receipts_root};
+pub use state::{state_root, storage_root, witness_state_root, StateTrie, TrieAccount};
+pub use trie::{MerkleTrie, EMPTY_ROOT_HASH};
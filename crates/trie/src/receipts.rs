@@ -0,0 +1,108 @@
+use crate::MerkleTrie;
+use alloy_rlp::{Encodable, Header};
+use block::Receipt;
+use primitives::{alloy_primitives::Bloom, Log, B256};
+use std::vec::Vec;
+
+/// Computes the logs bloom of the logs.
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = &'a Log>) -> Bloom {
+    let mut bloom = Bloom::ZERO;
+    for log in logs {
+        bloom.accrue_log(log);
+    }
+    bloom
+}
+
+/// Computes the logs bloom of the block from all receipts.
+pub fn receipts_bloom(receipts: &[Receipt]) -> Bloom {
+    logs_bloom(receipts.iter().flat_map(|receipt| &receipt.logs))
+}
+
+/// Encodes the receipt as [EIP-2718](https://eips.ethereum.org/EIPS/eip-2718) typed envelope.
+///
+/// Legacy receipts are encoded as RLP list `[status, cumulative_gas_used, logs_bloom, logs]`,
+/// typed receipts are the same list prefixed with the transaction type.
+pub fn encode_receipt(receipt: &Receipt, out: &mut Vec<u8>) {
+    let bloom = logs_bloom(&receipt.logs);
+    let payload_length = receipt.success.length()
+        + receipt.cumulative_gas_used.length()
+        + bloom.length()
+        + alloy_rlp::list_length(&receipt.logs);
+
+    if receipt.tx_type != 0 {
+        out.push(receipt.tx_type);
+    }
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(out);
+    receipt.success.encode(out);
+    receipt.cumulative_gas_used.encode(out);
+    bloom.encode(out);
+    alloy_rlp::encode_list(&receipt.logs, out);
+}
+
+/// Computes the receipts root of the block.
+///
+/// Receipts trie is keyed by RLP encoded transaction index.
+pub fn receipts_root(receipts: &[Receipt]) -> B256 {
+    let mut trie = MerkleTrie::new();
+    for (index, receipt) in receipts.iter().enumerate() {
+        let mut value = Vec::new();
+        encode_receipt(receipt, &mut value);
+        trie.insert(&alloy_rlp::encode(index), value);
+    }
+    trie.root()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{trie::tests::KeccakHasher, EMPTY_ROOT_HASH};
+    use primitives::{address, alloy_primitives::BloomInput, b256, bytes, LogData};
+    use std::vec;
+
+    #[test]
+    fn empty_receipts_root() {
+        assert_eq!(receipts_root(&[]), EMPTY_ROOT_HASH);
+        assert_eq!(receipts_bloom(&[]), Bloom::ZERO);
+    }
+
+    #[test]
+    fn receipt_bloom() {
+        let log = Log {
+            address: address!("0x0000000000000000000000000000000000000011"),
+            data: LogData::new_unchecked(
+                vec![b256!(
+                    "0x000000000000000000000000000000000000000000000000000000000000dead"
+                )],
+                bytes!("0100ff"),
+            ),
+        };
+        let receipt = Receipt {
+            tx_type: 2,
+            success: true,
+            gas_used: 21_000,
+            cumulative_gas_used: 21_000,
+            logs: vec![log.clone()],
+        };
+        let bloom = receipts_bloom(core::slice::from_ref(&receipt));
+        assert!(bloom.contains_input(BloomInput::Raw(log.address.as_slice())));
+
+        let mut encoded = Vec::new();
+        encode_receipt(&receipt, &mut encoded);
+        assert_eq!(encoded[0], 2);
+        // typed receipt payload is a list.
+        assert!(encoded[1] >= 0xf7);
+
+        // more than 128 receipts so keys of different lengths are present.
+        let receipts = vec![receipt; 130];
+        let expected = triehash::ordered_trie_root::<KeccakHasher, _>(receipts.iter().map(|r| {
+            let mut out = Vec::new();
+            encode_receipt(r, &mut out);
+            out
+        }));
+        assert_eq!(receipts_root(&receipts), expected);
+    }
+}
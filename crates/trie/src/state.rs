@@ -0,0 +1,653 @@
+use crate::{MerkleTrie, EMPTY_ROOT_HASH};
+use alloy_rlp::{Decodable, Encodable, Header};
+use database::{BundleState, PlainAccount, Witness, WitnessError};
+use primitives::{hardfork::SpecId, keccak256, Address, Bytes, HashMap, B256, U256};
+use state::{AccountInfo, EvmState};
+use std::{collections::BTreeSet, vec::Vec};
+
+/// Account as it is encoded in the state trie.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TrieAccount {
+    /// Account nonce.
+    pub nonce: u64,
+    /// Account balance.
+    pub balance: U256,
+    /// Root of the account storage trie.
+    pub storage_root: B256,
+    /// Hash of the account code.
+    pub code_hash: B256,
+}
+
+impl TrieAccount {
+    /// Creates trie account from account info and storage root.
+    pub fn new(info: &AccountInfo, storage_root: B256) -> Self {
+        Self {
+            nonce: info.nonce,
+            balance: info.balance,
+            storage_root,
+            code_hash: info.code_hash,
+        }
+    }
+
+    fn payload_length(&self) -> usize {
+        self.nonce.length()
+            + self.balance.length()
+            + self.storage_root.length()
+            + self.code_hash.length()
+    }
+}
+
+impl Encodable for TrieAccount {
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        Header {
+            list: true,
+            payload_length: self.payload_length(),
+        }
+        .encode(out);
+        self.nonce.encode(out);
+        self.balance.encode(out);
+        self.storage_root.encode(out);
+        self.code_hash.encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.payload_length();
+        payload_length + alloy_rlp::length_of_length(payload_length)
+    }
+}
+
+impl Decodable for TrieAccount {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let header = Header::decode(buf)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString);
+        }
+        let started_len = buf.len();
+        let account = Self {
+            nonce: u64::decode(buf)?,
+            balance: U256::decode(buf)?,
+            storage_root: B256::decode(buf)?,
+            code_hash: B256::decode(buf)?,
+        };
+        if started_len - buf.len() != header.payload_length {
+            return Err(alloy_rlp::Error::ListLengthMismatch {
+                expected: header.payload_length,
+                got: started_len - buf.len(),
+            });
+        }
+        Ok(account)
+    }
+}
+
+/// Account with its storage trie.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct StateTrieAccount {
+    info: AccountInfo,
+    storage: MerkleTrie,
+}
+
+/// State trie that keeps accounts and their storage tries in memory.
+///
+/// Built once from the pre-state, after that state changes from
+/// [`BundleState`] or [`EvmState`] can be applied and only touched accounts and
+/// storage slots are rehashed when calculating the [`StateTrie::state_root`].
+///
+/// # Example
+///
+/// ```
+/// use revm_trie::{StateTrie, EMPTY_ROOT_HASH};
+///
+/// let mut trie = StateTrie::new();
+/// assert_eq!(trie.state_root(), EMPTY_ROOT_HASH);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateTrie {
+    /// Accounts trie, keyed by `keccak256(address)`.
+    accounts: MerkleTrie,
+    /// Account info and storage trie of every account.
+    storages: HashMap<Address, StateTrieAccount>,
+    /// Accounts whose entry in the accounts trie needs to be recomputed.
+    dirty: Vec<Address>,
+}
+
+impl StateTrie {
+    /// Creates new empty state trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds state trie from plain accounts.
+    pub fn from_plain_accounts<'a>(
+        accounts: impl IntoIterator<Item = (Address, &'a PlainAccount)>,
+    ) -> Self {
+        let mut trie = Self::new();
+        for (address, account) in accounts {
+            trie.update_account(address, Some(&account.info), false, &account.storage);
+        }
+        trie
+    }
+
+    /// Builds state trie from the bundle state.
+    ///
+    /// Useful when the bundle contains the whole state, e.g. genesis allocation.
+    pub fn from_bundle(bundle: &BundleState) -> Self {
+        let mut trie = Self::new();
+        trie.apply_bundle(bundle);
+        trie
+    }
+
+    /// Updates account info and storage.
+    ///
+    /// If `info` is `None` the account and its storage are removed. If `wipe_storage` is set,
+    /// storage is cleared before the new storage values are applied. Zero storage values remove
+    /// the slot from the trie.
+    pub fn update_account<'a>(
+        &mut self,
+        address: Address,
+        info: Option<&AccountInfo>,
+        wipe_storage: bool,
+        storage: impl IntoIterator<Item = (&'a U256, &'a U256)>,
+    ) {
+        self.dirty.push(address);
+        let Some(info) = info else {
+            self.storages.remove(&address);
+            return;
+        };
+        let account = self.storages.entry(address).or_default();
+        account.info = info.clone();
+        if wipe_storage {
+            account.storage = MerkleTrie::new();
+        }
+        for (slot, value) in storage {
+            let key = keccak256(slot.to_be_bytes::<32>());
+            if value.is_zero() {
+                account.storage.remove(key.as_slice());
+            } else {
+                account
+                    .storage
+                    .insert(key.as_slice(), alloy_rlp::encode(value));
+            }
+        }
+    }
+
+    /// Applies changes of the accounts that are present in the bundle.
+    ///
+    /// Bundle needs to be created on top of the state this trie represents.
+    pub fn apply_bundle(&mut self, bundle: &BundleState) {
+        for (address, account) in bundle.state() {
+            self.update_account(
+                *address,
+                account.info.as_ref(),
+                account.was_destroyed(),
+                account
+                    .storage
+                    .iter()
+                    .map(|(slot, value)| (slot, &value.present_value)),
+            );
+        }
+    }
+
+    /// Applies changes of the accounts touched by the transaction.
+    ///
+    /// Selfdestructed accounts and, from Spurious Dragon, touched empty accounts are removed.
+    pub fn apply_evm_state(&mut self, state: &EvmState, spec: SpecId) {
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            if account.is_selfdestructed() || account.state_clear_aware_is_empty(spec) {
+                self.update_account(*address, None, true, []);
+                continue;
+            }
+            self.update_account(
+                *address,
+                Some(&account.info),
+                account.is_created(),
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| account.is_created() || slot.is_changed())
+                    .map(|(key, slot)| (key, &slot.present_value)),
+            );
+        }
+    }
+
+    /// Returns the account info, if account exists.
+    pub fn account(&self, address: &Address) -> Option<&AccountInfo> {
+        self.storages.get(address).map(|account| &account.info)
+    }
+
+    /// Returns the storage root of the account, or [`EMPTY_ROOT_HASH`] if account does not exist.
+    pub fn storage_root(&mut self, address: &Address) -> B256 {
+        self.storages
+            .get_mut(address)
+            .map(|account| account.storage.root())
+            .unwrap_or(EMPTY_ROOT_HASH)
+    }
+
+    /// Returns the state root.
+    ///
+    /// Only accounts changed since the last call are rehashed.
+    pub fn state_root(&mut self) -> B256 {
+        for address in core::mem::take(&mut self.dirty) {
+            let key = keccak256(address);
+            match self.storages.get_mut(&address) {
+                Some(account) => {
+                    let trie_account = TrieAccount::new(&account.info, account.storage.root());
+                    self.accounts
+                        .insert(key.as_slice(), alloy_rlp::encode(trie_account));
+                }
+                None => {
+                    self.accounts.remove(key.as_slice());
+                }
+            }
+        }
+        self.accounts.root()
+    }
+}
+
+impl StateTrie {
+    /// Returns the nodes of the accounts trie on the path to the account.
+    pub fn account_proof(&mut self, address: &Address) -> Vec<Bytes> {
+        self.state_root();
+        self.accounts
+            .proof(keccak256(address).as_slice())
+            .into_iter()
+            .map(Bytes::from)
+            .collect()
+    }
+
+    /// Returns the nodes of the account storage trie on the path to the slot.
+    ///
+    /// Proof is empty if the account does not exist or has no storage.
+    pub fn storage_proof(&mut self, address: &Address, slot: &U256) -> Vec<Bytes> {
+        let Some(account) = self.storages.get_mut(address) else {
+            return Vec::new();
+        };
+        account
+            .storage
+            .proof(keccak256(slot.to_be_bytes::<32>()).as_slice())
+            .into_iter()
+            .map(Bytes::from)
+            .collect()
+    }
+
+    /// Adds the nodes that prove the accounts and storage slots of the witness.
+    ///
+    /// Trie has to represent the pre-state of the witness, the witness can then be checked
+    /// with [`Witness::verify`] against the [`StateTrie::state_root`]. Nodes next to the paths
+    /// that removals merge are added too, so the post-state root can be computed with
+    /// [`witness_state_root`].
+    pub fn prove_witness(&mut self, witness: &mut Witness) {
+        self.state_root();
+        let addresses: BTreeSet<Address> = witness
+            .accounts
+            .keys()
+            .chain(witness.storage.keys())
+            .copied()
+            .collect();
+        let keys: Vec<B256> = addresses.iter().map(keccak256).collect();
+        for node in self.accounts.multiproof(keys.iter().map(B256::as_slice)) {
+            witness.insert_node(node.into());
+        }
+        for address in &addresses {
+            let (Some(account), Some(storage)) =
+                (self.storages.get_mut(address), witness.storage.get(address))
+            else {
+                continue;
+            };
+            let keys: Vec<B256> = storage
+                .keys()
+                .map(|slot| keccak256(slot.to_be_bytes::<32>()))
+                .collect();
+            for node in account.storage.multiproof(keys.iter().map(B256::as_slice)) {
+                witness.insert_node(node.into());
+            }
+        }
+    }
+}
+
+/// Computes the state root after the bundle is applied to the pre-state of the witness.
+///
+/// The witness needs the nodes added by [`StateTrie::prove_witness`] for the pre-state root,
+/// and the bundle has to be created by the execution on top of the witness.
+pub fn witness_state_root(
+    witness: &Witness,
+    state_root: B256,
+    bundle: &BundleState,
+) -> Result<B256, WitnessError> {
+    let mut accounts = MerkleTrie::from_nodes(state_root, &witness.nodes)?;
+    for (address, account) in bundle.state() {
+        let key = keccak256(address);
+        let Some(info) = &account.info else {
+            accounts.try_remove(key.as_slice())?;
+            continue;
+        };
+        let mut storage = match accounts.try_get(key.as_slice())? {
+            Some(_) if account.was_destroyed() => MerkleTrie::new(),
+            Some(mut leaf) => {
+                let trie_account = TrieAccount::decode(&mut leaf)
+                    .map_err(|_| WitnessError::InvalidAccount(*address))?;
+                MerkleTrie::from_nodes(trie_account.storage_root, &witness.nodes)?
+            }
+            None => MerkleTrie::new(),
+        };
+        for (slot, value) in &account.storage {
+            let key = keccak256(slot.to_be_bytes::<32>());
+            if value.present_value.is_zero() {
+                storage.try_remove(key.as_slice())?;
+            } else {
+                storage.try_insert(key.as_slice(), alloy_rlp::encode(value.present_value))?;
+            }
+        }
+        let trie_account = TrieAccount::new(info, storage.root());
+        accounts.try_insert(key.as_slice(), alloy_rlp::encode(trie_account))?;
+    }
+    Ok(accounts.root())
+}
+
+/// Computes the state root of the plain accounts.
+pub fn state_root<'a>(accounts: impl IntoIterator<Item = (Address, &'a PlainAccount)>) -> B256 {
+    StateTrie::from_plain_accounts(accounts).state_root()
+}
+
+/// Computes the storage root. Zero values are skipped.
+pub fn storage_root<'a>(storage: impl IntoIterator<Item = (&'a U256, &'a U256)>) -> B256 {
+    let mut trie = MerkleTrie::new();
+    for (slot, value) in storage {
+        if !value.is_zero() {
+            trie.insert(
+                keccak256(slot.to_be_bytes::<32>()).as_slice(),
+                alloy_rlp::encode(value),
+            );
+        }
+    }
+    trie.root()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use database::{states::bundle_state::BundleRetention, CacheDB, EmptyDB, State};
+    use primitives::{address, KECCAK_EMPTY};
+    use state::{Account, AccountStatus, EvmStorageSlot};
+
+    const A: Address = address!("0x1000000000000000000000000000000000000001");
+    const B: Address = address!("0x2000000000000000000000000000000000000002");
+
+    fn info(balance: u64) -> AccountInfo {
+        AccountInfo::default().with_balance(U256::from(balance))
+    }
+
+    #[test]
+    fn empty_state_root() {
+        assert_eq!(StateTrie::new().state_root(), EMPTY_ROOT_HASH);
+        assert_eq!(storage_root([]), EMPTY_ROOT_HASH);
+    }
+
+    #[test]
+    fn single_account_root() {
+        // state root of one empty account at the zero address.
+        let account = PlainAccount::new_empty_with_storage(Default::default());
+        let root = state_root([(Address::ZERO, &account)]);
+        let expected_leaf = alloy_rlp::encode(TrieAccount {
+            nonce: 0,
+            balance: U256::ZERO,
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        });
+        let mut trie = MerkleTrie::new();
+        trie.insert(keccak256(Address::ZERO).as_slice(), expected_leaf);
+        assert_eq!(root, trie.root());
+    }
+
+    #[test]
+    fn incremental_matches_full_rebuild() {
+        let mut storage = HashMap::default();
+        storage.insert(U256::from(1), U256::from(10));
+        storage.insert(U256::from(2), U256::from(20));
+        let pre = [
+            (
+                A,
+                PlainAccount {
+                    info: info(100),
+                    storage: storage.clone(),
+                },
+            ),
+            (
+                B,
+                PlainAccount {
+                    info: info(200),
+                    storage: Default::default(),
+                },
+            ),
+        ];
+        let mut trie = StateTrie::from_plain_accounts(pre.iter().map(|(a, acc)| (*a, acc)));
+        let pre_root = trie.state_root();
+
+        // A: clear slot 1, change slot 2, B: removed.
+        let mut state = EvmState::default();
+        let mut a = Account::from(info(150));
+        a.mark_touch();
+        a.storage.insert(
+            U256::from(1),
+            EvmStorageSlot::new_changed(U256::from(10), U256::ZERO, 0),
+        );
+        a.storage.insert(
+            U256::from(2),
+            EvmStorageSlot::new_changed(U256::from(20), U256::from(21), 0),
+        );
+        state.insert(A, a);
+        let mut b = Account::from(info(200));
+        b.status = AccountStatus::Touched | AccountStatus::SelfDestructed;
+        state.insert(B, b);
+        trie.apply_evm_state(&state, SpecId::PRAGUE);
+
+        let mut post_storage = HashMap::default();
+        post_storage.insert(U256::from(2), U256::from(21));
+        let post = PlainAccount {
+            info: info(150),
+            storage: post_storage.clone(),
+        };
+        assert_eq!(trie.state_root(), state_root([(A, &post)]));
+        assert_ne!(trie.state_root(), pre_root);
+        assert_eq!(trie.storage_root(&A), storage_root(&post_storage));
+        assert_eq!(trie.storage_root(&B), EMPTY_ROOT_HASH);
+    }
+
+    #[test]
+    fn apply_bundle() {
+        let mut cache_db = CacheDB::new(EmptyDB::default());
+        cache_db.insert_account_info(A, info(100));
+        let mut state = State::builder()
+            .with_database(cache_db)
+            .with_bundle_update()
+            .build();
+
+        let mut trie = StateTrie::from_plain_accounts([(
+            A,
+            &PlainAccount {
+                info: info(100),
+                storage: Default::default(),
+            },
+        )]);
+
+        // load accounts into the cache and commit changes.
+        let mut evm_state = EvmState::default();
+        for (address, balance) in [(A, 50), (B, 50)] {
+            let _ = database::Database::basic(&mut state, address).unwrap();
+            let mut account = Account::from(info(balance));
+            account.mark_touch();
+            if address == B {
+                account.mark_created();
+                account.storage.insert(
+                    U256::from(1),
+                    EvmStorageSlot::new_changed(U256::ZERO, U256::from(1), 0),
+                );
+            }
+            evm_state.insert(address, account);
+        }
+        database::DatabaseCommit::commit(&mut state, evm_state);
+        state.merge_transitions(BundleRetention::PlainState);
+        let bundle = state.take_bundle();
+
+        trie.apply_bundle(&bundle);
+        let expected = state_root(state.cache.trie_account());
+        assert_eq!(trie.state_root(), expected);
+    }
+
+    #[test]
+    fn prove_witness() {
+        use database::{Database, RecordingDb, WitnessError};
+
+        // enough accounts and slots for branch, extension and inlined nodes.
+        let mut db = CacheDB::new(EmptyDB::default());
+        for i in 1..=20u64 {
+            let address = Address::with_last_byte(i as u8);
+            db.insert_account_info(address, info(i));
+            for slot in 0..i {
+                db.insert_account_storage(address, U256::from(slot), U256::from(slot + 1))
+                    .unwrap();
+            }
+        }
+        let accounts: Vec<_> = db
+            .cache
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                let account = PlainAccount {
+                    info: account.info.clone(),
+                    storage: account.storage.clone(),
+                };
+                (*address, account)
+            })
+            .collect();
+        let mut trie =
+            StateTrie::from_plain_accounts(accounts.iter().map(|(address, acc)| (*address, acc)));
+        let root = trie.state_root();
+
+        let mut recording = RecordingDb::new(db);
+        let c = Address::with_last_byte(12);
+        recording.basic(c).unwrap();
+        recording.basic(A).unwrap();
+        recording.storage(c, U256::from(3)).unwrap();
+        recording.storage(c, U256::from(100)).unwrap();
+        recording
+            .storage(Address::with_last_byte(1), U256::ZERO)
+            .unwrap();
+        let mut witness = recording.take_witness();
+        trie.prove_witness(&mut witness);
+        assert_eq!(witness.verify(root), Ok(()));
+        assert!(matches!(
+            witness.verify(B256::ZERO),
+            Err(WitnessError::MissingNode(_))
+        ));
+
+        let mut tampered = witness.clone();
+        tampered
+            .accounts
+            .get_mut(&c)
+            .unwrap()
+            .as_mut()
+            .unwrap()
+            .balance = U256::ZERO;
+        assert_eq!(tampered.verify(root), Err(WitnessError::AccountMismatch(c)));
+
+        // missing account can't be made up.
+        let mut tampered = witness.clone();
+        tampered.accounts.insert(A, Some(Default::default()));
+        assert_eq!(tampered.verify(root), Err(WitnessError::AccountMismatch(A)));
+
+        let mut tampered = witness.clone();
+        tampered
+            .storage
+            .get_mut(&c)
+            .unwrap()
+            .insert(U256::from(100), U256::from(1));
+        assert_eq!(
+            tampered.verify(root),
+            Err(WitnessError::StorageMismatch(c, U256::from(100)))
+        );
+
+        let mut tampered = witness;
+        tampered.nodes.pop_first();
+        assert!(matches!(
+            tampered.verify(root),
+            Err(WitnessError::MissingNode(_))
+        ));
+    }
+
+    #[test]
+    fn witness_post_state_root() {
+        use database::{Database, DatabaseCommit, RecordingDb};
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        let mut pre = Vec::new();
+        for i in 1..=20u64 {
+            let address = Address::with_last_byte(i as u8);
+            let storage: HashMap<U256, U256> = (0..i)
+                .map(|slot| (U256::from(slot), U256::from(slot + 1)))
+                .collect();
+            db.insert_account_info(address, info(i));
+            db.replace_account_storage(address, storage.clone())
+                .unwrap();
+            pre.push((
+                address,
+                PlainAccount {
+                    info: info(i),
+                    storage,
+                },
+            ));
+        }
+        let mut trie = StateTrie::from_plain_accounts(pre.iter().map(|(a, acc)| (*a, acc)));
+        let root = trie.state_root();
+
+        let mut state = State::builder()
+            .with_database(RecordingDb::new(db))
+            .with_bundle_update()
+            .build();
+        let (c, removed) = (Address::with_last_byte(12), Address::with_last_byte(2));
+        let mut changes = EvmState::default();
+        // clear two slots and change one, remove an account and create one with storage.
+        let mut account = Account::from(state.basic(c).unwrap().unwrap());
+        account.mark_touch();
+        for (slot, value) in [(3, 0), (4, 0), (5, 50)] {
+            let slot = U256::from(slot);
+            let original = state.storage(c, slot).unwrap();
+            let changed = EvmStorageSlot::new_changed(original, U256::from(value), 0);
+            account.storage.insert(slot, changed);
+        }
+        changes.insert(c, account);
+        let mut account = Account::from(state.basic(removed).unwrap().unwrap());
+        account.status = AccountStatus::Touched | AccountStatus::SelfDestructed;
+        changes.insert(removed, account);
+        assert_eq!(state.basic(B).unwrap(), None);
+        let mut account = Account::from(info(7));
+        account.mark_touch();
+        account.mark_created();
+        account.storage.insert(
+            U256::from(1),
+            EvmStorageSlot::new_changed(U256::ZERO, U256::from(1), 0),
+        );
+        changes.insert(B, account);
+        state.commit(changes);
+        state.merge_transitions(BundleRetention::PlainState);
+        let bundle = state.take_bundle();
+
+        let mut witness = state.database.take_witness();
+        trie.prove_witness(&mut witness);
+        assert_eq!(witness.verify(root), Ok(()));
+        trie.apply_bundle(&bundle);
+        let post_root = trie.state_root();
+        assert_ne!(post_root, root);
+        assert_eq!(witness_state_root(&witness, root, &bundle), Ok(post_root));
+
+        // changes that are not proven by the witness.
+        let mut tampered = witness;
+        tampered.nodes.clear();
+        assert!(matches!(
+            witness_state_root(&tampered, root, &bundle),
+            Err(WitnessError::MissingNode(_))
+        ));
+    }
+}
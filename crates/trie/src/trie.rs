@@ -0,0 +1,862 @@
+//! In-memory Merkle Patricia Trie.
+//!
+//! Every node caches its reference (inlined RLP if shorter than 32 bytes, RLP encoded hash
+//! otherwise). Insert and remove only clear the cache on the path to the changed leaf, so
+//! recomputing the root after a small number of updates only rehashes touched nodes.
+//!
+//! A trie built with [`MerkleTrie::from_nodes`] is sparse: subtries without nodes are kept as
+//! their hash, only the paths through the known nodes can be read and changed.
+use alloy_rlp::{Encodable, Header, EMPTY_STRING_CODE};
+use database::WitnessError;
+use primitives::{keccak256, Bytes, B256};
+use std::{boxed::Box, collections::BTreeMap, vec::Vec};
+
+/// Root of the empty trie, `keccak256(rlp(""))`.
+pub const EMPTY_ROOT_HASH: B256 =
+    primitives::b256!("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// Cached node reference.
+type NodeRef = Option<Vec<u8>>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf {
+        /// Remaining path in nibbles.
+        key: Vec<u8>,
+        value: Vec<u8>,
+        cache: NodeRef,
+    },
+    Extension {
+        /// Shared path in nibbles.
+        key: Vec<u8>,
+        child: Box<Node>,
+        cache: NodeRef,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+        cache: NodeRef,
+    },
+    /// Node that is only known by its hash, holds the RLP encoded hash.
+    Hash(Vec<u8>),
+}
+
+/// Merkle Patricia Trie that keeps all nodes in memory.
+///
+/// Keys are used as they are. For secure tries (state and storage tries) keys need
+/// to be hashed by the caller.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MerkleTrie {
+    root: Node,
+}
+
+impl MerkleTrie {
+    /// Creates new empty trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` if trie is empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.root, Node::Empty)
+    }
+
+    /// Builds the sparse trie with the given root from the RLP encoded nodes keyed by their hash.
+    ///
+    /// Nodes that are not found are kept as their hash, so the trie has the same root.
+    pub fn from_nodes(root: B256, nodes: &BTreeMap<B256, Bytes>) -> Result<Self, WitnessError> {
+        if root == EMPTY_ROOT_HASH {
+            return Ok(Self::new());
+        }
+        Ok(Self {
+            root: Node::decode_hashed(root, nodes)?,
+        })
+    }
+
+    /// Inserts the value at the given key, replacing the previous value.
+    ///
+    /// Empty values are not allowed in the trie; inserting an empty value removes the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path goes through a node that is only known by its hash, use
+    /// [`MerkleTrie::try_insert`] for the sparse tries.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) {
+        self.try_insert(key, value).expect("trie node is known")
+    }
+
+    /// Removes the key from the trie. Returns `true` if the key was present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path or the nodes next to it are only known by their hash, use
+    /// [`MerkleTrie::try_remove`] for the sparse tries.
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        self.try_remove(key).expect("trie node is known")
+    }
+
+    /// Returns the value at the given key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path goes through a node that is only known by its hash, use
+    /// [`MerkleTrie::try_get`] for the sparse tries.
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.try_get(key).expect("trie node is known")
+    }
+
+    /// Inserts the value at the given key, see [`MerkleTrie::insert`].
+    ///
+    /// Fails if the path goes through a node that is only known by its hash.
+    pub fn try_insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), WitnessError> {
+        if value.is_empty() {
+            return self.try_remove(key).map(drop);
+        }
+        self.root
+            .insert(&to_nibbles(key), value)
+            .map_err(WitnessError::MissingNode)
+    }
+
+    /// Removes the key from the trie, see [`MerkleTrie::remove`].
+    ///
+    /// Fails if the path goes through a node that is only known by its hash, or if the removal
+    /// merges the node next to the path that is only known by its hash.
+    pub fn try_remove(&mut self, key: &[u8]) -> Result<bool, WitnessError> {
+        self.root
+            .remove(&to_nibbles(key))
+            .map_err(WitnessError::MissingNode)
+    }
+
+    /// Returns the value at the given key.
+    ///
+    /// Fails if the path goes through a node that is only known by its hash.
+    pub fn try_get(&self, key: &[u8]) -> Result<Option<&[u8]>, WitnessError> {
+        self.root
+            .get(&to_nibbles(key))
+            .map_err(WitnessError::MissingNode)
+    }
+
+    /// Returns the RLP encoded nodes on the path to the key, starting from the root.
+    ///
+    /// Nodes shorter than 32 bytes are inlined in their parent and are not returned, except
+    /// the root. Proof of a missing key ends with the node where the path diverges.
+    pub fn proof(&mut self, key: &[u8]) -> Vec<Vec<u8>> {
+        let mut proof = Vec::new();
+        if !self.root.is_blinded() {
+            proof.push(self.root.encode());
+            self.root.proof(&to_nibbles(key), &mut proof);
+        }
+        proof
+    }
+
+    /// Returns the RLP encoded nodes on the paths to the keys, starting from the root.
+    ///
+    /// A sparse trie built from these nodes can insert and remove any of the keys: if a branch
+    /// has only one child off the paths, the child is returned as well, because removing the
+    /// keys can leave it as the only child that is merged into its parent.
+    pub fn multiproof<'a>(&mut self, keys: impl IntoIterator<Item = &'a [u8]>) -> Vec<Vec<u8>> {
+        let paths: Vec<Vec<u8>> = keys.into_iter().map(to_nibbles).collect();
+        let paths: Vec<&[u8]> = paths.iter().map(Vec::as_slice).collect();
+        let mut proof = Vec::new();
+        if !self.root.is_blinded() && !paths.is_empty() {
+            proof.push(self.root.encode());
+            self.root.multiproof(&paths, &mut proof);
+        }
+        proof
+    }
+
+    /// Returns the root hash of the trie.
+    ///
+    /// Only nodes that changed since the last call are rehashed.
+    pub fn root(&mut self) -> B256 {
+        if self.is_empty() {
+            return EMPTY_ROOT_HASH;
+        }
+        let node_ref = self.root.node_ref();
+        // reference is RLP encoded hash if node is 32 bytes or longer.
+        if node_ref.len() == 33 {
+            B256::from_slice(&node_ref[1..])
+        } else {
+            keccak256(node_ref)
+        }
+    }
+}
+
+impl Node {
+    /// Returns `true` if the node is empty or only known by its hash.
+    fn is_blinded(&self) -> bool {
+        matches!(self, Node::Empty | Node::Hash(_))
+    }
+
+    /// Returns the node with the given hash, or the hash if the node is not found.
+    fn decode_hashed(hash: B256, nodes: &BTreeMap<B256, Bytes>) -> Result<Node, WitnessError> {
+        match nodes.get(&hash) {
+            Some(node) if keccak256(node) != hash => Err(WitnessError::InvalidNode(hash)),
+            Some(node) => Node::decode(node, hash, nodes),
+            None => {
+                let mut node_ref = Vec::with_capacity(33);
+                hash.as_slice().encode(&mut node_ref);
+                Ok(Node::Hash(node_ref))
+            }
+        }
+    }
+
+    /// Decodes the RLP encoded node, `hash` is the hash of the node or of its hashed parent.
+    fn decode(
+        encoded: &[u8],
+        hash: B256,
+        nodes: &BTreeMap<B256, Bytes>,
+    ) -> Result<Node, WitnessError> {
+        let invalid = |_| WitnessError::InvalidNode(hash);
+        let items = decode_list(encoded).map_err(invalid)?;
+        let node = match items.as_slice() {
+            [path, value] => {
+                let path = Header::decode_bytes(&mut &path[..], false).map_err(invalid)?;
+                let (is_leaf, key) =
+                    from_hex_prefix(path).ok_or(WitnessError::InvalidNode(hash))?;
+                if is_leaf {
+                    let value = Header::decode_bytes(&mut &value[..], false).map_err(invalid)?;
+                    Node::Leaf {
+                        key,
+                        value: value.to_vec(),
+                        cache: None,
+                    }
+                } else {
+                    Node::Extension {
+                        key,
+                        child: Box::new(Node::decode_child(value, hash, nodes)?),
+                        cache: None,
+                    }
+                }
+            }
+            [children @ .., value] if children.len() == 16 => {
+                let mut decoded: Box<[Node; 16]> = Default::default();
+                for (node, child) in decoded.iter_mut().zip(children) {
+                    *node = Node::decode_child(child, hash, nodes)?;
+                }
+                let value = Header::decode_bytes(&mut &value[..], false).map_err(invalid)?;
+                Node::Branch {
+                    children: decoded,
+                    value: (!value.is_empty()).then(|| value.to_vec()),
+                    cache: None,
+                }
+            }
+            _ => return Err(WitnessError::InvalidNode(hash)),
+        };
+        Ok(node)
+    }
+
+    /// Decodes the child reference: empty, hash of the node or the inlined node.
+    fn decode_child(
+        child: &[u8],
+        hash: B256,
+        nodes: &BTreeMap<B256, Bytes>,
+    ) -> Result<Node, WitnessError> {
+        match child {
+            [EMPTY_STRING_CODE] => Ok(Node::Empty),
+            [0xa0, child_hash @ ..] if child_hash.len() == 32 => {
+                Node::decode_hashed(B256::from_slice(child_hash), nodes)
+            }
+            _ => Node::decode(child, hash, nodes),
+        }
+    }
+
+    fn clear_cache(&mut self) {
+        match self {
+            Node::Empty | Node::Hash(_) => (),
+            Node::Leaf { cache, .. }
+            | Node::Extension { cache, .. }
+            | Node::Branch { cache, .. } => *cache = None,
+        }
+    }
+
+    fn new_branch() -> Node {
+        Node::Branch {
+            children: Default::default(),
+            value: None,
+            cache: None,
+        }
+    }
+
+    /// Wraps the node into the extension if the shared key is not empty.
+    fn with_prefix(prefix: &[u8], node: Node) -> Node {
+        if prefix.is_empty() {
+            return node;
+        }
+        Node::Extension {
+            key: prefix.to_vec(),
+            child: Box::new(node),
+            cache: None,
+        }
+    }
+
+    /// Inserts the node at the remaining path into the branch.
+    fn branch_insert(branch: &mut Node, path: &[u8], node: impl FnOnce(&[u8]) -> Node) {
+        let Node::Branch {
+            children, value, ..
+        } = branch
+        else {
+            unreachable!("node is a branch")
+        };
+        match path.split_first() {
+            None => {
+                if let Node::Leaf { value: v, .. } = node(&[]) {
+                    *value = Some(v);
+                }
+            }
+            Some((nibble, rest)) => children[*nibble as usize] = node(rest),
+        }
+    }
+
+    /// Inserts the value, fails with the hash of the node on the path that is not known.
+    fn insert(&mut self, path: &[u8], new_value: Vec<u8>) -> Result<(), B256> {
+        self.clear_cache();
+        match self {
+            Node::Hash(node_ref) => return Err(blinded_hash(node_ref)),
+            Node::Empty => {
+                *self = Node::Leaf {
+                    key: path.to_vec(),
+                    value: new_value,
+                    cache: None,
+                };
+            }
+            Node::Leaf { key, value, .. } => {
+                if key.as_slice() == path {
+                    *value = new_value;
+                    return Ok(());
+                }
+                let common = common_prefix(key, path);
+                let mut branch = Node::new_branch();
+                let old_value = core::mem::take(value);
+                Node::branch_insert(&mut branch, &key[common..], |rest| Node::Leaf {
+                    key: rest.to_vec(),
+                    value: old_value,
+                    cache: None,
+                });
+                Node::branch_insert(&mut branch, &path[common..], |rest| Node::Leaf {
+                    key: rest.to_vec(),
+                    value: new_value,
+                    cache: None,
+                });
+                *self = Node::with_prefix(&path[..common], branch);
+            }
+            Node::Extension { key, child, .. } => {
+                let common = common_prefix(key, path);
+                if common == key.len() {
+                    return child.insert(&path[common..], new_value);
+                }
+                // split the extension at the first differing nibble.
+                let mut branch = Node::new_branch();
+                let old_child = core::mem::take(child.as_mut());
+                Node::branch_insert(&mut branch, &key[common..], |rest| {
+                    Node::with_prefix(rest, old_child)
+                });
+                Node::branch_insert(&mut branch, &path[common..], |rest| Node::Leaf {
+                    key: rest.to_vec(),
+                    value: new_value,
+                    cache: None,
+                });
+                *self = Node::with_prefix(&path[..common], branch);
+            }
+            Node::Branch {
+                children, value, ..
+            } => match path.split_first() {
+                None => *value = Some(new_value),
+                Some((nibble, rest)) => return children[*nibble as usize].insert(rest, new_value),
+            },
+        }
+        Ok(())
+    }
+
+    /// Removes the value, fails with the hash of the node that is not known.
+    fn remove(&mut self, path: &[u8]) -> Result<bool, B256> {
+        let removed = match self {
+            Node::Empty => false,
+            Node::Hash(node_ref) => return Err(blinded_hash(node_ref)),
+            Node::Leaf { key, .. } => {
+                if key.as_slice() != path {
+                    return Ok(false);
+                }
+                *self = Node::Empty;
+                return Ok(true);
+            }
+            Node::Extension { key, child, .. } => match path.strip_prefix(key.as_slice()) {
+                Some(rest) => child.remove(rest)?,
+                None => false,
+            },
+            Node::Branch {
+                children, value, ..
+            } => match path.split_first() {
+                None => value.take().is_some(),
+                Some((nibble, rest)) => children[*nibble as usize].remove(rest)?,
+            },
+        };
+        if removed {
+            self.clear_cache();
+            self.normalize()?;
+        }
+        Ok(removed)
+    }
+
+    /// Restores the canonical form of the node after removal.
+    ///
+    /// Fails with the hash of the only child of the branch if the child is not known.
+    fn normalize(&mut self) -> Result<(), B256> {
+        match self {
+            Node::Extension { key, child, .. } => match core::mem::take(child.as_mut()) {
+                Node::Empty => *self = Node::Empty,
+                Node::Leaf {
+                    key: child_key,
+                    value,
+                    ..
+                } => {
+                    key.extend_from_slice(&child_key);
+                    *self = Node::Leaf {
+                        key: core::mem::take(key),
+                        value,
+                        cache: None,
+                    };
+                }
+                Node::Extension {
+                    key: child_key,
+                    child: grandchild,
+                    ..
+                } => {
+                    key.extend_from_slice(&child_key);
+                    *child = grandchild;
+                }
+                branch => **child = branch,
+            },
+            Node::Branch {
+                children, value, ..
+            } => {
+                let mut used = children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, child)| !matches!(child, Node::Empty));
+                let first = used.next().map(|(i, _)| i);
+                let more_than_one = used.next().is_some();
+                match (first, more_than_one, value.is_some()) {
+                    (None, _, false) => *self = Node::Empty,
+                    (None, _, true) => {
+                        *self = Node::Leaf {
+                            key: Vec::new(),
+                            value: value.take().unwrap_or_default(),
+                            cache: None,
+                        }
+                    }
+                    (Some(index), false, false) => {
+                        // the child is merged with the extension if it is a leaf or an extension.
+                        if let Node::Hash(node_ref) = &children[index] {
+                            return Err(blinded_hash(node_ref));
+                        }
+                        let child = core::mem::take(&mut children[index]);
+                        let mut node = Node::Extension {
+                            key: std::vec![index as u8],
+                            child: Box::new(child),
+                            cache: None,
+                        };
+                        node.normalize()?;
+                        *self = node;
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Returns the value, fails with the hash of the node on the path that is not known.
+    fn get(&self, path: &[u8]) -> Result<Option<&[u8]>, B256> {
+        match self {
+            Node::Empty => Ok(None),
+            Node::Hash(node_ref) => Err(blinded_hash(node_ref)),
+            Node::Leaf { key, value, .. } => {
+                Ok((key.as_slice() == path).then_some(value.as_slice()))
+            }
+            Node::Extension { key, child, .. } => match path.strip_prefix(key.as_slice()) {
+                Some(rest) => child.get(rest),
+                None => Ok(None),
+            },
+            Node::Branch {
+                children, value, ..
+            } => match path.split_first() {
+                None => Ok(value.as_deref()),
+                Some((nibble, rest)) => children[*nibble as usize].get(rest),
+            },
+        }
+    }
+
+    /// Appends the hashed descendant nodes on the path.
+    fn proof(&mut self, path: &[u8], proof: &mut Vec<Vec<u8>>) {
+        let child = match self {
+            Node::Empty | Node::Leaf { .. } | Node::Hash(_) => return,
+            Node::Extension { key, child, .. } => match path.strip_prefix(key.as_slice()) {
+                Some(rest) => (child.as_mut(), rest),
+                None => return,
+            },
+            Node::Branch { children, .. } => match path.split_first() {
+                Some((nibble, rest)) => (&mut children[*nibble as usize], rest),
+                None => return,
+            },
+        };
+        let (child, rest) = child;
+        child.push_hashed(proof);
+        child.proof(rest, proof);
+    }
+
+    /// Appends the hashed descendant nodes on the paths and the only children off the paths.
+    fn multiproof(&mut self, paths: &[&[u8]], proof: &mut Vec<Vec<u8>>) {
+        match self {
+            Node::Empty | Node::Leaf { .. } | Node::Hash(_) => (),
+            Node::Extension { key, child, .. } => {
+                let rest: Vec<&[u8]> = paths
+                    .iter()
+                    .filter_map(|path| path.strip_prefix(key.as_slice()))
+                    .collect();
+                if !rest.is_empty() {
+                    child.push_hashed(proof);
+                    child.multiproof(&rest, proof);
+                }
+            }
+            Node::Branch { children, .. } => {
+                let mut by_nibble: [Vec<&[u8]>; 16] = Default::default();
+                for path in paths {
+                    if let Some((nibble, rest)) = path.split_first() {
+                        by_nibble[*nibble as usize].push(rest);
+                    }
+                }
+                let mut off_paths = children
+                    .iter_mut()
+                    .zip(&by_nibble)
+                    .filter(|(child, rest)| rest.is_empty() && !matches!(child, Node::Empty));
+                if let (Some((child, _)), None) = (off_paths.next(), off_paths.next()) {
+                    child.push_hashed(proof);
+                }
+                for (child, rest) in children.iter_mut().zip(&by_nibble) {
+                    if !rest.is_empty() {
+                        child.push_hashed(proof);
+                        child.multiproof(rest, proof);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Appends the node if it is known and referenced by its hash.
+    fn push_hashed(&mut self, proof: &mut Vec<Vec<u8>>) {
+        if !self.is_blinded() && self.node_ref().len() == 33 {
+            proof.push(self.encode());
+        }
+    }
+
+    /// Returns node reference: RLP of the node if it is shorter than 32 bytes,
+    /// otherwise RLP encoded keccak256 hash of it.
+    fn node_ref(&mut self) -> &[u8] {
+        if let Node::Hash(node_ref) = self {
+            return node_ref;
+        }
+        let encoded = match self {
+            Node::Empty => return &[EMPTY_STRING_CODE],
+            Node::Hash(_) => unreachable!("hash node returned earlier"),
+            Node::Leaf { cache, .. }
+            | Node::Extension { cache, .. }
+            | Node::Branch { cache, .. }
+                if cache.is_some() =>
+            {
+                None
+            }
+            _ => Some(self.encode()),
+        };
+        let (Node::Leaf { cache, .. } | Node::Extension { cache, .. } | Node::Branch { cache, .. }) =
+            self
+        else {
+            unreachable!("empty and hash nodes returned earlier")
+        };
+        if let Some(encoded) = encoded {
+            *cache = Some(if encoded.len() < 32 {
+                encoded
+            } else {
+                let mut out = Vec::with_capacity(33);
+                keccak256(&encoded).as_slice().encode(&mut out);
+                out
+            });
+        }
+        cache.as_deref().unwrap_or_default()
+    }
+
+    /// RLP encodes the node, computing references of children.
+    ///
+    /// Node that is only known by its hash returns the RLP encoded hash.
+    fn encode(&mut self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match self {
+            Node::Empty => return std::vec![EMPTY_STRING_CODE],
+            Node::Hash(node_ref) => return node_ref.clone(),
+            Node::Leaf { key, value, .. } => {
+                hex_prefix(key, true).as_slice().encode(&mut payload);
+                value.as_slice().encode(&mut payload);
+            }
+            Node::Extension { key, child, .. } => {
+                hex_prefix(key, false).as_slice().encode(&mut payload);
+                payload.extend_from_slice(child.node_ref());
+            }
+            Node::Branch {
+                children, value, ..
+            } => {
+                for child in children.iter_mut() {
+                    payload.extend_from_slice(child.node_ref());
+                }
+                value.as_deref().unwrap_or_default().encode(&mut payload);
+            }
+        }
+        let mut out = Vec::with_capacity(payload.len() + 3);
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(&mut out);
+        out.extend_from_slice(&payload);
+        out
+    }
+}
+
+/// Returns the hash of the node from its RLP encoded hash.
+fn blinded_hash(node_ref: &[u8]) -> B256 {
+    B256::from_slice(&node_ref[1..])
+}
+
+/// Splits the RLP list of the trie node into its raw items.
+fn decode_list(mut node: &[u8]) -> alloy_rlp::Result<Vec<&[u8]>> {
+    let header = Header::decode(&mut node)?;
+    if !header.list || header.payload_length != node.len() {
+        return Err(alloy_rlp::Error::UnexpectedString);
+    }
+    let mut items = Vec::with_capacity(17);
+    while !node.is_empty() {
+        let mut rest = node;
+        let item = Header::decode(&mut rest)?;
+        let length = node.len() - rest.len() + item.payload_length;
+        if length > node.len() {
+            return Err(alloy_rlp::Error::InputTooShort);
+        }
+        let (item, rest) = node.split_at(length);
+        items.push(item);
+        node = rest;
+    }
+    Ok(items)
+}
+
+/// Decodes the hex prefix encoded path, returns `true` if the node is a leaf.
+fn from_hex_prefix(encoded: &[u8]) -> Option<(bool, Vec<u8>)> {
+    let (first, rest) = encoded.split_first()?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return None;
+    }
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(to_nibbles(rest));
+    Some((flag & 2 == 2, path))
+}
+
+/// Splits bytes into nibbles.
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|b| [b >> 4, b & 0x0f]).collect()
+}
+
+/// Length of the common prefix of two nibble paths.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Hex prefix encoding of the nibble path.
+fn hex_prefix(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let odd = nibbles.len() % 2 == 1;
+    let flag = (u8::from(is_leaf) << 1) | u8::from(odd);
+    let mut out = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if odd {
+        out.push((flag << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        out.push(flag << 4);
+        nibbles
+    };
+    out.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use hash_db::Hasher;
+    use plain_hasher::PlainHasher;
+    use primitives::HashMap;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+    pub(crate) struct KeccakHasher;
+
+    impl Hasher for KeccakHasher {
+        type Out = B256;
+        type StdHasher = PlainHasher;
+        const LENGTH: usize = 32;
+
+        fn hash(x: &[u8]) -> Self::Out {
+            keccak256(x)
+        }
+    }
+
+    fn expected_root(map: &HashMap<Vec<u8>, Vec<u8>>) -> B256 {
+        triehash::trie_root::<KeccakHasher, _, _, _>(map.clone())
+    }
+
+    #[test]
+    fn empty_root() {
+        assert_eq!(MerkleTrie::new().root(), EMPTY_ROOT_HASH);
+        assert_eq!(
+            expected_root(&HashMap::default()),
+            EMPTY_ROOT_HASH,
+            "triehash empty root"
+        );
+    }
+
+    #[test]
+    fn hex_prefix_encoding() {
+        assert_eq!(hex_prefix(&[1, 2, 3, 4, 5], false), [0x11, 0x23, 0x45]);
+        assert_eq!(
+            hex_prefix(&[0, 1, 2, 3, 4, 5], false),
+            [0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix(&[0, 15, 1, 12, 11, 8], true),
+            [0x20, 0x0f, 0x1c, 0xb8]
+        );
+        assert_eq!(hex_prefix(&[15, 1, 12, 11, 8], true), [0x3f, 0x1c, 0xb8]);
+    }
+
+    #[test]
+    fn random_inserts_and_removes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut trie = MerkleTrie::new();
+        let mut map = HashMap::default();
+        for round in 0..2000 {
+            // short keys produce extensions and branches with values.
+            let key_len = rng.random_range(1..4);
+            let key: Vec<u8> = (0..key_len).map(|_| rng.random_range(0..4)).collect();
+            if rng.random_bool(0.3) {
+                assert_eq!(trie.remove(&key), map.remove(&key).is_some());
+            } else {
+                let value_len = rng.random_range(1..40);
+                let value: Vec<u8> = (0..value_len).map(|_| rng.random()).collect();
+                trie.insert(&key, value.clone());
+                map.insert(key, value);
+            }
+            if round % 50 == 0 {
+                assert_eq!(trie.root(), expected_root(&map), "round {round}");
+            }
+        }
+        for (key, value) in &map {
+            assert_eq!(trie.get(key), Some(value.as_slice()));
+        }
+        assert_eq!(trie.root(), expected_root(&map));
+
+        // remove everything
+        for key in map.keys() {
+            assert!(trie.remove(key));
+        }
+        assert!(trie.is_empty());
+        assert_eq!(trie.root(), EMPTY_ROOT_HASH);
+    }
+
+    fn nodes_by_hash(nodes: Vec<Vec<u8>>) -> BTreeMap<B256, Bytes> {
+        nodes
+            .into_iter()
+            .map(|node| (keccak256(&node), node.into()))
+            .collect()
+    }
+
+    #[test]
+    fn sparse_trie_from_multiproof() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut trie = MerkleTrie::new();
+        let keys: Vec<B256> = (0..300u64).map(|i| keccak256(i.to_be_bytes())).collect();
+        for key in &keys {
+            let value_len = rng.random_range(1..40);
+            let value: Vec<u8> = (0..value_len).map(|_| rng.random()).collect();
+            trie.insert(key.as_slice(), value);
+        }
+        let root = trie.root();
+
+        // present and missing keys.
+        let changed: Vec<B256> = keys
+            .iter()
+            .step_by(7)
+            .copied()
+            .chain((1000..1010u64).map(|i| keccak256(i.to_be_bytes())))
+            .collect();
+        let nodes = nodes_by_hash(trie.multiproof(changed.iter().map(B256::as_slice)));
+        let mut sparse = MerkleTrie::from_nodes(root, &nodes).unwrap();
+        assert_eq!(sparse.root(), root);
+        assert_eq!(
+            sparse.try_get(keys[0].as_slice()).unwrap(),
+            trie.get(keys[0].as_slice())
+        );
+        assert!(matches!(
+            sparse.try_get(keys[1].as_slice()),
+            Err(WitnessError::MissingNode(_))
+        ));
+
+        for (i, key) in changed.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(
+                    sparse.try_remove(key.as_slice()).unwrap(),
+                    trie.remove(key.as_slice())
+                );
+            } else {
+                trie.insert(key.as_slice(), std::vec![i as u8; 40]);
+                sparse
+                    .try_insert(key.as_slice(), std::vec![i as u8; 40])
+                    .unwrap();
+            }
+        }
+        assert_eq!(sparse.root(), trie.root());
+    }
+
+    #[test]
+    fn sparse_trie_removal_needs_sibling() {
+        let (first, second) = (B256::repeat_byte(0x11), B256::repeat_byte(0x22));
+        let mut trie = MerkleTrie::new();
+        trie.insert(first.as_slice(), std::vec![1; 40]);
+        trie.insert(second.as_slice(), std::vec![2; 40]);
+        let root = trie.root();
+
+        // only child of the branch next to the path is in the proof.
+        let mut nodes = nodes_by_hash(trie.multiproof([first.as_slice()]));
+        assert_eq!(nodes.len(), 3);
+        let sibling = keccak256(trie.proof(second.as_slice()).last().unwrap());
+        let mut sparse = MerkleTrie::from_nodes(root, &nodes).unwrap();
+        assert_eq!(sparse.try_remove(first.as_slice()), Ok(true));
+        trie.remove(first.as_slice());
+        assert_eq!(sparse.root(), trie.root());
+
+        nodes.remove(&sibling);
+        let mut sparse = MerkleTrie::from_nodes(root, &nodes).unwrap();
+        assert_eq!(
+            sparse.try_remove(first.as_slice()),
+            Err(WitnessError::MissingNode(sibling))
+        );
+
+        // node has to match its hash.
+        let nodes = BTreeMap::from([(root, Bytes::from_static(&[0xc0]))]);
+        assert_eq!(
+            MerkleTrie::from_nodes(root, &nodes),
+            Err(WitnessError::InvalidNode(root))
+        );
+    }
+}